@@ -0,0 +1,12 @@
+//! генерация кода gRPC сервиса заказов из proto/orders.proto
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // protoc из зависимостей, чтобы сборка не требовала установленного protoc
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+
+    tonic_build::configure()
+        .build_client(true)
+        .build_server(true)
+        .compile_protos(&["proto/orders.proto"], &["proto"])?;
+
+    Ok(())
+}
@@ -0,0 +1,38 @@
+-- перевод денежных колонок существующей базы в bigint и сумм - из основных единиц валюты в минимальные:
+-- суммы умножаются на 10^N, где N - количество знаков минимальной единицы валюты заказа
+-- (товары берут валюту из оплаты своего заказа). Новой базе из db_init/init.sql миграция не нужна.
+-- Выполняется один раз, в одной транзакции; повторный запуск прерывается, не меняя суммы.
+BEGIN;
+
+-- защита от повторного умножения сумм
+DO $$
+BEGIN
+    IF (SELECT data_type FROM information_schema.columns
+        WHERE table_name = 'payments' AND column_name = 'amount') = 'bigint' THEN
+        RAISE EXCEPTION 'миграция уже применена: payments.amount уже bigint';
+    END IF;
+END $$;
+
+ALTER TABLE payments ALTER COLUMN amount TYPE bigint, ALTER COLUMN delivery_cost TYPE bigint,
+    ALTER COLUMN goods_total TYPE bigint, ALTER COLUMN custom_fee TYPE bigint, ALTER COLUMN currency TYPE CHAR(3);
+ALTER TABLE items ALTER COLUMN price TYPE bigint, ALTER COLUMN total_price TYPE bigint;
+
+-- множитель минимальной единицы для каждого заказа
+CREATE TEMP TABLE minor_units ON COMMIT DROP AS
+SELECT order_uid, CASE
+    WHEN upper(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG',
+                             'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN upper(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    WHEN upper(currency) IN ('CLF', 'UYW') THEN 10000
+    ELSE 100
+END AS factor
+FROM payments;
+
+UPDATE payments p SET currency = upper(p.currency), amount = p.amount * m.factor,
+    delivery_cost = p.delivery_cost * m.factor, goods_total = p.goods_total * m.factor,
+    custom_fee = p.custom_fee * m.factor
+FROM minor_units m WHERE p.order_uid = m.order_uid;
+UPDATE items i SET price = i.price * m.factor, total_price = i.total_price * m.factor
+FROM minor_units m WHERE i.order_uid = m.order_uid;
+
+COMMIT;
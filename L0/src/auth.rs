@@ -0,0 +1,417 @@
+//! аутентификация по API-ключам: ключи хранятся в Postgres в виде хэшей, доступ к маршрутам - по scope
+use crate::db::postgres_db::PostgresDB;
+use crate::model::ServerError;
+use crate::rate_limit::API_KEY_HEADER;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::warn;
+use uuid::Uuid;
+
+// префикс выдаваемых ключей
+const API_KEY_PREFIX: &str = "l0_";
+// сколько проверенный ключ хранится в памяти без повторного запроса к Postgres;
+// отозванный ключ принимается ещё до этого времени (отзыв идёт из скрипта, а не через сервер)
+pub const VERIFIED_KEY_TTL: Duration = Duration::from_secs(10);
+// очередь записей аудита: при переполнении новые записи отбрасываются, а не копятся в памяти
+const USAGE_QUEUE_CAPACITY: usize = 10_000;
+// сколько записей аудита вставляется одним запросом
+const USAGE_BATCH_SIZE: usize = 500;
+// last_used_at ключа обновляется не чаще этого интервала
+const LAST_USED_INTERVAL: Duration = Duration::from_secs(60);
+
+// права доступа ключа
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum Scope {
+    #[serde(rename = "orders:read")]
+    OrdersRead,
+    #[serde(rename = "orders:write")]
+    OrdersWrite,
+    #[serde(rename = "orders:admin")]
+    OrdersAdmin,
+}
+
+impl Scope {
+    // строковое представление scope
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::OrdersRead => "orders:read",
+            Scope::OrdersWrite => "orders:write",
+            Scope::OrdersAdmin => "orders:admin",
+        }
+    }
+
+    // покрывает ли этот scope требуемый (admin включает все остальные)
+    pub fn allows(&self, required: Scope) -> bool {
+        *self == required || *self == Scope::OrdersAdmin
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.trim() {
+            "orders:read" => Ok(Scope::OrdersRead),
+            "orders:write" => Ok(Scope::OrdersWrite),
+            "orders:admin" => Ok(Scope::OrdersAdmin),
+            other => Err(format!(
+                "неизвестный scope {:?}, допустимые: orders:read, orders:write, orders:admin",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// структура API-ключа (без самого ключа - в базе хранится только хэш)
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct ApiKey {
+    pub key_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+impl ApiKey {
+    // есть ли у ключа нужный scope
+    pub fn has_scope(&self, required: Scope) -> bool {
+        self.scopes.iter().any(|scope| scope.allows(required))
+    }
+}
+
+// хэш ключа для хранения и поиска в базе
+pub fn hash_api_key(api_key: &str) -> String {
+    format!("{:x}", Sha256::digest(api_key.as_bytes()))
+}
+
+// генерация нового случайного ключа, возвращает сам ключ и его хэш
+pub fn generate_api_key() -> (String, String) {
+    let api_key = format!(
+        "{}{}{}",
+        API_KEY_PREFIX,
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    );
+    let key_hash = hash_api_key(&api_key);
+
+    (api_key, key_hash)
+}
+
+// запись аудита об одном использовании ключа
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiKeyUsage {
+    pub key_id: Uuid,
+    pub method: String,
+    pub path: String,
+    pub status: i32,
+}
+
+// ключи пачки, у которых пора обновить last_used_at, с отметкой времени обновления
+fn keys_to_touch(
+    touched: &mut HashMap<Uuid, Instant>,
+    batch: &[ApiKeyUsage],
+    now: Instant,
+) -> Vec<Uuid> {
+    // давно обновлённые ключи забываются, чтобы карта не росла
+    touched.retain(|_, touched_at| now.duration_since(*touched_at) < LAST_USED_INTERVAL);
+
+    let mut key_ids = Vec::new();
+    for usage in batch {
+        if let Entry::Vacant(entry) = touched.entry(usage.key_id) {
+            entry.insert(now);
+            key_ids.push(usage.key_id);
+        }
+    }
+    key_ids
+}
+
+// фоновая запись аудита: пачками из очереди, last_used_at - не чаще раза в интервал на ключ
+async fn write_usage(
+    postgres_instance: PostgresDB,
+    mut receiver: mpsc::Receiver<ApiKeyUsage>,
+    dropped: Arc<AtomicU64>,
+) {
+    let mut batch = Vec::with_capacity(USAGE_BATCH_SIZE);
+    let mut touched = HashMap::new();
+
+    while receiver.recv_many(&mut batch, USAGE_BATCH_SIZE).await > 0 {
+        if let Err(err) = postgres_instance.insert_api_key_usage(&batch).await {
+            warn!(
+                "Не удалось записать {} использований ключей: {:?}",
+                batch.len(),
+                err
+            );
+        }
+
+        let key_ids = keys_to_touch(&mut touched, &batch, Instant::now());
+        if !key_ids.is_empty() {
+            if let Err(err) = postgres_instance.touch_api_keys(&key_ids).await {
+                warn!("Не удалось обновить last_used_at ключей: {:?}", err);
+            }
+        }
+
+        let dropped = dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            warn!("Очередь аудита переполнена, отброшено записей: {}", dropped);
+        }
+        batch.clear();
+    }
+}
+
+// проверка ключей с коротким кэшем проверенных ключей в памяти
+pub struct Authenticator {
+    postgres_instance: PostgresDB,
+    verified_keys: Mutex<HashMap<String, (ApiKey, Instant)>>,
+    usage_sender: mpsc::Sender<ApiKeyUsage>,
+    // записи аудита, отброшенные из-за переполненной очереди
+    dropped_usage: Arc<AtomicU64>,
+}
+
+impl Authenticator {
+    // инициализация и запуск фоновой записи аудита, вызывается внутри рантайма tokio
+    pub fn new(postgres_instance: PostgresDB) -> Self {
+        let (usage_sender, receiver) = mpsc::channel(USAGE_QUEUE_CAPACITY);
+        let dropped_usage = Arc::new(AtomicU64::new(0));
+        tokio::spawn(write_usage(
+            postgres_instance.clone(),
+            receiver,
+            dropped_usage.clone(),
+        ));
+
+        Authenticator {
+            postgres_instance,
+            verified_keys: Mutex::new(HashMap::new()),
+            usage_sender,
+            dropped_usage,
+        }
+    }
+
+    // поиск действующего ключа по его хэшу
+    async fn find_key(&self, key_hash: &str) -> Result<Option<ApiKey>, ServerError> {
+        let now = Instant::now();
+
+        // проверенный недавно ключ
+        {
+            let mut verified_keys = self
+                .verified_keys
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            verified_keys.retain(|_, (_, verified_at)| now.duration_since(*verified_at) < VERIFIED_KEY_TTL);
+            if let Some((api_key, _)) = verified_keys.get(key_hash) {
+                return Ok(Some(api_key.clone()));
+            }
+        }
+
+        // запрос к базе данных с тайм-аутом
+        let api_key = match tokio::time::timeout(Duration::from_secs(1), async {
+            self.postgres_instance.get_api_key_by_hash(key_hash).await
+        })
+        .await
+        {
+            Ok(Ok(api_key)) => api_key,
+            Ok(Err(err)) => return Err(ServerError::PostgresError(err.into())),
+            Err(_) => {
+                return Err(ServerError::TimeoutError(
+                    "Проверка API-ключа".to_string(),
+                ))
+            }
+        };
+
+        if let Some(api_key) = &api_key {
+            self.verified_keys
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .insert(key_hash.to_string(), (api_key.clone(), now));
+        }
+
+        Ok(api_key)
+    }
+
+    // проверка ключа и его scope для запроса method/path,
+    // Unauthorized - ключ не передан или неверен, Forbidden - не хватает прав (записывается в аудит)
+    pub async fn authorize(
+        &self,
+        api_key: Option<&str>,
+        scope: Scope,
+        method: &str,
+        path: &str,
+    ) -> Result<ApiKey, ServerError> {
+        let api_key = api_key.ok_or_else(|| {
+            ServerError::Unauthorized(format!("Не передан ключ {}", API_KEY_HEADER))
+        })?;
+
+        let api_key = self.find_key(&hash_api_key(api_key)).await?.ok_or_else(|| {
+            ServerError::Unauthorized("Неверный или отозванный API-ключ".to_string())
+        })?;
+
+        if !api_key.has_scope(scope) {
+            self.record_usage(
+                api_key.key_id,
+                method.to_string(),
+                path.to_string(),
+                StatusCode::FORBIDDEN.as_u16(),
+            );
+            return Err(ServerError::Forbidden(format!(
+                "Ключу {:?} не хватает прав {}",
+                api_key.name, scope
+            )));
+        }
+
+        Ok(api_key)
+    }
+
+    // запись использования ключа для аудита через очередь фоновой записи, чтобы не задерживать
+    // ответ; при переполненной очереди запись отбрасывается
+    pub fn record_usage(&self, key_id: Uuid, method: String, path: String, status: u16) {
+        let usage = ApiKeyUsage {
+            key_id,
+            method,
+            path,
+            status: status as i32,
+        };
+        match self.usage_sender.try_send(usage) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                self.dropped_usage.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Closed(_)) => {
+                warn!(
+                    "Фоновая запись аудита остановлена, использование ключа {} не записано",
+                    key_id
+                )
+            }
+        }
+    }
+}
+
+// состояние middleware одного маршрута
+#[derive(Clone)]
+pub struct RouteAuth {
+    authenticator: Arc<Authenticator>,
+    scope: Scope,
+}
+
+impl RouteAuth {
+    pub fn new(authenticator: Arc<Authenticator>, scope: Scope) -> Self {
+        RouteAuth {
+            authenticator,
+            scope,
+        }
+    }
+}
+
+// middleware проверки API-ключа и его scope, 401 - ключ не передан или неверен, 403 - не хватает прав
+pub async fn require_scope(
+    State(route_auth): State<RouteAuth>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let api_key = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+
+    let api_key = match route_auth
+        .authenticator
+        .authorize(api_key, route_auth.scope, &method, &path)
+        .await
+    {
+        Ok(api_key) => api_key,
+        Err(err) => return err.into_response(),
+    };
+
+    // ключ доступен обработчикам запроса
+    let key_id = api_key.key_id;
+    request.extensions_mut().insert(api_key);
+
+    let response = next.run(request).await;
+    route_auth
+        .authenticator
+        .record_usage(key_id, method, path, response.status().as_u16());
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        generate_api_key, hash_api_key, keys_to_touch, ApiKeyUsage, Scope, LAST_USED_INTERVAL,
+    };
+    use std::collections::HashMap;
+    use std::time::Instant;
+    use uuid::Uuid;
+
+    #[test]
+    // admin включает остальные права
+    fn test_scope_allows() {
+        assert!(Scope::OrdersRead.allows(Scope::OrdersRead));
+        assert!(!Scope::OrdersRead.allows(Scope::OrdersWrite));
+        assert!(!Scope::OrdersWrite.allows(Scope::OrdersAdmin));
+        assert!(Scope::OrdersAdmin.allows(Scope::OrdersRead));
+        assert!(Scope::OrdersAdmin.allows(Scope::OrdersWrite));
+        assert_eq!("orders:write".parse::<Scope>(), Ok(Scope::OrdersWrite));
+        assert!("orders:delete".parse::<Scope>().is_err());
+    }
+
+    #[test]
+    // генерация ключей и их хэшей
+    fn test_generate_api_key() {
+        let (api_key, key_hash) = generate_api_key();
+        let (other_key, _) = generate_api_key();
+
+        assert!(api_key.starts_with("l0_"));
+        assert_eq!(api_key.len(), 67);
+        assert_ne!(api_key, other_key);
+        assert_eq!(key_hash, hash_api_key(&api_key));
+        assert_eq!(key_hash.len(), 64);
+    }
+
+    #[test]
+    // last_used_at ключа обновляется один раз за интервал, даже если он в нескольких пачках
+    fn test_keys_to_touch() {
+        let usage = |key_id: Uuid| ApiKeyUsage {
+            key_id,
+            method: "GET".to_string(),
+            path: "/orders".to_string(),
+            status: 200,
+        };
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = Instant::now();
+        let mut touched = HashMap::new();
+
+        assert_eq!(
+            keys_to_touch(&mut touched, &[usage(first), usage(first)], now),
+            [first]
+        );
+        assert_eq!(
+            keys_to_touch(&mut touched, &[usage(first), usage(second)], now),
+            [second]
+        );
+
+        let later = now + LAST_USED_INTERVAL;
+        assert_eq!(keys_to_touch(&mut touched, &[usage(first)], later), [first]);
+        assert_eq!(touched.len(), 1);
+    }
+}
@@ -1,6 +1,7 @@
 //! скрипт для добавления данных в базу через API
 use l0::model::Order;
 use reqwest::Client;
+use std::env;
 use std::fs::File;
 use std::io::Read;
 
@@ -14,11 +15,15 @@ async fn main() {
     // десериализация прочтённых данных
     let orders: Vec<Order> = serde_json::from_str(&contents).unwrap();
 
+    // ключ со scope orders:write
+    let api_key = env::var("L0_API_KEY").expect("L0_API_KEY не найден в переменных окружения");
+
     // http post запросы с помощью reqwest
     let client = Client::new();
     for order in orders {
         client
             .post("http://127.0.0.1:3000/orders")
+            .header("x-api-key", &api_key)
             .json(&order)
             .send()
             .await
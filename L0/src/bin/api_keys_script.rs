@@ -0,0 +1,98 @@
+//! скрипт администрирования API-ключей: создание, список и отзыв
+use l0::auth::{generate_api_key, Scope, VERIFIED_KEY_TTL};
+use l0::config::DbConfig;
+use l0::db::postgres_db::PostgresDB;
+use std::env;
+use std::process::exit;
+use uuid::Uuid;
+
+// подсказка по использованию
+const USAGE: &str = "использование:
+    api_keys_script create <имя> <scope[,scope...]>   (orders:read, orders:write, orders:admin)
+    api_keys_script list
+    api_keys_script revoke <key_id>   (серверы принимают ключ ещё до 10 с после отзыва)";
+
+#[tokio::main]
+async fn main() {
+    // аргументы командной строки
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    // подключение к базе данных Postgres из переменных окружения
+    let db_config = DbConfig::new();
+    let postgres_instance = PostgresDB::new(&db_config).await.unwrap();
+
+    match args.iter().map(String::as_str).collect::<Vec<&str>>().as_slice() {
+        // создание ключа, сам ключ печатается один раз - в базе хранится только хэш
+        ["create", name, scopes] => {
+            let scopes: Vec<Scope> = match scopes.split(',').map(str::parse).collect() {
+                Ok(scopes) => scopes,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    exit(2);
+                }
+            };
+
+            let (api_key, key_hash) = generate_api_key();
+            let created = postgres_instance
+                .insert_api_key(name, &key_hash, &scopes)
+                .await
+                .unwrap();
+
+            println!("key_id: {}", created.key_id);
+            println!("api_key: {}", api_key);
+            println!("ключ показывается один раз, сохраните его");
+        }
+        // список всех ключей
+        ["list"] => {
+            let api_keys = postgres_instance.list_api_keys().await.unwrap();
+
+            println!(
+                "{:<36}  {:<20}  {:<40}  {:<19}  {:<19}  {:<19}",
+                "key_id", "name", "scopes", "created_at", "last_used_at", "revoked_at"
+            );
+            for api_key in api_keys {
+                let scopes: Vec<&str> = api_key.scopes.iter().map(Scope::as_str).collect();
+                let format_time = |time: Option<chrono::NaiveDateTime>| {
+                    time.map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                        .unwrap_or_else(|| "-".to_string())
+                };
+
+                println!(
+                    "{:<36}  {:<20}  {:<40}  {:<19}  {:<19}  {:<19}",
+                    api_key.key_id,
+                    api_key.name,
+                    scopes.join(","),
+                    format_time(Some(api_key.created_at)),
+                    format_time(api_key.last_used_at),
+                    format_time(api_key.revoked_at),
+                );
+            }
+        }
+        // отзыв ключа по key_id
+        ["revoke", key_id] => {
+            let key_id: Uuid = match key_id.parse() {
+                Ok(key_id) => key_id,
+                Err(err) => {
+                    eprintln!("неверный key_id {:?}: {}", key_id, err);
+                    exit(2);
+                }
+            };
+
+            if postgres_instance.revoke_api_key(&key_id).await.unwrap() {
+                println!("ключ {} отозван", key_id);
+                // кэш проверенных ключей в памяти серверов отсюда не очистить
+                println!(
+                    "серверы, недавно проверившие ключ, принимают его ещё до {} с",
+                    VERIFIED_KEY_TTL.as_secs()
+                );
+            } else {
+                eprintln!("ключ {} не найден или уже отозван", key_id);
+                exit(1);
+            }
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    }
+}
@@ -86,14 +86,14 @@ async fn main() {
                      transaction VARCHAR,
                      order_uid UUID UNIQUE REFERENCES orders(order_uid),
                      request_id VARCHAR,
-                     currency VARCHAR,
+                     currency CHAR(3),
                      provider VARCHAR,
-                     amount integer,
+                     amount bigint,
                      payment_dt integer,
                      bank VARCHAR,
-                     delivery_cost integer,
-                     goods_total integer,
-                     custom_fee integer
+                     delivery_cost bigint,
+                     goods_total bigint,
+                     custom_fee bigint
                 );",
             &[],
         )
@@ -107,12 +107,12 @@ async fn main() {
                     order_uid UUID REFERENCES orders(order_uid),
                     chrt_id integer,
                     track_number VARCHAR,
-                    price integer,
+                    price bigint,
                     rid VARCHAR,
                     name VARCHAR,
                     sale integer,
                     size VARCHAR,
-                    total_price integer,
+                    total_price bigint,
                     nm_id integer,
                     brand VARCHAR,
                     status integer
@@ -121,4 +121,35 @@ async fn main() {
         )
         .await
         .unwrap();
+
+    client
+        .query(
+            "CREATE TABLE api_keys (
+                    key_id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+                    name VARCHAR NOT NULL,
+                    key_hash CHAR(64) UNIQUE NOT NULL,
+                    scopes TEXT[] NOT NULL,
+                    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+                    revoked_at TIMESTAMP,
+                    last_used_at TIMESTAMP
+                );",
+            &[],
+        )
+        .await
+        .unwrap();
+
+    client
+        .query(
+            "CREATE TABLE api_key_usage (
+                    usage_id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+                    key_id UUID REFERENCES api_keys(key_id),
+                    method VARCHAR,
+                    path VARCHAR,
+                    status integer,
+                    used_at TIMESTAMP NOT NULL DEFAULT NOW()
+                );",
+            &[],
+        )
+        .await
+        .unwrap();
 }
@@ -43,6 +43,10 @@ async fn main() {
 
     client.query("DROP TABLE orders;", &[]).await.unwrap();
 
+    client.query("DROP TABLE api_key_usage;", &[]).await.unwrap();
+
+    client.query("DROP TABLE api_keys;", &[]).await.unwrap();
+
     client
         .query(r#"DROP EXTENSION "uuid-ossp";"#, &[])
         .await
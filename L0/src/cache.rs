@@ -0,0 +1,457 @@
+//! кэширование заказов: объединение одновременных промахов кэша (single-flight) и stale-while-revalidate
+use crate::db::postgres_db::PostgresDB;
+use crate::db::redis_db::RedisDB;
+use crate::model::{Order, ServerError};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::OnceCell;
+use tokio::time::error::Elapsed;
+use tokio::time::timeout;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+// результат загрузки заказа, общий для всех ожидающих его запросов
+type LoadResult = Result<Order, ServerError>;
+
+// запись заказа в кэше вместе с моментом, до которого она считается свежей
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CachedOrder {
+    // unix-время в миллисекундах
+    pub fresh_until_ms: u128,
+    pub order: Order,
+}
+
+impl CachedOrder {
+    // свежая ли запись на текущий момент
+    fn is_fresh(&self) -> bool {
+        self.fresh_until_ms > now_ms()
+    }
+}
+
+// текущее unix-время в миллисекундах (общее для всех реплик через redis)
+fn now_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+// источник заказов - база данных
+pub trait OrderSource: Send + Sync + 'static {
+    fn load_order(
+        &self,
+        order_uid: &Uuid,
+    ) -> impl Future<Output = Result<Option<Order>, Box<dyn Error + Send + Sync>>> + Send;
+}
+
+// кэш заказов
+pub trait OrderCache: Send + Sync + 'static {
+    fn get_cached_order(
+        &self,
+        key: &str,
+    ) -> impl Future<Output = Result<Option<CachedOrder>, Box<dyn Error + Send + Sync>>> + Send;
+
+    fn set_cached_order(
+        &self,
+        key: &str,
+        entry: &CachedOrder,
+        ttl: Duration,
+    ) -> impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send;
+}
+
+impl OrderSource for PostgresDB {
+    async fn load_order(
+        &self,
+        order_uid: &Uuid,
+    ) -> Result<Option<Order>, Box<dyn Error + Send + Sync>> {
+        self.get_one_order_by_uuid(order_uid).await
+    }
+}
+
+impl OrderCache for RedisDB {
+    async fn get_cached_order(
+        &self,
+        key: &str,
+    ) -> Result<Option<CachedOrder>, Box<dyn Error + Send + Sync>> {
+        self.get_order(key).await
+    }
+
+    async fn set_cached_order(
+        &self,
+        key: &str,
+        entry: &CachedOrder,
+        ttl: Duration,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let value = serde_json::to_string(entry)?;
+        self.set_with_ttl(key, &value, ttl).await
+    }
+}
+
+// общее состояние загрузчика, разделяемое с фоновыми задачами обновления
+struct OrderLoaderInner<S, C> {
+    source: S,
+    cache: C,
+    // загрузки из базы, выполняющиеся прямо сейчас, по ключу заказа
+    in_flight: Mutex<HashMap<Uuid, Arc<OnceCell<LoadResult>>>>,
+    // заказы, для которых уже запущено фоновое обновление
+    revalidating: Mutex<HashSet<Uuid>>,
+    // сколько запись считается свежей
+    fresh_ttl: Duration,
+    // сколько устаревшая запись ещё может отдаваться, пока идёт обновление
+    stale_ttl: Duration,
+}
+
+// загрузчик заказов по uuid: кэш -> база, с объединением запросов и фоновым обновлением
+pub struct OrderLoader<S, C> {
+    inner: Arc<OrderLoaderInner<S, C>>,
+}
+
+impl<S: OrderSource, C: OrderCache> OrderLoader<S, C> {
+    pub fn new(source: S, cache: C, fresh_ttl: Duration, stale_ttl: Duration) -> Self {
+        OrderLoader {
+            inner: Arc::new(OrderLoaderInner {
+                source,
+                cache,
+                in_flight: Mutex::new(HashMap::new()),
+                revalidating: Mutex::new(HashSet::new()),
+                fresh_ttl,
+                stale_ttl,
+            }),
+        }
+    }
+
+    // получение заказа: свежий из кэша, устаревший из кэша с фоновым обновлением, иначе из базы
+    pub async fn get(&self, order_uid: &Uuid) -> Result<Order, ServerError> {
+        // запрос к кэшу с тайм-аутом
+        let cache_result = timeout(Duration::from_secs(1), async {
+            self.inner
+                .cache
+                .get_cached_order(&order_uid.to_string())
+                .await
+        })
+        .await;
+
+        // если данные есть в кэшэ - их возрат, обработка ошибок
+        match cache_result {
+            Ok(Ok(Some(entry))) => {
+                if !entry.is_fresh() {
+                    OrderLoaderInner::revalidate_in_background(&self.inner, *order_uid);
+                }
+                return Ok(entry.order);
+            }
+            Ok(Ok(None)) => {}
+            Ok(Err(err)) => return Err(ServerError::RedisError(err.into())),
+            Err(Elapsed { .. }) => {
+                warn!("Тайм-аут запроса заказа {:?} из кэша Redis", order_uid)
+            }
+        };
+
+        self.inner.load_coalesced(*order_uid).await
+    }
+}
+
+impl<S: OrderSource, C: OrderCache> OrderLoaderInner<S, C> {
+    // загрузка из базы, одновременные запросы одного заказа ждут одну и ту же загрузку
+    async fn load_coalesced(&self, order_uid: Uuid) -> LoadResult {
+        let cell = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(order_uid)
+            .or_default()
+            .clone();
+
+        // если загружающий запрос будет отменён, загрузку продолжит следующий ожидающий
+        let result = cell
+            .get_or_init(|| self.load_and_cache(order_uid))
+            .await
+            .clone();
+
+        // удаление завершённой загрузки, если её ещё не заменила новая
+        let mut in_flight = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if in_flight
+            .get(&order_uid)
+            .is_some_and(|current| Arc::ptr_eq(current, &cell))
+        {
+            in_flight.remove(&order_uid);
+        }
+
+        result
+    }
+
+    // загрузка заказа из базы и запись в кэш
+    async fn load_and_cache(&self, order_uid: Uuid) -> LoadResult {
+        // запрос к базе данных с тайм-аутом
+        let order_result = timeout(Duration::from_secs(1), async {
+            self.source.load_order(&order_uid).await
+        })
+        .await;
+
+        // если база вернула данные - запись в кэш, в противном случае - обработка ошибок
+        let order = match order_result {
+            Ok(Ok(Some(order))) => order,
+            Ok(Ok(None)) => {
+                return Err(ServerError::NotFound(format!(
+                    "Получение заказа {:?} из базы",
+                    order_uid
+                )))
+            }
+            Ok(Err(err)) => return Err(ServerError::PostgresError(err.into())),
+            Err(Elapsed { .. }) => {
+                return Err(ServerError::TimeoutError(format!(
+                    "Получение заказа {:?} из базы",
+                    order_uid
+                )))
+            }
+        };
+
+        // запись в кэш с запасом времени на отдачу устаревших данных
+        let entry = CachedOrder {
+            fresh_until_ms: now_ms() + self.fresh_ttl.as_millis(),
+            order,
+        };
+        let cache_result = timeout(Duration::from_secs(1), async {
+            self.cache
+                .set_cached_order(
+                    &order_uid.to_string(),
+                    &entry,
+                    self.fresh_ttl + self.stale_ttl,
+                )
+                .await
+        })
+        .await;
+
+        match cache_result {
+            Ok(Ok(())) => {
+                info!("Запрос заказа {} закэширован в базе данных redis", order_uid);
+                Ok(entry.order)
+            }
+            Ok(Err(err)) => Err(ServerError::RedisError(err.into())),
+            Err(Elapsed { .. }) => Err(ServerError::TimeoutError(format!(
+                "добавление в кэш заказа {:?}",
+                order_uid
+            ))),
+        }
+    }
+
+    // запуск одного фонового обновления устаревшей записи
+    fn revalidate_in_background(inner: &Arc<Self>, order_uid: Uuid) {
+        let is_new = inner
+            .revalidating
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(order_uid);
+        if !is_new {
+            return;
+        }
+
+        let inner = inner.clone();
+        tokio::spawn(async move {
+            if let Err(err) = inner.load_coalesced(order_uid).await {
+                warn!("Не удалось обновить заказ {} в кэше: {:?}", order_uid, err);
+            }
+
+            inner
+                .revalidating
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .remove(&order_uid);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{now_ms, CachedOrder, OrderCache, OrderLoader, OrderSource};
+    use crate::model::{Order, ServerError};
+    use futures::future::join_all;
+    use std::collections::HashMap;
+    use std::error::Error;
+    use std::fs::read_to_string;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    // фейковая база, считающая количество запросов
+    struct CountingSource {
+        orders: Mutex<HashMap<Uuid, Order>>,
+        calls: Arc<AtomicUsize>,
+        fail: bool,
+    }
+
+    impl OrderSource for CountingSource {
+        async fn load_order(
+            &self,
+            order_uid: &Uuid,
+        ) -> Result<Option<Order>, Box<dyn Error + Send + Sync>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            // задержка чтобы запросы гарантированно пересеклись
+            tokio::time::sleep(Duration::from_millis(50)).await;
+
+            if self.fail {
+                return Err("база недоступна".into());
+            }
+            Ok(self.orders.lock().unwrap().get(order_uid).cloned())
+        }
+    }
+
+    // кэш в памяти
+    #[derive(Clone, Default)]
+    struct MemoryCache {
+        entries: Arc<Mutex<HashMap<String, CachedOrder>>>,
+    }
+
+    impl OrderCache for MemoryCache {
+        async fn get_cached_order(
+            &self,
+            key: &str,
+        ) -> Result<Option<CachedOrder>, Box<dyn Error + Send + Sync>> {
+            Ok(self.entries.lock().unwrap().get(key).cloned())
+        }
+
+        async fn set_cached_order(
+            &self,
+            key: &str,
+            entry: &CachedOrder,
+            _ttl: Duration,
+        ) -> Result<(), Box<dyn Error + Send + Sync>> {
+            self.entries
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), entry.clone());
+            Ok(())
+        }
+    }
+
+    // тестовый заказ из файлика json
+    fn test_order() -> Order {
+        let contents = read_to_string("additional_files/model.json").unwrap();
+        let mut orders: Vec<Order> = serde_json::from_str(&contents).unwrap();
+        orders.remove(0)
+    }
+
+    fn loader(
+        order: Option<&Order>,
+        fail: bool,
+    ) -> (
+        OrderLoader<CountingSource, MemoryCache>,
+        Arc<AtomicUsize>,
+        MemoryCache,
+    ) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let orders = order
+            .map(|order| HashMap::from([(order.order_uid, order.clone())]))
+            .unwrap_or_default();
+        let source = CountingSource {
+            orders: Mutex::new(orders),
+            calls: calls.clone(),
+            fail,
+        };
+        let cache = MemoryCache::default();
+        let loader = OrderLoader::new(
+            source,
+            cache.clone(),
+            Duration::from_secs(100),
+            Duration::from_secs(30),
+        );
+
+        (loader, calls, cache)
+    }
+
+    #[tokio::test]
+    // одновременные промахи кэша делают один запрос к базе
+    async fn test_concurrent_misses_are_coalesced() {
+        let order = test_order();
+        let (loader, calls, cache) = loader(Some(&order), false);
+
+        let results = join_all((0..20).map(|_| loader.get(&order.order_uid))).await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        for result in results {
+            assert_eq!(result.ok().as_ref(), Some(&order));
+        }
+        assert!(cache
+            .entries
+            .lock()
+            .unwrap()
+            .contains_key(&order.order_uid.to_string()));
+
+        // следующий запрос берётся из кэша
+        assert_eq!(loader.get(&order.order_uid).await.ok(), Some(order));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    // ошибка базы получают все ожидающие, следующий запрос пробует снова
+    async fn test_errors_are_shared_and_not_cached() {
+        let order = test_order();
+        let (loader, calls, _) = loader(Some(&order), true);
+
+        let results = join_all((0..5).map(|_| loader.get(&order.order_uid))).await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(results
+            .iter()
+            .all(|result| matches!(result, Err(ServerError::PostgresError(_)))));
+
+        assert!(loader.get(&order.order_uid).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    // отсутствующий заказ - NotFound без записи в кэш
+    async fn test_missing_order() {
+        let (loader, calls, cache) = loader(None, false);
+
+        let missing_uid = Uuid::nil();
+        let results = join_all((0..5).map(|_| loader.get(&missing_uid))).await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(results
+            .iter()
+            .all(|result| matches!(result, Err(ServerError::NotFound(_)))));
+        assert!(cache.entries.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    // устаревшая запись отдаётся сразу, а обновляется одним фоновым запросом
+    async fn test_stale_while_revalidate() {
+        let fresh_order = test_order();
+        let (loader, calls, cache) = loader(Some(&fresh_order), false);
+
+        // устаревшая версия заказа в кэше
+        let mut stale_order = fresh_order.clone();
+        stale_order.track_number = "STALE".to_string();
+        cache.entries.lock().unwrap().insert(
+            fresh_order.order_uid.to_string(),
+            CachedOrder {
+                fresh_until_ms: now_ms() - 1,
+                order: stale_order.clone(),
+            },
+        );
+
+        let results = join_all((0..10).map(|_| loader.get(&fresh_order.order_uid))).await;
+        for result in results {
+            assert_eq!(result.ok().as_ref(), Some(&stale_order));
+        }
+
+        // ожидание фонового обновления
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let entry = cache.entries.lock().unwrap()[&fresh_order.order_uid.to_string()].clone();
+        assert!(entry.fresh_until_ms > now_ms());
+        assert_eq!(entry.order, fresh_order);
+
+        assert_eq!(loader.get(&fresh_order.order_uid).await.ok(), Some(fresh_order));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
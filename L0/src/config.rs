@@ -1,4 +1,5 @@
 //! конфиг баз данных
+use crate::currency::Currency;
 use dotenv::dotenv;
 use std::env;
 
@@ -52,3 +53,184 @@ impl Default for DbConfig {
         Self::new()
     }
 }
+
+// политика ограничения частоты запросов (token bucket) для одного маршрута
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RatePolicy {
+    // максимальное количество токенов в корзине (допустимый всплеск запросов)
+    pub capacity: u32,
+    // скорость пополнения корзины в токенах в секунду
+    pub refill_per_sec: f64,
+}
+
+impl RatePolicy {
+    // парсинг политики из строки формата "capacity/refill_per_sec", например "100/10"
+    pub fn parse(raw: &str) -> Option<Self> {
+        let (capacity, refill_per_sec) = raw.trim().split_once('/')?;
+        let capacity = capacity.trim().parse::<u32>().ok()?;
+        let refill_per_sec = refill_per_sec.trim().parse::<f64>().ok()?;
+
+        if capacity == 0 || !refill_per_sec.is_finite() || refill_per_sec <= 0.0 {
+            return None;
+        }
+
+        Some(RatePolicy {
+            capacity,
+            refill_per_sec,
+        })
+    }
+
+    // загрузка политики из переменной окружения или значение по умолчанию
+    fn from_env(var_name: &str, default: RatePolicy) -> Self {
+        match env::var(var_name) {
+            Ok(raw) => RatePolicy::parse(&raw).unwrap_or_else(|| {
+                panic!("{} должен быть в формате capacity/refill_per_sec", var_name)
+            }),
+            Err(_) => default,
+        }
+    }
+}
+
+// структура конфига ограничения частоты запросов по маршрутам
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    // все маршруты с одного IP до проверки ключа
+    pub pre_auth_ip: RatePolicy,
+    // GET /orders
+    pub get_all_orders: RatePolicy,
+    // GET /orders/:order_uuid
+    pub get_order_by_uuid: RatePolicy,
+    // POST /orders
+    pub insert_order: RatePolicy,
+    // GET /analytics/payments
+    pub analytics: RatePolicy,
+}
+
+impl RateLimitConfig {
+    // Инициализация конфига, переменные окружения необязательны
+    pub fn new() -> Self {
+        // занрузка переменных окружения
+        dotenv().ok();
+
+        // одна корзина IP на все маршруты до проверки ключа: свободнее лимитов маршрутов,
+        // чтобы клиенты за общим NAT не упирались в неё раньше лимитов своих ключей
+        let pre_auth_ip = RatePolicy::from_env(
+            "RATE_LIMIT_PRE_AUTH_IP",
+            RatePolicy {
+                capacity: 300,
+                refill_per_sec: 60.0,
+            },
+        );
+        // получение всех заказов - самый тяжёлый запрос к Postgres
+        let get_all_orders = RatePolicy::from_env(
+            "RATE_LIMIT_GET_ALL_ORDERS",
+            RatePolicy {
+                capacity: 20,
+                refill_per_sec: 2.0,
+            },
+        );
+        // получение одного заказа
+        let get_order_by_uuid = RatePolicy::from_env(
+            "RATE_LIMIT_GET_ORDER",
+            RatePolicy {
+                capacity: 100,
+                refill_per_sec: 20.0,
+            },
+        );
+        // добавление заказа
+        let insert_order = RatePolicy::from_env(
+            "RATE_LIMIT_INSERT_ORDER",
+            RatePolicy {
+                capacity: 50,
+                refill_per_sec: 10.0,
+            },
+        );
+
+        // аналитика - пересчёт всех заказов
+        let analytics = RatePolicy::from_env(
+            "RATE_LIMIT_ANALYTICS",
+            RatePolicy {
+                capacity: 20,
+                refill_per_sec: 2.0,
+            },
+        );
+
+        RateLimitConfig {
+            pre_auth_ip,
+            get_all_orders,
+            get_order_by_uuid,
+            insert_order,
+            analytics,
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// структура конфига валют
+#[derive(Debug, Clone)]
+pub struct CurrencyConfig {
+    // валюта, в которой считается аналитика
+    pub base_currency: Currency,
+    // путь к CSV файлу с курсами относительно базовой валюты
+    pub exchange_rates_path: Option<String>,
+}
+
+impl CurrencyConfig {
+    // Инициализация конфига, переменные окружения необязательны
+    pub fn new() -> Self {
+        // занрузка переменных окружения
+        dotenv().ok();
+
+        // базовая валюта, по умолчанию USD
+        let base_currency = env::var("BASE_CURRENCY")
+            .unwrap_or_else(|_| "USD".to_string())
+            .parse::<Currency>()
+            .expect("BASE_CURRENCY должен быть кодом валюты ISO-4217");
+        // файл с курсами валют
+        let exchange_rates_path = env::var("EXCHANGE_RATES_PATH").ok();
+
+        CurrencyConfig {
+            base_currency,
+            exchange_rates_path,
+        }
+    }
+}
+
+impl Default for CurrencyConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// структура конфига gRPC сервера
+#[derive(Debug, Clone)]
+pub struct GrpcConfig {
+    // порт gRPC сервера, отдельный от HTTP
+    pub port: u16,
+}
+
+impl GrpcConfig {
+    // Инициализация конфига, переменные окружения необязательны
+    pub fn new() -> Self {
+        // занрузка переменных окружения
+        dotenv().ok();
+
+        // порт gRPC сервера, по умолчанию 50051
+        let port = env::var("GRPC_PORT")
+            .map(|raw| raw.parse::<u16>().expect("GRPC_PORT должен быть номером порта"))
+            .unwrap_or(50051);
+
+        GrpcConfig { port }
+    }
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,27 +1,50 @@
 //! функции поведения эндпоинтов
-use crate::model::{Order, OrdersModel, ServerError};
-use axum::extract::{Path, State};
+use crate::currency::Currency;
+use crate::model::{Order, OrdersModel, PaymentsSummary, ServerError};
+use axum::extract::{Path, Query, State};
 use axum::Json;
+use serde::Deserialize;
 use std::sync::Arc;
 use uuid::Uuid;
 
-// GET /orders - получение всех заказов из базы данных
+// параметры запроса для получения заказов
+#[derive(Debug, Deserialize)]
+pub struct OrdersQuery {
+    // валюта, в которую пересчитываются все денежные поля
+    pub currency: Option<Currency>,
+}
+
+// GET /orders?currency= - получение всех заказов из базы данных
 pub async fn get_all_orders(
     State(orders_model): State<Arc<OrdersModel>>,
+    Query(query): Query<OrdersQuery>,
 ) -> Result<Json<Vec<Order>>, ServerError> {
     // получение всех заказов из базы данных
-    let query_response = orders_model.get_all_orders().await?;
+    let mut query_response = orders_model.get_all_orders().await?;
+
+    // пересчёт в нужную валюту
+    if let Some(currency) = query.currency {
+        query_response = orders_model.convert_orders(query_response, currency)?;
+    }
 
     Ok(Json(query_response))
 }
 
-// GET /orders/:order_uuid - получение всех заказов из базы данных по order_uuid
+// GET /orders/:order_uuid?currency= - получение всех заказов из базы данных по order_uuid
 pub async fn get_order_by_uuid(
     State(orders_model): State<Arc<OrdersModel>>,
     Path(order_uuid): Path<Uuid>,
+    Query(query): Query<OrdersQuery>,
 ) -> Result<Json<Order>, ServerError> {
     // получение одного заказа из базы данных по uuid
-    let query_response = orders_model.get_one_order_by_uuid(&order_uuid).await?;
+    let mut query_response = orders_model.get_one_order_by_uuid(&order_uuid).await?;
+
+    // пересчёт в нужную валюту
+    if let Some(currency) = query.currency {
+        query_response = orders_model
+            .convert_orders(vec![query_response], currency)?
+            .remove(0);
+    }
 
     Ok(Json(query_response))
 }
@@ -36,3 +59,13 @@ pub async fn insert_order(
 
     Ok(Json(order))
 }
+
+// GET /analytics/payments - сводка по оплатам всех заказов в базовой валюте
+pub async fn get_payments_summary(
+    State(orders_model): State<Arc<OrdersModel>>,
+) -> Result<Json<PaymentsSummary>, ServerError> {
+    // пересчёт и суммирование оплат
+    let summary = orders_model.get_payments_summary().await?;
+
+    Ok(Json(summary))
+}
@@ -0,0 +1,329 @@
+//! валюты ISO-4217, суммы в минимальных единицах (копейки, центы) и пересчёт по таблице курсов
+use crate::model::Order;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::read_to_string;
+use std::str::FromStr;
+
+// масштаб курсов с фиксированной точкой (9 знаков после запятой)
+const RATE_SCALE: i128 = 1_000_000_000;
+
+// действующие коды ISO-4217 и количество знаков минимальной единицы
+#[rustfmt::skip]
+const ISO_4217: &[(&str, u32)] = &[
+    ("AED", 2), ("AFN", 2), ("ALL", 2), ("AMD", 2), ("ANG", 2), ("AOA", 2), ("ARS", 2),
+    ("AUD", 2), ("AWG", 2), ("AZN", 2), ("BAM", 2), ("BBD", 2), ("BDT", 2), ("BGN", 2),
+    ("BHD", 3), ("BIF", 0), ("BMD", 2), ("BND", 2), ("BOB", 2), ("BOV", 2), ("BRL", 2),
+    ("BSD", 2), ("BTN", 2), ("BWP", 2), ("BYN", 2), ("BZD", 2), ("CAD", 2), ("CDF", 2),
+    ("CHE", 2), ("CHF", 2), ("CHW", 2), ("CLF", 4), ("CLP", 0), ("CNY", 2), ("COP", 2),
+    ("COU", 2), ("CRC", 2), ("CUP", 2), ("CVE", 2), ("CZK", 2), ("DJF", 0), ("DKK", 2),
+    ("DOP", 2), ("DZD", 2), ("EGP", 2), ("ERN", 2), ("ETB", 2), ("EUR", 2), ("FJD", 2),
+    ("FKP", 2), ("GBP", 2), ("GEL", 2), ("GHS", 2), ("GIP", 2), ("GMD", 2), ("GNF", 0),
+    ("GTQ", 2), ("GYD", 2), ("HKD", 2), ("HNL", 2), ("HTG", 2), ("HUF", 2), ("IDR", 2),
+    ("ILS", 2), ("INR", 2), ("IQD", 3), ("IRR", 2), ("ISK", 0), ("JMD", 2), ("JOD", 3),
+    ("JPY", 0), ("KES", 2), ("KGS", 2), ("KHR", 2), ("KMF", 0), ("KPW", 2), ("KRW", 0),
+    ("KWD", 3), ("KYD", 2), ("KZT", 2), ("LAK", 2), ("LBP", 2), ("LKR", 2), ("LRD", 2),
+    ("LSL", 2), ("LYD", 3), ("MAD", 2), ("MDL", 2), ("MGA", 2), ("MKD", 2), ("MMK", 2),
+    ("MNT", 2), ("MOP", 2), ("MRU", 2), ("MUR", 2), ("MVR", 2), ("MWK", 2), ("MXN", 2),
+    ("MXV", 2), ("MYR", 2), ("MZN", 2), ("NAD", 2), ("NGN", 2), ("NIO", 2), ("NOK", 2),
+    ("NPR", 2), ("NZD", 2), ("OMR", 3), ("PAB", 2), ("PEN", 2), ("PGK", 2), ("PHP", 2),
+    ("PKR", 2), ("PLN", 2), ("PYG", 0), ("QAR", 2), ("RON", 2), ("RSD", 2), ("RUB", 2),
+    ("RWF", 0), ("SAR", 2), ("SBD", 2), ("SCR", 2), ("SDG", 2), ("SEK", 2), ("SGD", 2),
+    ("SHP", 2), ("SLE", 2), ("SOS", 2), ("SRD", 2), ("SSP", 2), ("STN", 2), ("SVC", 2),
+    ("SYP", 2), ("SZL", 2), ("THB", 2), ("TJS", 2), ("TMT", 2), ("TND", 3), ("TOP", 2),
+    ("TRY", 2), ("TTD", 2), ("TWD", 2), ("TZS", 2), ("UAH", 2), ("UGX", 0), ("USD", 2),
+    ("USN", 2), ("UYI", 0), ("UYU", 2), ("UYW", 4), ("UZS", 2), ("VED", 2), ("VES", 2),
+    ("VND", 0), ("VUV", 0), ("WST", 2), ("XAF", 0), ("XCD", 2), ("XOF", 0), ("XPF", 0),
+    ("YER", 2), ("ZAR", 2), ("ZMW", 2), ("ZWG", 2),
+];
+
+// возможные ошибки работы с валютами
+#[derive(Debug, PartialEq)]
+pub enum CurrencyError {
+    UnknownCurrency(String),
+    MissingRate(Currency),
+    InvalidRate(String),
+    Overflow,
+}
+
+impl fmt::Display for CurrencyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CurrencyError::UnknownCurrency(code) => {
+                write!(f, "неизвестный код валюты ISO-4217: {:?}", code)
+            }
+            CurrencyError::MissingRate(currency) => {
+                write!(f, "нет курса для валюты {}", currency)
+            }
+            CurrencyError::InvalidRate(line) => write!(f, "неверная строка курса: {:?}", line),
+            CurrencyError::Overflow => write!(f, "переполнение при пересчёте суммы"),
+        }
+    }
+}
+
+impl Error for CurrencyError {}
+
+// проверенный код валюты ISO-4217
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Currency {
+    code: [u8; 3],
+    minor_unit: u32,
+}
+
+impl Currency {
+    // трёхбуквенный код валюты
+    pub fn code(&self) -> &str {
+        // код всегда состоит из ASCII-букв из таблицы ISO_4217
+        std::str::from_utf8(&self.code).unwrap_or_default()
+    }
+
+    // количество знаков минимальной единицы (2 для USD, 0 для JPY, 3 для KWD)
+    pub fn minor_unit(&self) -> u32 {
+        self.minor_unit
+    }
+}
+
+impl FromStr for Currency {
+    type Err = CurrencyError;
+
+    // парсинг кода без учёта регистра ("usd" -> USD)
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let code = raw.trim().to_ascii_uppercase();
+
+        ISO_4217
+            .iter()
+            .find(|(iso_code, _)| *iso_code == code)
+            .map(|(iso_code, minor_unit)| {
+                let bytes = iso_code.as_bytes();
+                Currency {
+                    code: [bytes[0], bytes[1], bytes[2]],
+                    minor_unit: *minor_unit,
+                }
+            })
+            .ok_or_else(|| CurrencyError::UnknownCurrency(raw.to_string()))
+    }
+}
+
+impl TryFrom<String> for Currency {
+    type Error = CurrencyError;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        raw.parse()
+    }
+}
+
+impl From<Currency> for String {
+    fn from(currency: Currency) -> Self {
+        currency.code().to_string()
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+// парсинг десятичного курса в число с фиксированной точкой
+fn parse_rate(raw: &str) -> Option<i128> {
+    let raw = raw.trim();
+    let (integer, fraction) = raw.split_once('.').unwrap_or((raw, ""));
+
+    if integer.is_empty() && fraction.is_empty()
+        || fraction.len() > 9
+        || !integer.chars().all(|c| c.is_ascii_digit())
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let integer: i128 = match integer {
+        "" => 0,
+        integer => integer.parse().ok()?,
+    };
+    let fraction: i128 = match fraction {
+        "" => 0,
+        fraction => fraction.parse::<i128>().ok()? * 10_i128.pow(9 - fraction.len() as u32),
+    };
+
+    let rate = integer.checked_mul(RATE_SCALE)?.checked_add(fraction)?;
+    (rate > 0).then_some(rate)
+}
+
+// деление с округлением половины от нуля
+fn div_round(numerator: i128, denominator: i128) -> i128 {
+    let quotient = numerator / denominator;
+    let remainder = numerator % denominator;
+
+    if remainder.abs() * 2 >= denominator.abs() {
+        quotient + numerator.signum() * denominator.signum()
+    } else {
+        quotient
+    }
+}
+
+// таблица курсов относительно базовой валюты
+#[derive(Debug, Clone)]
+pub struct ExchangeRates {
+    base: Currency,
+    // стоимость одной единицы валюты в базовой валюте, с фиксированной точкой
+    rates: HashMap<Currency, i128>,
+}
+
+impl ExchangeRates {
+    // таблица только с базовой валютой
+    pub fn new(base: Currency) -> Self {
+        ExchangeRates {
+            base,
+            rates: HashMap::from([(base, RATE_SCALE)]),
+        }
+    }
+
+    // парсинг CSV вида "currency,rate", где rate - стоимость одной единицы валюты в базовой валюте
+    // строка-заголовок, пустые строки и комментарии "#" пропускаются
+    pub fn from_csv(base: Currency, csv: &str) -> Result<Self, CurrencyError> {
+        let mut exchange_rates = ExchangeRates::new(base);
+
+        for line in csv.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.eq_ignore_ascii_case("currency,rate") {
+                continue;
+            }
+
+            let (code, rate) = line
+                .split_once(',')
+                .ok_or_else(|| CurrencyError::InvalidRate(line.to_string()))?;
+            let currency: Currency = code.parse()?;
+            let rate = parse_rate(rate).ok_or_else(|| CurrencyError::InvalidRate(line.to_string()))?;
+
+            // курс базовой валюты всегда 1
+            if currency == base && rate != RATE_SCALE {
+                return Err(CurrencyError::InvalidRate(line.to_string()));
+            }
+
+            exchange_rates.rates.insert(currency, rate);
+        }
+
+        Ok(exchange_rates)
+    }
+
+    // загрузка таблицы курсов из CSV файла
+    pub fn from_csv_file(base: Currency, path: &str) -> Result<Self, Box<dyn Error>> {
+        let csv = read_to_string(path)?;
+        Ok(ExchangeRates::from_csv(base, &csv)?)
+    }
+
+    // базовая валюта
+    pub fn base(&self) -> Currency {
+        self.base
+    }
+
+    // есть ли курс валюты
+    pub fn has_rate(&self, currency: Currency) -> bool {
+        self.rates.contains_key(&currency)
+    }
+
+    // пересчёт суммы в минимальных единицах из одной валюты в другую
+    pub fn convert(&self, amount: i64, from: Currency, to: Currency) -> Result<i64, CurrencyError> {
+        if from == to {
+            return Ok(amount);
+        }
+
+        let rate_from = *self.rates.get(&from).ok_or(CurrencyError::MissingRate(from))?;
+        let rate_to = *self.rates.get(&to).ok_or(CurrencyError::MissingRate(to))?;
+
+        // minor_to = minor_from * rate_from * 10^unit_to / (rate_to * 10^unit_from)
+        let numerator = (amount as i128)
+            .checked_mul(rate_from)
+            .and_then(|value| value.checked_mul(10_i128.pow(to.minor_unit())))
+            .ok_or(CurrencyError::Overflow)?;
+        let denominator = rate_to
+            .checked_mul(10_i128.pow(from.minor_unit()))
+            .ok_or(CurrencyError::Overflow)?;
+
+        i64::try_from(div_round(numerator, denominator)).map_err(|_| CurrencyError::Overflow)
+    }
+
+    // пересчёт всех денежных полей заказа в нужную валюту
+    pub fn convert_order(&self, mut order: Order, to: Currency) -> Result<Order, CurrencyError> {
+        let from = order.payment.currency;
+        if from == to {
+            return Ok(order);
+        }
+
+        let payment = &mut order.payment;
+        payment.amount = self.convert(payment.amount, from, to)?;
+        payment.delivery_cost = self.convert(payment.delivery_cost, from, to)?;
+        payment.goods_total = self.convert(payment.goods_total, from, to)?;
+        payment.custom_fee = self.convert(payment.custom_fee, from, to)?;
+        payment.currency = to;
+
+        for item in order.items.iter_mut() {
+            item.price = self.convert(item.price, from, to)?;
+            item.total_price = self.convert(item.total_price, from, to)?;
+        }
+
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Currency, CurrencyError, ExchangeRates};
+
+    fn currency(code: &str) -> Currency {
+        code.parse().unwrap()
+    }
+
+    #[test]
+    // проверка кодов ISO-4217 и минимальных единиц
+    fn test_currency_parse() {
+        assert_eq!(currency("usd").code(), "USD");
+        assert_eq!(currency("USD").minor_unit(), 2);
+        assert_eq!(currency("JPY").minor_unit(), 0);
+        assert_eq!(currency("KWD").minor_unit(), 3);
+        assert_eq!(
+            "ABC".parse::<Currency>(),
+            Err(CurrencyError::UnknownCurrency("ABC".to_string()))
+        );
+        assert!(serde_json::from_str::<Currency>("\"XYZ\"").is_err());
+        assert_eq!(serde_json::to_string(&currency("eur")).unwrap(), "\"EUR\"");
+    }
+
+    #[test]
+    // пересчёт с учётом разного количества знаков минимальной единицы
+    fn test_convert() {
+        let csv = "currency,rate\n# курсы к доллару\nEUR,1.08\nJPY,0.0067\nKWD,3.25\n";
+        let rates = ExchangeRates::from_csv(currency("USD"), csv).unwrap();
+
+        // 10.00 EUR = 10.80 USD
+        assert_eq!(rates.convert(1000, currency("EUR"), currency("USD")), Ok(1080));
+        // 10.80 USD = 10.00 EUR
+        assert_eq!(rates.convert(1080, currency("USD"), currency("EUR")), Ok(1000));
+        // 1000 JPY = 6.70 USD
+        assert_eq!(rates.convert(1000, currency("JPY"), currency("USD")), Ok(670));
+        // 1.000 KWD = 3.25 USD
+        assert_eq!(rates.convert(1000, currency("KWD"), currency("USD")), Ok(325));
+        // 1.00 EUR = 161.19 JPY -> 161 JPY
+        assert_eq!(rates.convert(100, currency("EUR"), currency("JPY")), Ok(161));
+        assert_eq!(
+            rates.convert(100, currency("EUR"), currency("GBP")),
+            Err(CurrencyError::MissingRate(currency("GBP")))
+        );
+        assert!(rates.has_rate(currency("EUR")) && !rates.has_rate(currency("GBP")));
+    }
+
+    #[test]
+    // неверные строки в CSV
+    fn test_invalid_csv() {
+        let usd = currency("USD");
+        assert!(ExchangeRates::from_csv(usd, "EUR;1.08").is_err());
+        assert!(ExchangeRates::from_csv(usd, "EUR,-1").is_err());
+        assert!(ExchangeRates::from_csv(usd, "EUR,0").is_err());
+        assert!(ExchangeRates::from_csv(usd, "USD,2").is_err());
+        assert!(ExchangeRates::from_csv(usd, "ZZZ,1").is_err());
+    }
+}
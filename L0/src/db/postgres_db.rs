@@ -1,4 +1,5 @@
 //! инициализация и методы работы с базой данных Postgres
+use crate::auth::{ApiKey, ApiKeyUsage, Scope};
 use crate::config::DbConfig;
 use crate::model::{Delivery, Item, Order, Payment};
 use deadpool_postgres::{
@@ -7,10 +8,11 @@ use deadpool_postgres::{
 };
 use serde_json::Value;
 use std::error::Error;
-use tokio_postgres::NoTls;
+use tokio_postgres::{NoTls, Row};
 use uuid::Uuid;
 
 // обёртка вокруг пула подключений
+#[derive(Clone)]
 pub struct PostgresDB {
     pool: Pool,
 }
@@ -29,6 +31,26 @@ fn create_deadpool_config(db_config: &DbConfig) -> DeadpoolConfig {
     cfg
 }
 
+// колонки ключа в запросах
+const API_KEY_COLUMNS: &str = "key_id, name, scopes, created_at, revoked_at, last_used_at";
+
+// преобразование строки из таблицы api_keys в структуру ключа
+fn api_key_from_row(row: &Row) -> Result<ApiKey, Box<dyn Error + Send + Sync>> {
+    let scopes: Vec<String> = row.try_get("scopes")?;
+
+    Ok(ApiKey {
+        key_id: row.try_get("key_id")?,
+        name: row.try_get("name")?,
+        scopes: scopes
+            .iter()
+            .map(|scope| scope.parse::<Scope>())
+            .collect::<Result<Vec<Scope>, String>>()?,
+        created_at: row.try_get("created_at")?,
+        revoked_at: row.try_get("revoked_at")?,
+        last_used_at: row.try_get("last_used_at")?,
+    })
+}
+
 // методы инициализации и работы с базой данных Postgres
 impl PostgresDB {
     // создание инстанса базы данных опираясь на конфиг
@@ -171,7 +193,7 @@ impl PostgresDB {
                 &[
                     &payment.transaction,
                     &payment.request_id,
-                    &payment.currency,
+                    &payment.currency.code(),
                     &payment.provider,
                     &payment.amount,
                     &payment.payment_dt,
@@ -329,6 +351,102 @@ impl PostgresDB {
         Ok(Some(orders))
     }
 
+    // страница заказов по возрастанию order_uid после заказа after (с начала, если None)
+    pub async fn get_orders_page(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<Order>, Box<dyn Error + Send + Sync>> {
+        // получение подключения из пула
+        let client = self.pool.get().await?;
+
+        // форма запроса
+        let statement = "
+                    SELECT json_agg(result ORDER BY result.order_uid) as order_json
+                    FROM (
+                        SELECT
+                            orders.order_uid,
+                            orders.track_number,
+                            orders.entry,
+                            orders.payment,
+                            orders.locale,
+                            orders.internal_signature,
+                            orders.customer_id,
+                            orders.delivery_service,
+                            orders.shardkey,
+                            orders.sm_id,
+                            orders.date_created,
+                            orders.oof_shard,
+                            json_build_object(
+                                'transaction', payments.transaction,
+                                'request_id', payments.request_id,
+                                'currency', payments.currency,
+                                'provider', payments.provider,
+                                'amount', payments.amount,
+                                'payment_dt', payments.payment_dt,
+                                'bank', payments.bank,
+                                'delivery_cost', payments.delivery_cost,
+                                'goods_total', payments.goods_total,
+                                'custom_fee', payments.custom_fee
+                            ) AS payment,
+                            json_build_object(
+                                'name', deliveries.name,
+                                'phone', deliveries.phone,
+                                'zip', deliveries.zip,
+                                'city', deliveries.city,
+                                'address', deliveries.address,
+                                'region', deliveries.region,
+                                'email', deliveries.email
+                            ) as delivery,
+                            json_agg(
+                                json_build_object(
+                                    'chrt_id', items.chrt_id,
+                                    'track_number', items.track_number,
+                                    'price', items.price,
+                                    'rid', items.rid,
+                                    'name', items.name,
+                                    'sale', items.sale,
+                                    'size', items.size,
+                                    'total_price', items.total_price,
+                                    'nm_id', items.nm_id,
+                                    'brand', items.brand,
+                                    'status', items.status
+                                )
+                            ) AS items
+                        FROM
+                            orders
+                        INNER JOIN
+                            payments ON orders.order_uid = payments.order_uid
+                        INNER JOIN
+                            deliveries ON orders.order_uid = deliveries.order_uid
+                        INNER JOIN
+                            items ON orders.order_uid = items.order_uid
+                        WHERE $1::uuid IS NULL OR orders.order_uid > $1
+                        GROUP BY
+                            orders.order_uid, payments.payment_uid, deliveries.delivery_uid
+                        ORDER BY orders.order_uid
+                        LIMIT $2
+                    ) result;
+                ";
+
+        // выполнение запроса с нужными данными
+        let row = client.query_one(statement, &[&after, &limit]).await?;
+
+        // парсинг json-а
+        let orders_json_option: Option<Value> = row.get("order_json");
+
+        // если json пуст - заказы закончились
+        let orders_json = match orders_json_option {
+            None => return Ok(Vec::new()),
+            Some(orders_json) => orders_json,
+        };
+
+        // десериализация
+        let orders: Vec<Order> = serde_json::from_value(orders_json)?;
+
+        Ok(orders)
+    }
+
     // функция для получения одно заказа по uuid
     pub async fn get_one_order_by_uuid(
         &self,
@@ -422,4 +540,126 @@ impl PostgresDB {
 
         Ok(Some(order))
     }
+
+    // добавление нового API-ключа, в базе хранится только хэш ключа
+    pub async fn insert_api_key(
+        &self,
+        name: &str,
+        key_hash: &str,
+        scopes: &[Scope],
+    ) -> Result<ApiKey, Box<dyn Error + Send + Sync>> {
+        // получение подключения из пула
+        let client = self.pool.get().await?;
+
+        // форма запроса
+        let statement = format!(
+            "INSERT INTO api_keys (name, key_hash, scopes) VALUES ($1, $2, $3) RETURNING {};",
+            API_KEY_COLUMNS
+        );
+        let scopes: Vec<&str> = scopes.iter().map(|scope| scope.as_str()).collect();
+
+        // выполнение запроса с нужными данными
+        let row = client
+            .query_one(&statement, &[&name, &key_hash, &scopes])
+            .await?;
+
+        api_key_from_row(&row)
+    }
+
+    // получение действующего (не отозванного) ключа по хэшу
+    pub async fn get_api_key_by_hash(
+        &self,
+        key_hash: &str,
+    ) -> Result<Option<ApiKey>, Box<dyn Error + Send + Sync>> {
+        // получение подключения из пула
+        let client = self.pool.get().await?;
+
+        // форма запроса
+        let statement = format!(
+            "SELECT {} FROM api_keys WHERE key_hash = $1 AND revoked_at IS NULL;",
+            API_KEY_COLUMNS
+        );
+
+        // выполнение запроса с нужными данными
+        let row = client.query_opt(&statement, &[&key_hash]).await?;
+
+        row.as_ref().map(api_key_from_row).transpose()
+    }
+
+    // получение всех ключей, включая отозванные
+    pub async fn list_api_keys(&self) -> Result<Vec<ApiKey>, Box<dyn Error + Send + Sync>> {
+        // получение подключения из пула
+        let client = self.pool.get().await?;
+
+        // форма запроса
+        let statement = format!(
+            "SELECT {} FROM api_keys ORDER BY created_at;",
+            API_KEY_COLUMNS
+        );
+
+        // выполнение запроса
+        let rows = client.query(&statement, &[]).await?;
+
+        rows.iter().map(api_key_from_row).collect()
+    }
+
+    // отзыв ключа, возвращает false если ключ не найден или уже отозван
+    pub async fn revoke_api_key(&self, key_id: &Uuid) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        // получение подключения из пула
+        let client = self.pool.get().await?;
+
+        // форма запроса
+        let statement = "
+            UPDATE api_keys SET revoked_at = NOW()
+            WHERE key_id = $1 AND revoked_at IS NULL;
+        ";
+
+        // выполнение запроса с нужными данными
+        let updated = client.execute(statement, &[key_id]).await?;
+
+        Ok(updated > 0)
+    }
+
+    // запись пачки использований ключей для аудита одним запросом
+    pub async fn insert_api_key_usage(
+        &self,
+        usage: &[ApiKeyUsage],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // получение подключения из пула
+        let client = self.pool.get().await?;
+
+        // форма запроса: колонки передаются массивами и разворачиваются в строки
+        let statement = "
+            INSERT INTO api_key_usage (key_id, method, path, status)
+            SELECT * FROM UNNEST($1::uuid[], $2::varchar[], $3::varchar[], $4::integer[]);
+        ";
+        let key_ids: Vec<Uuid> = usage.iter().map(|usage| usage.key_id).collect();
+        let methods: Vec<&str> = usage.iter().map(|usage| usage.method.as_str()).collect();
+        let paths: Vec<&str> = usage.iter().map(|usage| usage.path.as_str()).collect();
+        let statuses: Vec<i32> = usage.iter().map(|usage| usage.status).collect();
+
+        // выполнение запроса с нужными данными
+        client
+            .execute(statement, &[&key_ids, &methods, &paths, &statuses])
+            .await?;
+
+        Ok(())
+    }
+
+    // обновление времени последнего использования ключей
+    pub async fn touch_api_keys(
+        &self,
+        key_ids: &[Uuid],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // получение подключения из пула
+        let client = self.pool.get().await?;
+
+        // форма запроса
+        let statement = "UPDATE api_keys SET last_used_at = NOW() WHERE key_id = ANY($1);";
+
+        // выполнение запроса с нужными данными
+        client.execute(statement, &[&key_ids]).await?;
+
+        Ok(())
+    }
 }
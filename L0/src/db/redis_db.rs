@@ -1,10 +1,47 @@
 //! инициализация и методы работы с базой данных redis для кэширования
+use crate::cache::CachedOrder;
 use crate::config::DbConfig;
 use crate::model::Order;
 use deadpool_redis::{redis::cmd, Config, CreatePoolError, Pool, Runtime};
 use std::error::Error;
+use std::time::Duration;
+use tracing::warn;
+
+// lua-скрипт token bucket: пополняет корзину по времени сервера redis и списывает один токен,
+// возвращает {1, 0} если запрос разрешён или {0, миллисекунды до следующего токена}
+const TOKEN_BUCKET_SCRIPT: &str = r#"
+local capacity = tonumber(ARGV[1])
+local refill_per_ms = tonumber(ARGV[2]) / 1000
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(bucket[1])
+local ts = tonumber(bucket[2])
+if tokens == nil or ts == nil then
+    tokens = capacity
+    ts = now
+end
+
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * refill_per_ms)
+
+local allowed = 0
+local retry_after = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+else
+    retry_after = math.ceil((1 - tokens) / refill_per_ms)
+end
+
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', tostring(now))
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / refill_per_ms) + 1000)
+
+return {allowed, retry_after}
+"#;
 
 // обёртка вокруг пула подключений
+#[derive(Clone)]
 pub struct RedisDB {
     pool: Pool,
 }
@@ -46,22 +83,43 @@ impl RedisDB {
         Ok(())
     }
 
+    // добавление в кэш по ключу с заданным временем жизни
+    pub async fn set_with_ttl(
+        &self,
+        key: &str,
+        value: &str,
+        ttl: Duration,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // получение подключения из пула
+        let mut conn = self.pool.get().await?;
+
+        // добавление в кэш по ключу вместе с временем жизни
+        cmd("SET")
+            .arg(&[key, value])
+            .arg("PX")
+            .arg(ttl.as_millis().max(1) as u64)
+            .query_async::<()>(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
     // удаление из кэша по ключу
     pub async fn del(&self, key: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
         // получение подключения из пула
         let mut conn = self.pool.get().await?;
 
         // удаление из кэша по ключу
-        cmd("DEL").arg(&[key]).query_async(&mut conn).await?;
+        cmd("DEL").arg(&[key]).query_async::<()>(&mut conn).await?;
 
         Ok(())
     }
 
-    // получение одного заказа по ключу
+    // получение одного заказа (вместе со временем свежести) по ключу
     pub async fn get_order(
         &self,
         key: &str,
-    ) -> Result<Option<Order>, Box<dyn Error + Send + Sync>> {
+    ) -> Result<Option<CachedOrder>, Box<dyn Error + Send + Sync>> {
         // получение подключения из пула
         let mut conn = self.pool.get().await?;
 
@@ -74,11 +132,14 @@ impl RedisDB {
             None => return Ok(None),
         };
 
-        // десериализация одного заказа
-        let order: Order = serde_json::from_str(&data)?;
-        // let order: Order = orders.remove(0);
-
-        Ok(Some(order))
+        // десериализация одного заказа, запись в старом формате считается промахом кэша
+        match serde_json::from_str::<CachedOrder>(&data) {
+            Ok(entry) => Ok(Some(entry)),
+            Err(err) => {
+                warn!("Запись {:?} в кэше в неизвестном формате: {:?}", key, err);
+                Ok(None)
+            }
+        }
     }
 
     // получение всех заказов
@@ -100,4 +161,31 @@ impl RedisDB {
         let orders: Vec<Order> = serde_json::from_str(&data)?;
         Ok(Some(orders))
     }
+
+    // списание одного токена из корзины rate limit-а по ключу, атомарно на стороне redis
+    // возвращает Ok(None) если запрос разрешён или Ok(Some(миллисекунды до следующего токена))
+    pub async fn take_rate_limit_token(
+        &self,
+        key: &str,
+        capacity: u32,
+        refill_per_sec: f64,
+    ) -> Result<Option<u64>, Box<dyn Error + Send + Sync>> {
+        // получение подключения из пула
+        let mut conn = self.pool.get().await?;
+
+        // выполнение скрипта token bucket
+        let (allowed, retry_after_ms): (i64, i64) = cmd("EVAL")
+            .arg(TOKEN_BUCKET_SCRIPT)
+            .arg(1)
+            .arg(key)
+            .arg(capacity)
+            .arg(refill_per_sec)
+            .query_async(&mut conn)
+            .await?;
+
+        match allowed {
+            1 => Ok(None),
+            _ => Ok(Some(retry_after_ms.max(0) as u64)),
+        }
+    }
 }
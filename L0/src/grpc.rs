@@ -0,0 +1,665 @@
+//! gRPC сервис заказов поверх той же модели заказов, что и HTTP API
+use crate::auth::{Authenticator, Scope};
+use crate::config::RateLimitConfig;
+use crate::currency::Currency;
+use crate::model::{Delivery, Item, Order, OrdersModel, Payment, ServerError};
+use crate::rate_limit::{ip_client_id, key_client_id, RateLimiter, API_KEY_HEADER, PRE_AUTH_ROUTE};
+use axum::http::StatusCode;
+use chrono::{DateTime, NaiveDateTime};
+use futures::{Future, Stream, StreamExt, TryStreamExt};
+use std::pin::Pin;
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+// сгенерированный из proto/orders.proto код
+pub mod proto {
+    tonic::include_proto!("orders");
+}
+
+use proto::order_service_server::OrderService;
+pub use proto::order_service_server::OrderServiceServer;
+
+// перевод ошибок модели в статусы gRPC
+impl From<ServerError> for Status {
+    fn from(err: ServerError) -> Self {
+        match err {
+            ServerError::NotFound(text) => Status::not_found(text),
+            ServerError::BadRequest(text) => Status::invalid_argument(text),
+            ServerError::Unauthorized(text) => Status::unauthenticated(text),
+            ServerError::Forbidden(text) => Status::permission_denied(text),
+            ServerError::TimeoutError(text) => Status::deadline_exceeded(text),
+            ServerError::TooManyRequests(retry_after) => Status::resource_exhausted(format!(
+                "Превышен лимит запросов, повторите через {} мс",
+                retry_after.as_millis()
+            )),
+            ServerError::PostgresError(err) => {
+                Status::internal(format!("Ошибка базы данных Postgres {:?}", err))
+            }
+            ServerError::RedisError(err) => {
+                Status::internal(format!("Ошибка базы данных Redis {:?}", err))
+            }
+            ServerError::SerializationError(text) | ServerError::ConversionError(text) => {
+                Status::internal(text)
+            }
+            ServerError::UnknownError => Status::unknown("Неизвестная ошибка"),
+        }
+    }
+}
+
+// дата без часового пояса передаётся как UTC
+fn timestamp_from_naive(date: NaiveDateTime) -> prost_types::Timestamp {
+    let date = date.and_utc();
+    prost_types::Timestamp {
+        seconds: date.timestamp(),
+        nanos: date.timestamp_subsec_nanos() as i32,
+    }
+}
+
+// обратное преобразование, неверная дата - ошибка запроса
+fn naive_from_timestamp(timestamp: prost_types::Timestamp) -> Result<NaiveDateTime, ServerError> {
+    u32::try_from(timestamp.nanos)
+        .ok()
+        .and_then(|nanos| DateTime::from_timestamp(timestamp.seconds, nanos))
+        .map(|date| date.naive_utc())
+        .ok_or_else(|| ServerError::BadRequest("Неверная дата date_created".to_string()))
+}
+
+// разбор order_uid из строки
+fn parse_order_uid(raw: &str) -> Result<Uuid, ServerError> {
+    raw.parse()
+        .map_err(|err| ServerError::BadRequest(format!("Неверный order_uid {:?}: {}", raw, err)))
+}
+
+// разбор необязательной валюты запроса
+fn parse_currency(raw: Option<&str>) -> Result<Option<Currency>, ServerError> {
+    raw.map(|raw| raw.parse::<Currency>().map_err(ServerError::from))
+        .transpose()
+}
+
+impl From<Delivery> for proto::Delivery {
+    fn from(delivery: Delivery) -> Self {
+        proto::Delivery {
+            name: delivery.name,
+            phone: delivery.phone,
+            zip: delivery.zip,
+            city: delivery.city,
+            address: delivery.address,
+            region: delivery.region,
+            email: delivery.email,
+        }
+    }
+}
+
+impl From<proto::Delivery> for Delivery {
+    fn from(delivery: proto::Delivery) -> Self {
+        Delivery {
+            name: delivery.name,
+            phone: delivery.phone,
+            zip: delivery.zip,
+            city: delivery.city,
+            address: delivery.address,
+            region: delivery.region,
+            email: delivery.email,
+        }
+    }
+}
+
+impl From<Payment> for proto::Payment {
+    fn from(payment: Payment) -> Self {
+        proto::Payment {
+            transaction: payment.transaction,
+            request_id: payment.request_id,
+            currency: payment.currency.code().to_string(),
+            provider: payment.provider,
+            amount: payment.amount,
+            payment_dt: payment.payment_dt,
+            bank: payment.bank,
+            delivery_cost: payment.delivery_cost,
+            goods_total: payment.goods_total,
+            custom_fee: payment.custom_fee,
+        }
+    }
+}
+
+impl TryFrom<proto::Payment> for Payment {
+    type Error = ServerError;
+
+    fn try_from(payment: proto::Payment) -> Result<Self, Self::Error> {
+        Ok(Payment {
+            transaction: payment.transaction,
+            request_id: payment.request_id,
+            currency: payment.currency.parse()?,
+            provider: payment.provider,
+            amount: payment.amount,
+            payment_dt: payment.payment_dt,
+            bank: payment.bank,
+            delivery_cost: payment.delivery_cost,
+            goods_total: payment.goods_total,
+            custom_fee: payment.custom_fee,
+        })
+    }
+}
+
+impl From<Item> for proto::Item {
+    fn from(item: Item) -> Self {
+        proto::Item {
+            chrt_id: item.chrt_id,
+            track_number: item.track_number,
+            price: item.price,
+            rid: item.rid,
+            name: item.name,
+            sale: item.sale,
+            size: item.size,
+            total_price: item.total_price,
+            nm_id: item.nm_id,
+            brand: item.brand,
+            status: item.status,
+        }
+    }
+}
+
+impl From<proto::Item> for Item {
+    fn from(item: proto::Item) -> Self {
+        Item {
+            chrt_id: item.chrt_id,
+            track_number: item.track_number,
+            price: item.price,
+            rid: item.rid,
+            name: item.name,
+            sale: item.sale,
+            size: item.size,
+            total_price: item.total_price,
+            nm_id: item.nm_id,
+            brand: item.brand,
+            status: item.status,
+        }
+    }
+}
+
+impl From<Order> for proto::Order {
+    fn from(order: Order) -> Self {
+        proto::Order {
+            order_uid: order.order_uid.to_string(),
+            track_number: order.track_number,
+            entry: order.entry,
+            delivery: Some(order.delivery.into()),
+            payment: Some(order.payment.into()),
+            items: order.items.into_iter().map(proto::Item::from).collect(),
+            locale: order.locale,
+            internal_signature: order.internal_signature,
+            customer_id: order.customer_id,
+            delivery_service: order.delivery_service,
+            shardkey: order.shardkey,
+            sm_id: order.sm_id,
+            date_created: Some(timestamp_from_naive(order.date_created)),
+            oof_shard: order.oof_shard,
+        }
+    }
+}
+
+impl TryFrom<proto::Order> for Order {
+    type Error = ServerError;
+
+    fn try_from(order: proto::Order) -> Result<Self, Self::Error> {
+        // вложенные сообщения в proto3 необязательны, в заказе - обязательны
+        let missing = |field: &str| ServerError::BadRequest(format!("Не передано поле {}", field));
+
+        Ok(Order {
+            order_uid: parse_order_uid(&order.order_uid)?,
+            track_number: order.track_number,
+            entry: order.entry,
+            delivery: order.delivery.ok_or_else(|| missing("delivery"))?.into(),
+            payment: order.payment.ok_or_else(|| missing("payment"))?.try_into()?,
+            items: order.items.into_iter().map(Item::from).collect(),
+            locale: order.locale,
+            internal_signature: order.internal_signature,
+            customer_id: order.customer_id,
+            delivery_service: order.delivery_service,
+            shardkey: order.shardkey,
+            sm_id: order.sm_id,
+            date_created: naive_from_timestamp(
+                order.date_created.ok_or_else(|| missing("date_created"))?,
+            )?,
+            oof_shard: order.oof_shard,
+        })
+    }
+}
+
+// заказов на странице ListOrders: поток читает из Postgres по странице, пока клиент принимает
+const LIST_ORDERS_PAGE_SIZE: i64 = 100;
+
+// заказы по страницам: fetch(after) - страница после заказа after по возрастанию order_uid,
+// следующая страница запрашивается, только когда предыдущая отдана; неполная - последняя
+fn paged_orders<F, Fut>(
+    page_size: i64,
+    fetch: F,
+) -> impl Stream<Item = Result<Order, ServerError>>
+where
+    F: Fn(Option<Uuid>) -> Fut,
+    Fut: Future<Output = Result<Vec<Order>, ServerError>>,
+{
+    // состояние - order_uid последнего отданного заказа, None после последней страницы
+    futures::stream::try_unfold(Some(None), move |after| {
+        let page = after.map(&fetch);
+        async move {
+            let Some(page) = page else {
+                return Ok(None);
+            };
+
+            let orders: Vec<Order> = page.await?;
+            let next = match orders.last() {
+                Some(last) if orders.len() as i64 == page_size => Some(Some(last.order_uid)),
+                _ => None,
+            };
+
+            Ok::<_, ServerError>(Some((
+                futures::stream::iter(orders.into_iter().map(Ok::<_, ServerError>)),
+                next,
+            )))
+        }
+    })
+    .try_flatten()
+}
+
+// поток заказов ListOrders
+type OrderStream = Pin<Box<dyn Stream<Item = Result<proto::Order, Status>> + Send>>;
+// поток заказов модели до перевода в сообщения gRPC
+type OrdersStream = Pin<Box<dyn Stream<Item = Result<Order, ServerError>> + Send>>;
+
+// статус в аудите для потока, брошенного клиентом до конца (как 499 у nginx)
+const CLIENT_CLOSED_REQUEST: u16 = 499;
+
+// вызов on_end со статусом завершения при удалении потока
+struct StreamEnd<F: FnOnce(u16)> {
+    on_end: Option<F>,
+    // None - поток не дочитан до конца или до ошибки
+    status: Option<u16>,
+}
+
+impl<F: FnOnce(u16)> Drop for StreamEnd<F> {
+    fn drop(&mut self) {
+        if let Some(on_end) = self.on_end.take() {
+            on_end(self.status.unwrap_or(CLIENT_CLOSED_REQUEST));
+        }
+    }
+}
+
+// поток с вызовом on_end по его завершении: 200 - поток дочитан, статус ошибки - поток
+// прерван ошибкой, CLIENT_CLOSED_REQUEST - клиент отключился раньше
+fn on_stream_end<T, S, F>(stream: S, on_end: F) -> impl Stream<Item = Result<T, ServerError>>
+where
+    S: Stream<Item = Result<T, ServerError>>,
+    F: FnOnce(u16),
+{
+    let end = StreamEnd {
+        on_end: Some(on_end),
+        status: None,
+    };
+    futures::stream::unfold(
+        (Box::pin(stream), end),
+        |(mut stream, mut end)| async move {
+            let item = stream.next().await;
+            match &item {
+                Some(Ok(_)) => {}
+                Some(Err(err)) => end.status = Some(err.status_code().as_u16()),
+                None => {
+                    end.status.get_or_insert(StatusCode::OK.as_u16());
+                }
+            }
+            item.map(|item| (item, (stream, end)))
+        },
+    )
+}
+
+// gRPC сервис заказов с теми же правами доступа и лимитами, что и HTTP API
+pub struct OrderGrpcService {
+    orders_model: Arc<OrdersModel>,
+    authenticator: Arc<Authenticator>,
+    rate_limiter: Arc<RateLimiter>,
+    rate_limit_config: RateLimitConfig,
+}
+
+impl OrderGrpcService {
+    pub fn new(
+        orders_model: Arc<OrdersModel>,
+        authenticator: Arc<Authenticator>,
+        rate_limiter: Arc<RateLimiter>,
+        rate_limit_config: RateLimitConfig,
+    ) -> Self {
+        OrderGrpcService {
+            orders_model,
+            authenticator,
+            rate_limiter,
+            rate_limit_config,
+        }
+    }
+
+    // проверка лимита маршрута для проверенного ключа
+    async fn check_rate_limit(&self, rpc: &str, client: &str) -> Result<(), Status> {
+        let policy = match rpc {
+            "CreateOrder" => self.rate_limit_config.insert_order,
+            "GetOrder" => self.rate_limit_config.get_order_by_uuid,
+            _ => self.rate_limit_config.get_all_orders,
+        };
+        // ключи лимитов общие с HTTP маршрутами, чтобы лимит нельзя было обойти через gRPC
+        let route = match rpc {
+            "CreateOrder" => "insert_order",
+            "GetOrder" => "get_order_by_uuid",
+            _ => "get_all_orders",
+        };
+
+        self.rate_limiter
+            .check(route, client, &policy)
+            .await
+            .map_err(|retry_after| ServerError::TooManyRequests(retry_after).into())
+    }
+
+    // проверка вызова: ограничение частоты по IP, проверка ключа и scope, ограничение
+    // по проверенному ключу; возвращает key_id ключа и путь вызова для аудита
+    async fn authorize<T>(
+        &self,
+        request: &Request<T>,
+        rpc: &'static str,
+        scope: Scope,
+    ) -> Result<(Uuid, String), Status> {
+        let path = format!("/orders.OrderService/{}", rpc);
+        let api_key = request
+            .metadata()
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok());
+
+        // до проверки ключа - по IP общей с HTTP корзиной, чтобы перебор ключей не обходил лимит
+        // и не нагружал Postgres
+        let ip = request.remote_addr().map(|addr| addr.ip());
+        self.rate_limiter
+            .check(
+                PRE_AUTH_ROUTE,
+                &ip_client_id(ip),
+                &self.rate_limit_config.pre_auth_ip,
+            )
+            .await
+            .map_err(|retry_after| Status::from(ServerError::TooManyRequests(retry_after)))?;
+
+        let api_key = self
+            .authenticator
+            .authorize(api_key, scope, "GRPC", &path)
+            .await?;
+        self.check_rate_limit(rpc, &key_client_id(&api_key.key_id))
+            .await?;
+
+        Ok((api_key.key_id, path))
+    }
+
+    // выполнение вызова: проверка, обработчик и запись в аудит
+    async fn call<T, R, F>(
+        &self,
+        request: Request<T>,
+        rpc: &'static str,
+        scope: Scope,
+        handler: impl FnOnce(T) -> F,
+    ) -> Result<R, Status>
+    where
+        F: Future<Output = Result<R, ServerError>>,
+    {
+        let (key_id, path) = self.authorize(&request, rpc, scope).await?;
+
+        let result = handler(request.into_inner()).await;
+        let status = match &result {
+            Ok(_) => StatusCode::OK,
+            Err(err) => err.status_code(),
+        };
+        self.authenticator
+            .record_usage(key_id, "GRPC".to_string(), path, status.as_u16());
+
+        result.map_err(Status::from)
+    }
+
+    // выполнение потокового вызова: как call, но при успешном старте запись в аудит
+    // делается по завершении потока, а не до того, как клиент его прочитал
+    async fn call_stream<T, S, F>(
+        &self,
+        request: Request<T>,
+        rpc: &'static str,
+        scope: Scope,
+        handler: impl FnOnce(T) -> F,
+    ) -> Result<Response<OrderStream>, Status>
+    where
+        F: Future<Output = Result<S, ServerError>>,
+        S: Stream<Item = Result<Order, ServerError>> + Send + 'static,
+    {
+        let (key_id, path) = self.authorize(&request, rpc, scope).await?;
+
+        let orders = match handler(request.into_inner()).await {
+            Ok(orders) => orders,
+            Err(err) => {
+                self.authenticator.record_usage(
+                    key_id,
+                    "GRPC".to_string(),
+                    path,
+                    err.status_code().as_u16(),
+                );
+                return Err(err.into());
+            }
+        };
+
+        let authenticator = self.authenticator.clone();
+        let orders = on_stream_end(orders, move |status| {
+            authenticator.record_usage(key_id, "GRPC".to_string(), path, status)
+        });
+        Ok(Response::new(Box::pin(
+            orders.map_ok(proto::Order::from).map_err(Status::from),
+        )))
+    }
+}
+
+#[tonic::async_trait]
+impl OrderService for OrderGrpcService {
+    // добавление одного заказа
+    async fn create_order(
+        &self,
+        request: Request<proto::CreateOrderRequest>,
+    ) -> Result<Response<proto::Order>, Status> {
+        let orders_model = self.orders_model.clone();
+        self.call(request, "CreateOrder", Scope::OrdersWrite, |request| async move {
+            let order: Order = request
+                .order
+                .ok_or_else(|| ServerError::BadRequest("Не передан заказ".to_string()))?
+                .try_into()?;
+
+            orders_model.insert_order(&order).await?;
+
+            Ok(Response::new(order.into()))
+        })
+        .await
+    }
+
+    // получение одного заказа по order_uid
+    async fn get_order(
+        &self,
+        request: Request<proto::GetOrderRequest>,
+    ) -> Result<Response<proto::Order>, Status> {
+        let orders_model = self.orders_model.clone();
+        self.call(request, "GetOrder", Scope::OrdersRead, |request| async move {
+            let order_uid = parse_order_uid(&request.order_uid)?;
+            let currency = parse_currency(request.currency.as_deref())?;
+
+            let mut order = orders_model.get_one_order_by_uuid(&order_uid).await?;
+
+            // пересчёт в нужную валюту
+            if let Some(currency) = currency {
+                order = orders_model.convert_orders(vec![order], currency)?.remove(0);
+            }
+
+            Ok(Response::new(order.into()))
+        })
+        .await
+    }
+
+    type ListOrdersStream = OrderStream;
+
+    // получение всех заказов потоком, по одному сообщению на заказ: из кэша redis, если весь
+    // список там есть, иначе из Postgres страницами по мере того, как клиент принимает сообщения
+    async fn list_orders(
+        &self,
+        request: Request<proto::ListOrdersRequest>,
+    ) -> Result<Response<Self::ListOrdersStream>, Status> {
+        let orders_model = self.orders_model.clone();
+        self.call_stream(request, "ListOrders", Scope::OrdersRead, |request| async move {
+            let currency = parse_currency(request.currency.as_deref())?;
+            // пересчёт в нужную валюту
+            let convert = move |orders_model: &OrdersModel, orders| match currency {
+                Some(currency) => orders_model.convert_orders(orders, currency),
+                None => Ok(orders),
+            };
+
+            // в кэше - порядок Postgres без сортировки, поток отдаёт по возрастанию order_uid
+            if let Some(mut orders) = orders_model.get_cached_orders().await? {
+                orders.sort_by_key(|order| order.order_uid);
+                let orders = convert(&orders_model, orders)?;
+                let stream: OrdersStream = Box::pin(futures::stream::iter(orders).map(Ok));
+                return Ok(stream);
+            }
+
+            let orders = paged_orders(LIST_ORDERS_PAGE_SIZE, move |after| {
+                let orders_model = orders_model.clone();
+                async move {
+                    let orders = orders_model
+                        .get_orders_page(after, LIST_ORDERS_PAGE_SIZE)
+                        .await?;
+                    convert(&orders_model, orders)
+                }
+            });
+            let stream: OrdersStream = Box::pin(orders);
+            Ok(stream)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{on_stream_end, paged_orders, proto, CLIENT_CLOSED_REQUEST};
+    use crate::currency::CurrencyError;
+    use crate::model::{Order, ServerError};
+    use std::fs::read_to_string;
+    use tonic::Code;
+
+    // заказ из тестовых данных
+    fn test_order() -> Order {
+        let contents = read_to_string("additional_files/model.json").unwrap();
+        let mut orders: Vec<Order> = serde_json::from_str(&contents).unwrap();
+        orders.remove(0)
+    }
+
+    #[test]
+    // заказ без потерь переводится в сообщение gRPC и обратно
+    fn test_order_proto_round_trip() {
+        let order = test_order();
+
+        let message = proto::Order::from(order.clone());
+        assert_eq!(message.order_uid, order.order_uid.to_string());
+        assert_eq!(message.payment.as_ref().unwrap().currency, order.payment.currency.code());
+
+        let restored = Order::try_from(message).unwrap();
+        assert_eq!(restored, order);
+    }
+
+    #[test]
+    // неполное или неверное сообщение - ошибка запроса, а не паника
+    fn test_invalid_order_proto() {
+        let order = test_order();
+
+        let mut message = proto::Order::from(order.clone());
+        message.payment = None;
+        let err = Order::try_from(message).unwrap_err();
+        assert!(matches!(err, ServerError::BadRequest(_)));
+        assert_eq!(tonic::Status::from(err).code(), Code::InvalidArgument);
+
+        let mut message = proto::Order::from(order);
+        message.order_uid = "not-a-uuid".to_string();
+        assert!(matches!(
+            Order::try_from(message),
+            Err(ServerError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    // неверный код валюты - ошибка запроса, нет курса сохранённой валюты или переполнение - внутренняя
+    fn test_currency_error_status() {
+        let status = |err: CurrencyError| tonic::Status::from(ServerError::from(err)).code();
+
+        assert_eq!(
+            status(CurrencyError::UnknownCurrency("ABC".to_string())),
+            Code::InvalidArgument
+        );
+        assert_eq!(
+            status(CurrencyError::MissingRate("GBP".parse().unwrap())),
+            Code::Internal
+        );
+        assert_eq!(status(CurrencyError::Overflow), Code::Internal);
+    }
+
+    #[tokio::test]
+    // страницы запрашиваются после последнего отданного заказа, неполная - последняя
+    async fn test_paged_orders() {
+        use futures::TryStreamExt;
+        use std::sync::Mutex;
+
+        let mut orders: Vec<Order> = (0..5)
+            .map(|_| {
+                let mut order = test_order();
+                order.order_uid = uuid::Uuid::new_v4();
+                order
+            })
+            .collect();
+        orders.sort_by_key(|order| order.order_uid);
+
+        let requests = Mutex::new(Vec::new());
+        let streamed: Vec<Order> = paged_orders(2, |after| {
+            requests.lock().unwrap().push(after);
+            let page: Vec<Order> = orders
+                .iter()
+                .filter(|order| after.is_none_or(|after| order.order_uid > after))
+                .take(2)
+                .cloned()
+                .collect();
+            async move { Ok(page) }
+        })
+        .try_collect()
+        .await
+        .unwrap();
+
+        assert_eq!(streamed, orders);
+        assert_eq!(
+            *requests.lock().unwrap(),
+            [None, Some(orders[1].order_uid), Some(orders[3].order_uid)]
+        );
+    }
+
+    #[tokio::test]
+    // статус завершения потока: дочитан, прерван ошибкой или брошен клиентом
+    async fn test_on_stream_end() {
+        use futures::StreamExt;
+        use std::sync::{Arc, Mutex};
+
+        let ended = Arc::new(Mutex::new(Vec::new()));
+        let stream = |items: Vec<Result<u32, ServerError>>| {
+            let ended = ended.clone();
+            on_stream_end(futures::stream::iter(items), move |status| {
+                ended.lock().unwrap().push(status)
+            })
+        };
+
+        assert_eq!(stream(vec![Ok(1), Ok(2)]).count().await, 2);
+        let not_found = || Err(ServerError::NotFound("заказ".to_string()));
+        assert_eq!(stream(vec![Ok(1), not_found()]).count().await, 2);
+        // до завершения потока статус не записывается
+        let mut dropped = Box::pin(stream(vec![Ok(1), Ok(2)]));
+        assert!(dropped.next().await.is_some());
+        assert_eq!(ended.lock().unwrap().len(), 2);
+        drop(dropped);
+
+        assert_eq!(*ended.lock().unwrap(), [200, 404, CLIENT_CLOSED_REQUEST]);
+    }
+}
@@ -1,22 +1,68 @@
 //! декларация модулей для скриптов и декларация тестов
+pub mod auth;
+pub mod cache;
 pub mod config;
+pub mod currency;
 pub mod db {
     pub mod postgres_db;
     pub mod redis_db;
 }
 pub mod controller;
+pub mod grpc;
 pub mod model;
+pub mod rate_limit;
 
 #[cfg(test)]
 mod tests {
+    use crate::auth::{generate_api_key, Scope};
+    use crate::config::DbConfig;
+    use crate::db::postgres_db::PostgresDB;
     use crate::model::Order;
     use reqwest::Client;
+    use std::env;
     use std::fs::File;
     use std::io::Read;
 
+    // ключ со scope orders:read и orders:write для запросов к серверу: из L0_API_KEY или новый,
+    // созданный в Postgres из .env как в api_keys_script; None - ключа нет, тест пропускается
+    async fn api_key() -> Option<String> {
+        if let Ok(api_key) = env::var("L0_API_KEY") {
+            return Some(api_key);
+        }
+
+        let (api_key, key_hash) = generate_api_key();
+        let created = match PostgresDB::new(&DbConfig::new()).await {
+            Ok(postgres_instance) => {
+                postgres_instance
+                    .insert_api_key(
+                        "l0-tests",
+                        &key_hash,
+                        &[Scope::OrdersRead, Scope::OrdersWrite],
+                    )
+                    .await
+            }
+            Err(err) => Err(err.into()),
+        };
+
+        match created {
+            Ok(_) => Some(api_key),
+            Err(err) => {
+                eprintln!(
+                    "тест пропущен: L0_API_KEY не задан, а создать ключ в Postgres не удалось: {}",
+                    err
+                );
+                None
+            }
+        }
+    }
+
     #[tokio::test]
     // тест добавления и получения множества заказов из базы
     async fn test_add_many_orders() {
+        let Some(api_key) = api_key().await else {
+            return;
+        };
+
         let mut file = File::open("additional_files/model.json").unwrap();
         let mut contents = String::new();
         file.read_to_string(&mut contents).unwrap();
@@ -30,6 +76,7 @@ mod tests {
         for order in &orders {
             client
                 .post("http://127.0.0.1:3000/orders")
+                .header("x-api-key", &api_key)
                 .json(order)
                 .send()
                 .await
@@ -39,6 +86,7 @@ mod tests {
         // http get запрос с помощью reqwest
         let response = client
             .get("http://127.0.0.1:3000/orders")
+            .header("x-api-key", &api_key)
             .send()
             .await
             .unwrap()
@@ -60,6 +108,10 @@ mod tests {
     #[tokio::test]
     // тест добавления и получения одного заказа из базы
     async fn test_add_one_order() {
+        let Some(api_key) = api_key().await else {
+            return;
+        };
+
         let mut file = File::open("additional_files/model.json").unwrap();
         let mut contents = String::new();
         file.read_to_string(&mut contents).unwrap();
@@ -72,6 +124,7 @@ mod tests {
         let client = Client::new();
         client
             .post("http://127.0.0.1:3000/orders")
+            .header("x-api-key", &api_key)
             .json(&one_order)
             .send()
             .await
@@ -83,6 +136,7 @@ mod tests {
                 "http://127.0.0.1:3000/orders/{}",
                 &one_order.order_uid
             ))
+            .header("x-api-key", &api_key)
             .send()
             .await
             .unwrap()
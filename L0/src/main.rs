@@ -1,39 +1,263 @@
 //! запуск приложения и веб-сервера
+pub mod auth;
+pub mod cache;
 pub mod config;
+pub mod currency;
 pub mod db {
     pub mod postgres_db;
     pub mod redis_db;
 }
 pub mod controller;
+pub mod grpc;
 pub mod model;
+pub mod rate_limit;
 
-use crate::config::DbConfig;
-use crate::controller::{get_all_orders, get_order_by_uuid, insert_order};
+use crate::auth::{require_scope, Authenticator, RouteAuth, Scope};
+use crate::config::{CurrencyConfig, DbConfig, GrpcConfig, RateLimitConfig};
+use crate::controller::{get_all_orders, get_order_by_uuid, get_payments_summary, insert_order};
+use crate::grpc::{OrderGrpcService, OrderServiceServer};
 use crate::model::OrdersModel;
-use axum::routing::{get, post};
+use crate::rate_limit::{
+    rate_limit_ip, rate_limit_key, RateLimiter, RouteRateLimit, PRE_AUTH_ROUTE,
+};
+use axum::middleware::from_fn_with_state;
+use axum::routing::{get, post, MethodRouter};
 use axum::Router;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tracing::{info, Level};
 
+// защита маршрута: ограничение частоты запросов по IP общей политикой до проверки ключа,
+// проверка API-ключа и его scope, затем ограничение по проверенному ключу политикой маршрута
+// (внешний слой добавляется последним)
+fn protected<S: Clone + Send + Sync + 'static>(
+    method_router: MethodRouter<S>,
+    route_auth: RouteAuth,
+    ip_rate_limit: RouteRateLimit,
+    route_rate_limit: RouteRateLimit,
+) -> MethodRouter<S> {
+    method_router
+        .layer(from_fn_with_state(route_rate_limit, rate_limit_key))
+        .layer(from_fn_with_state(route_auth, require_scope))
+        .layer(from_fn_with_state(ip_rate_limit, rate_limit_ip))
+}
+
 #[tokio::main]
 async fn main() {
     // ицициализация базы данных
     let db_config = DbConfig::new();
+    // инициализация валют и курсов
+    let currency_config = CurrencyConfig::new();
     // инициализация модели заказов
-    let orders_model: Arc<OrdersModel> = Arc::new(OrdersModel::new(&db_config).await.unwrap());
+    let orders_model: Arc<OrdersModel> =
+        Arc::new(OrdersModel::new(&db_config, &currency_config).await.unwrap());
+
+    // инициализация ограничителя частоты запросов поверх пула redis
+    let rate_limit_config = RateLimitConfig::new();
+    let rate_limiter = Arc::new(RateLimiter::new(orders_model.redis_instance().clone()));
+    // корзина IP до проверки ключа - одна на все маршруты и gRPC
+    let ip_rate_limit = RouteRateLimit::new(
+        rate_limiter.clone(),
+        PRE_AUTH_ROUTE,
+        rate_limit_config.pre_auth_ip,
+    );
+
+    // инициализация проверки API-ключей поверх пула Postgres
+    let authenticator = Arc::new(Authenticator::new(
+        orders_model.postgres_instance().clone(),
+    ));
 
     // инициализация логирования
     tracing_subscriber::fmt().with_max_level(Level::INFO).init();
 
-    // конфигурация энд-поинтов и общих ресурсов
+    // gRPC сервис заказов на отдельном порту с теми же моделью, ключами и лимитами
+    let grpc_config = GrpcConfig::new();
+    let grpc_service = OrderGrpcService::new(
+        orders_model.clone(),
+        authenticator.clone(),
+        rate_limiter.clone(),
+        rate_limit_config.clone(),
+    );
+    let grpc_addr = SocketAddr::from(([0, 0, 0, 0], grpc_config.port));
+    tokio::spawn(async move {
+        info!("Сервер gRPC готов принимать запросы на порту {}", grpc_config.port);
+        tonic::transport::Server::builder()
+            .add_service(OrderServiceServer::new(grpc_service))
+            .serve(grpc_addr)
+            .await
+            .unwrap();
+    });
+
+    // конфигурация энд-поинтов, прав доступа и лимитов запросов на каждый из них и общих ресурсов
     let app = Router::new()
-        .route("/orders", get(get_all_orders))
-        .route("/orders/:order_uuid", get(get_order_by_uuid))
-        .route("/orders", post(insert_order))
+        .route(
+            "/orders",
+            protected(
+                get(get_all_orders),
+                RouteAuth::new(authenticator.clone(), Scope::OrdersRead),
+                ip_rate_limit.clone(),
+                RouteRateLimit::new(
+                    rate_limiter.clone(),
+                    "get_all_orders",
+                    rate_limit_config.get_all_orders,
+                ),
+            ),
+        )
+        .route(
+            "/orders/:order_uuid",
+            protected(
+                get(get_order_by_uuid),
+                RouteAuth::new(authenticator.clone(), Scope::OrdersRead),
+                ip_rate_limit.clone(),
+                RouteRateLimit::new(
+                    rate_limiter.clone(),
+                    "get_order_by_uuid",
+                    rate_limit_config.get_order_by_uuid,
+                ),
+            ),
+        )
+        .route(
+            "/orders",
+            protected(
+                post(insert_order),
+                RouteAuth::new(authenticator.clone(), Scope::OrdersWrite),
+                ip_rate_limit.clone(),
+                RouteRateLimit::new(
+                    rate_limiter.clone(),
+                    "insert_order",
+                    rate_limit_config.insert_order,
+                ),
+            ),
+        )
+        .route(
+            "/analytics/payments",
+            protected(
+                get(get_payments_summary),
+                RouteAuth::new(authenticator.clone(), Scope::OrdersAdmin),
+                ip_rate_limit.clone(),
+                RouteRateLimit::new(
+                    rate_limiter.clone(),
+                    "analytics",
+                    rate_limit_config.analytics,
+                ),
+            ),
+        )
         .with_state(orders_model);
 
     // старт сервера на порту 3000
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
     info!("Сервер AXUM готов принимать запросы на порту 3000");
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::protected;
+    use crate::auth::{Authenticator, RouteAuth, Scope};
+    use crate::config::{DbConfig, RatePolicy};
+    use crate::db::postgres_db::PostgresDB;
+    use crate::db::redis_db::RedisDB;
+    use crate::rate_limit::{RateLimiter, RouteRateLimit, API_KEY_HEADER, PRE_AUTH_ROUTE};
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::Router;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    // сервер с защищённым маршрутом поверх недоступных баз: лимит - в памяти,
+    // проверка ключа - ошибка, но не 429
+    async fn serve(ip_policy: RatePolicy, route_policy: RatePolicy) -> SocketAddr {
+        let db_config = DbConfig {
+            pg_host: "127.0.0.1".to_string(),
+            pg_user: "l0".to_string(),
+            pg_password: "l0".to_string(),
+            pg_dbname: "l0".to_string(),
+            redis_host: "127.0.0.1".to_string(),
+            redis_port: "1".to_string(),
+        };
+        let rate_limiter = Arc::new(RateLimiter::new(RedisDB::new(&db_config).await.unwrap()));
+        let authenticator = Arc::new(Authenticator::new(
+            PostgresDB::new(&db_config).await.unwrap(),
+        ));
+
+        let app = Router::new().route(
+            "/orders",
+            protected(
+                get(|| async { "ok" }),
+                RouteAuth::new(authenticator, Scope::OrdersRead),
+                RouteRateLimit::new(rate_limiter.clone(), PRE_AUTH_ROUTE, ip_policy),
+                RouteRateLimit::new(rate_limiter, "test", route_policy),
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .unwrap();
+        });
+        addr
+    }
+
+    // статусы ответов на запросы с выдуманными ключами
+    async fn rotate_invalid_keys(addr: SocketAddr, count: usize) -> Vec<StatusCode> {
+        let client = reqwest::Client::new();
+        let mut statuses = Vec::new();
+        for _ in 0..count {
+            let response = client
+                .get(format!("http://{}/orders", addr))
+                .header(API_KEY_HEADER, format!("l0_{}", Uuid::new_v4().simple()))
+                .send()
+                .await
+                .unwrap();
+            statuses.push(response.status());
+        }
+        statuses
+    }
+
+    #[tokio::test]
+    // перебор выдуманных ключей с одного IP упирается в лимит IP
+    async fn test_rotating_invalid_keys_are_limited_by_ip() {
+        let ip_policy = RatePolicy {
+            capacity: 3,
+            refill_per_sec: 0.01,
+        };
+        let route_policy = RatePolicy {
+            capacity: 100,
+            refill_per_sec: 100.0,
+        };
+        let statuses = rotate_invalid_keys(serve(ip_policy, route_policy).await, 5).await;
+
+        assert!(statuses[..3]
+            .iter()
+            .all(|status| *status != StatusCode::TOO_MANY_REQUESTS));
+        assert_eq!(statuses[3..], [StatusCode::TOO_MANY_REQUESTS; 2]);
+    }
+
+    #[tokio::test]
+    // политика маршрута до проверки ключа не применяется
+    async fn test_route_policy_is_not_applied_by_ip() {
+        let ip_policy = RatePolicy {
+            capacity: 100,
+            refill_per_sec: 100.0,
+        };
+        let route_policy = RatePolicy {
+            capacity: 1,
+            refill_per_sec: 0.01,
+        };
+        let statuses = rotate_invalid_keys(serve(ip_policy, route_policy).await, 5).await;
+
+        assert!(statuses
+            .iter()
+            .all(|status| *status != StatusCode::TOO_MANY_REQUESTS));
+    }
 }
@@ -1,12 +1,16 @@
 //! декларация модели данных, возможных ошибок сервера и основной логики модели заказов
-use crate::config::DbConfig;
+use crate::cache::OrderLoader;
+use crate::config::{CurrencyConfig, DbConfig};
+use crate::currency::{Currency, CurrencyError, ExchangeRates};
 use crate::db::postgres_db::PostgresDB;
 use crate::db::redis_db::RedisDB;
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::error::Elapsed;
 use tokio::time::timeout;
@@ -14,7 +18,7 @@ use tracing::{error, info, warn};
 use uuid::Uuid;
 
 // структура доставки
-#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Delivery {
     pub name: String,
     pub phone: String,
@@ -25,39 +29,39 @@ pub struct Delivery {
     pub email: String,
 }
 
-// структура оплаты
-#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Ord, PartialOrd)]
+// структура оплаты, денежные поля - в минимальных единицах валюты (копейки, центы)
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Payment {
     pub transaction: String,
     pub request_id: String,
-    pub currency: String,
+    pub currency: Currency,
     pub provider: String,
-    pub amount: i32,
+    pub amount: i64,
     pub payment_dt: i32,
     pub bank: String,
-    pub delivery_cost: i32,
-    pub goods_total: i32,
-    pub custom_fee: i32,
+    pub delivery_cost: i64,
+    pub goods_total: i64,
+    pub custom_fee: i64,
 }
 
-// структура вещи
-#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Ord, PartialOrd)]
+// структура вещи, цены - в минимальных единицах валюты оплаты
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Item {
     pub chrt_id: i32,
     pub track_number: String,
-    pub price: i32,
+    pub price: i64,
     pub rid: String,
     pub name: String,
     pub sale: i32,
     pub size: String,
-    pub total_price: i32,
+    pub total_price: i64,
     pub nm_id: i32,
     pub brand: String,
     pub status: i32,
 }
 
 // структура заказа
-#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Order {
     pub order_uid: Uuid,
     pub track_number: String,
@@ -75,16 +79,55 @@ pub struct Order {
     pub oof_shard: String,
 }
 
-// потенциальные ошибки
+// сводка по оплатам всех заказов в базовой валюте
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct PaymentsSummary {
+    pub base_currency: Currency,
+    pub orders_count: usize,
+    pub amount: i64,
+    pub delivery_cost: i64,
+    pub goods_total: i64,
+    pub custom_fee: i64,
+    // количество заказов в каждой исходной валюте
+    pub orders_by_currency: BTreeMap<Currency, usize>,
+}
+
+// потенциальные ошибки (Clone - для раздачи одного результата нескольким запросам)
+#[derive(Debug, Clone)]
 pub enum ServerError {
     NotFound(String),
-    PostgresError(Box<dyn Error>),
-    RedisError(Box<dyn Error>),
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    PostgresError(Arc<dyn Error + Send + Sync>),
+    RedisError(Arc<dyn Error + Send + Sync>),
     TimeoutError(String),
     SerializationError(String),
+    // пересчёт сохранённых сумм невозможен: нет курса или переполнение
+    ConversionError(String),
+    TooManyRequests(Duration),
     UnknownError,
 }
 
+impl ServerError {
+    // HTTP статус ошибки (для ответа и для аудита запросов, в том числе gRPC)
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ServerError::NotFound(_) => StatusCode::NOT_FOUND,
+            ServerError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ServerError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ServerError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ServerError::TimeoutError(_) => StatusCode::REQUEST_TIMEOUT,
+            ServerError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            ServerError::PostgresError(_)
+            | ServerError::RedisError(_)
+            | ServerError::SerializationError(_)
+            | ServerError::ConversionError(_)
+            | ServerError::UnknownError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
 // для обработки потенциальных ошибок сервером Axum
 impl IntoResponse for ServerError {
     fn into_response(self) -> Response {
@@ -93,6 +136,18 @@ impl IntoResponse for ServerError {
                 warn!("Данные по запросу не найдены: {:?}", text);
                 (StatusCode::NOT_FOUND, format!("Данные по запросу не найдены: {:?}", text)).into_response()
             }
+            ServerError::BadRequest(text) => {
+                warn!("Неверный запрос: {:?}", text);
+                (StatusCode::BAD_REQUEST, format!("Неверный запрос: {:?}", text)).into_response()
+            }
+            ServerError::Unauthorized(text) => {
+                warn!("Запрос без доступа: {:?}", text);
+                (StatusCode::UNAUTHORIZED, format!("Нет доступа: {:?}", text)).into_response()
+            }
+            ServerError::Forbidden(text) => {
+                warn!("Недостаточно прав: {:?}", text);
+                (StatusCode::FORBIDDEN, format!("Недостаточно прав: {:?}", text)).into_response()
+            }
             ServerError::PostgresError(err) => {
                 error!("Ошибка базы данных Postgres {:?}", err);
                 (
@@ -121,6 +176,21 @@ impl IntoResponse for ServerError {
                 error!("Ошибка сериализации в запросе {:?}", text);
                 (StatusCode::INTERNAL_SERVER_ERROR, format!("Ошибка десериализации в запросе{:?}", text)).into_response()
             }
+            ServerError::ConversionError(text) => {
+                error!("Ошибка пересчёта валюты {:?}", text);
+                (StatusCode::INTERNAL_SERVER_ERROR, format!("Ошибка пересчёта валюты: {:?}", text)).into_response()
+            }
+            ServerError::TooManyRequests(retry_after) => {
+                // Retry-After в целых секундах, округление вверх
+                let retry_after_secs = retry_after.as_millis().div_ceil(1000).max(1);
+                warn!("Превышен лимит запросов, повтор через {} с", retry_after_secs);
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                    format!("Превышен лимит запросов, повторите через {} с", retry_after_secs),
+                )
+                    .into_response()
+            }
             ServerError::UnknownError => {
                 error!("Неизвестная ошибка");
                 (StatusCode::INTERNAL_SERVER_ERROR, "Неизвестная ошибка").into_response()
@@ -129,26 +199,77 @@ impl IntoResponse for ServerError {
     }
 }
 
+// неверный код валюты - ошибка в параметрах запроса, а нет курса для уже сохранённой валюты
+// или переполнение суммы - ошибка конфигурации курсов или данных
+impl From<CurrencyError> for ServerError {
+    fn from(err: CurrencyError) -> Self {
+        match err {
+            CurrencyError::UnknownCurrency(_) | CurrencyError::InvalidRate(_) => {
+                ServerError::BadRequest(err.to_string())
+            }
+            CurrencyError::MissingRate(_) | CurrencyError::Overflow => {
+                ServerError::ConversionError(err.to_string())
+            }
+        }
+    }
+}
+
 // структура модели заказов для передачи трэдам axum/tokio с помощью разделённого состояния
 pub struct OrdersModel {
     postgres_instance: PostgresDB,
     redis_instance: RedisDB,
+    order_loader: OrderLoader<PostgresDB, RedisDB>,
+    exchange_rates: ExchangeRates,
 }
 
+// сколько заказ в кэше считается свежим
+const ORDER_FRESH_TTL: Duration = Duration::from_secs(100);
+// сколько устаревший заказ ещё отдаётся из кэша, пока он обновляется в фоне
+const ORDER_STALE_TTL: Duration = Duration::from_secs(30);
+
 // функции работы с данными о заказе / базами данных
 impl OrdersModel {
     // инициализация модели заказов
-    pub async fn new(db_config: &DbConfig) -> Result<Self, Box<dyn Error>> {
+    pub async fn new(
+        db_config: &DbConfig,
+        currency_config: &CurrencyConfig,
+    ) -> Result<Self, Box<dyn Error>> {
         // инициализация базы данных
         let postgres_instance = PostgresDB::new(db_config).await?;
         let redis_instance = RedisDB::new(db_config).await?;
 
+        // загрузка таблицы курсов, без файла - пересчёт возможен только в базовую валюту
+        let exchange_rates = match &currency_config.exchange_rates_path {
+            Some(path) => ExchangeRates::from_csv_file(currency_config.base_currency, path)?,
+            None => ExchangeRates::new(currency_config.base_currency),
+        };
+
+        // загрузчик отдельных заказов поверх тех же пулов подключений
+        let order_loader = OrderLoader::new(
+            postgres_instance.clone(),
+            redis_instance.clone(),
+            ORDER_FRESH_TTL,
+            ORDER_STALE_TTL,
+        );
+
         Ok(OrdersModel {
             postgres_instance,
             redis_instance,
+            order_loader,
+            exchange_rates,
         })
     }
 
+    // пул Postgres модели, например для проверки API-ключей
+    pub fn postgres_instance(&self) -> &PostgresDB {
+        &self.postgres_instance
+    }
+
+    // пул redis модели, например для ограничения частоты запросов
+    pub fn redis_instance(&self) -> &RedisDB {
+        &self.redis_instance
+    }
+
     // добавлене нового заказа в базу
     pub async fn insert_order(&self, order: &Order) -> Result<(), ServerError> {
         // запрос к базе данных с тайм-аутом
@@ -163,13 +284,13 @@ impl OrdersModel {
             Ok(()) => {
                 info!("Данные о всех заказах удалены из кэша Redis")
             }
-            Err(err) => return Err(ServerError::PostgresError(err)),
+            Err(err) => return Err(ServerError::PostgresError(err.into())),
         }
 
         // обработка ошибок
         match insert_order_result {
             Ok(Ok(())) => Ok(()),
-            Ok(Err(err)) => Err(ServerError::PostgresError(err)),
+            Ok(Err(err)) => Err(ServerError::PostgresError(err.into())),
             Err(Elapsed { .. }) => Err(ServerError::TimeoutError(
                 format!("Добавление в базу данных: {:?}", order)
             )),
@@ -187,30 +308,38 @@ impl OrdersModel {
         // обработка ошибок redis
         match redis_result {
             Ok(Ok(())) => Ok(()),
-            Ok(Err(err )) => Err(ServerError::RedisError(err)),
+            Ok(Err(err )) => Err(ServerError::RedisError(err.into())),
             Err(Elapsed { .. }) => Err(ServerError::TimeoutError(
                 format!("добавление в кэш key: {:?}, value: {:?}", key, value)
             )),
         }
     }
 
-    // получение всех заказов
-    pub async fn get_all_orders(&self) -> Result<Vec<Order>, ServerError> {
+    // все заказы из кэша redis, None - в кэше их нет или redis не ответил вовремя
+    pub async fn get_cached_orders(&self) -> Result<Option<Vec<Order>>, ServerError> {
         // запрос к базе данных redis с тайм-аутом
         let redis_get_result = timeout(Duration::from_secs(1), async {
             self.redis_instance.get_all_orders().await
         })
         .await;
 
-        // если данные есть в кэшэ - их возрат, обработка ошибок
+        // обработка ошибок
         match redis_get_result {
-            Ok(Ok(Some(orders))) => return Ok(orders),
-            Ok(Ok(None)) => {}
-            Ok(Err(err)) => return Err(ServerError::RedisError(err)),
+            Ok(Ok(orders)) => Ok(orders),
+            Ok(Err(err)) => Err(ServerError::RedisError(err.into())),
             Err(Elapsed { .. }) => {
-                warn!("Тайм-аут запроса всех заказов из кэша Redis")
+                warn!("Тайм-аут запроса всех заказов из кэша Redis");
+                Ok(None)
             }
-        };
+        }
+    }
+
+    // получение всех заказов
+    pub async fn get_all_orders(&self) -> Result<Vec<Order>, ServerError> {
+        // если данные есть в кэшэ - их возрат
+        if let Some(orders) = self.get_cached_orders().await? {
+            return Ok(orders);
+        }
 
         // запрос к базе данных postgres с тайм-аутом
         let postgres_result = timeout(Duration::from_secs(1), async {
@@ -239,75 +368,89 @@ impl OrdersModel {
                 }
             }
             Ok(Ok(None)) => Err(ServerError::NotFound("Получение всех заказов из базы".to_string())),
-            Ok(Err(err)) => Err(ServerError::PostgresError(err)),
+            Ok(Err(err)) => Err(ServerError::PostgresError(err.into())),
             Err(Elapsed { .. }) => Err(ServerError::TimeoutError(
                 "Получение всех заказов из базы".to_string(),
             )),
         }
     }
 
-    // получение всех заказов по uuid
-    pub async fn get_one_order_by_uuid(&self, order_uuid: &Uuid) -> Result<Order, ServerError> {
-        let redis_result = timeout(Duration::from_secs(1), async {
-            self.redis_instance.get_order(&order_uuid.to_string()).await
+    // страница заказов по возрастанию order_uid из Postgres, для выдачи всех заказов потоком
+    // при промахе кэша: кэш хранит только весь список, и собирать его целиком ради потока не нужно
+    pub async fn get_orders_page(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<Order>, ServerError> {
+        // запрос к базе данных postgres с тайм-аутом
+        let postgres_result = timeout(Duration::from_secs(1), async {
+            self.postgres_instance.get_orders_page(after, limit).await
         })
         .await;
 
-        // если данные есть в кэшэ - их возрат, обработка ошибок
-        match redis_result {
-            Ok(Ok(Some(order))) => return Ok(order),
-            Ok(Ok(None)) => {}
-            Ok(Err(err)) => return Err(ServerError::RedisError(err)),
-            Err(Elapsed { .. }) => {
-                warn!("Тайм-аут запроса заказа {:?} из кэша Redis", &order_uuid)
-            }
-        };
+        match postgres_result {
+            Ok(Ok(orders)) => Ok(orders),
+            Ok(Err(err)) => Err(ServerError::PostgresError(err.into())),
+            Err(Elapsed { .. }) => Err(ServerError::TimeoutError(
+                "Получение страницы заказов из базы".to_string(),
+            )),
+        }
+    }
 
-        // запрос к базе данных Postgres с тайм-аутом
-        let order_result = timeout(Duration::from_secs(1), async {
-            self.postgres_instance
-                .get_one_order_by_uuid(order_uuid)
-                .await
-        })
-        .await;
+    // получение заказа по uuid: кэш с фоновым обновлением устаревших записей,
+    // одновременные промахи кэша объединяются в один запрос к Postgres
+    pub async fn get_one_order_by_uuid(&self, order_uuid: &Uuid) -> Result<Order, ServerError> {
+        self.order_loader.get(order_uuid).await
+    }
 
-        // если база postgres вернула данные - запись в кэш, в противном случае - обработка ошибок
-        match order_result {
-            Ok(Ok(Some(order))) => {
-                // сериаизация
-                let order_str_result = serde_json::to_string(&order);
-                let order_str = match order_str_result {
-                    Ok(order_str) => order_str,
-                    Err(_) => return Err(ServerError::SerializationError(format!(
-                        "Получение заказа {:?} из базы",
-                        order_uuid
-                    ))),
-                };
+    // пересчёт денежных полей заказов в нужную валюту
+    pub fn convert_orders(
+        &self,
+        orders: Vec<Order>,
+        currency: Currency,
+    ) -> Result<Vec<Order>, ServerError> {
+        // валюта без курса в запросе - ошибка клиента
+        if !self.exchange_rates.has_rate(currency) {
+            return Err(ServerError::BadRequest(
+                CurrencyError::MissingRate(currency).to_string(),
+            ));
+        }
 
-                // запись в кэш
-                let redis_set_result = self
-                    .add_to_cache(&order.order_uid.to_string(), &order_str)
-                    .await;
-                match redis_set_result {
-                    Ok(()) => {
-                        info!(
-                            "Запрос заказа {} закэширован в базе данных redis",
-                            &order.order_uid
-                        );
-                        Ok(order)
-                    }
-                    Err(err) => Err(err),
-                }
-            }
-            Ok(Ok(None)) => Err(ServerError::NotFound(format!(
-                "Получение заказа {:?} из базы",
-                order_uuid
-            ))),
-            Ok(Err(err)) => Err(ServerError::PostgresError(err)),
-            Err(Elapsed { .. }) => Err(ServerError::TimeoutError(format!(
-                "Получение заказа {:?} из базы",
-                order_uuid
-            ))),
+        orders
+            .into_iter()
+            .map(|order| Ok(self.exchange_rates.convert_order(order, currency)?))
+            .collect()
+    }
+
+    // сводка по оплатам всех заказов в базовой валюте
+    pub async fn get_payments_summary(&self) -> Result<PaymentsSummary, ServerError> {
+        let base_currency = self.exchange_rates.base();
+        let orders = self.get_all_orders().await?;
+
+        let mut summary = PaymentsSummary {
+            base_currency,
+            orders_count: orders.len(),
+            amount: 0,
+            delivery_cost: 0,
+            goods_total: 0,
+            custom_fee: 0,
+            orders_by_currency: BTreeMap::new(),
+        };
+
+        for order in orders {
+            *summary
+                .orders_by_currency
+                .entry(order.payment.currency)
+                .or_insert(0) += 1;
+
+            let payment = self.exchange_rates.convert_order(order, base_currency)?.payment;
+            let add = |total: i64, value: i64| total.checked_add(value).ok_or(CurrencyError::Overflow);
+            summary.amount = add(summary.amount, payment.amount)?;
+            summary.delivery_cost = add(summary.delivery_cost, payment.delivery_cost)?;
+            summary.goods_total = add(summary.goods_total, payment.goods_total)?;
+            summary.custom_fee = add(summary.custom_fee, payment.custom_fee)?;
         }
+
+        Ok(summary)
     }
 }
@@ -0,0 +1,386 @@
+//! ограничение частоты запросов (token bucket) по API-ключу или IP с хранением состояния в redis.
+//! До проверки ключа запросы ограничиваются по IP общей для всех маршрутов свободной политикой,
+//! чтобы перебор ключей не обходил лимит, после проверки - по key_id политикой маршрута
+use crate::auth::ApiKey;
+use crate::config::RatePolicy;
+use crate::db::redis_db::RedisDB;
+use crate::model::ServerError;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::error::Elapsed;
+use tokio::time::timeout;
+use tracing::warn;
+use uuid::Uuid;
+
+// заголовок с API-ключом клиента
+pub const API_KEY_HEADER: &str = "x-api-key";
+// имя общей корзины IP до проверки ключа
+pub const PRE_AUTH_ROUTE: &str = "pre_auth";
+
+// как часто удалять из памяти полностью пополненные корзины
+const LOCAL_BUCKETS_CLEANUP_INTERVAL: Duration = Duration::from_secs(10);
+// жёсткий предел числа корзин в памяти, новые клиенты сверх него делят одну корзину маршрута
+const LOCAL_BUCKETS_LIMIT: usize = 100_000;
+
+// корзина токенов для запасного ограничения в памяти, когда redis недоступен
+#[derive(Debug, Clone, Copy)]
+struct LocalBucket {
+    // политика маршрута, с которой создана корзина
+    policy: RatePolicy,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl LocalBucket {
+    // новая полная корзина
+    fn new(policy: &RatePolicy, now: Instant) -> Self {
+        LocalBucket {
+            policy: *policy,
+            tokens: policy.capacity as f64,
+            updated: now,
+        }
+    }
+
+    // пополнение корзины за прошедшее время
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.policy.refill_per_sec).min(self.policy.capacity as f64);
+        self.updated = now;
+    }
+
+    // списание одного токена, в случае превышения лимита - время до следующего токена
+    fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / self.policy.refill_per_sec,
+            ))
+        }
+    }
+
+    // полна ли корзина (такую можно удалить без изменения поведения)
+    fn is_full(&self, now: Instant) -> bool {
+        let mut bucket = *self;
+        bucket.refill(now);
+        bucket.tokens >= self.policy.capacity as f64
+    }
+}
+
+// корзины в памяти по ключу "маршрут:клиент"
+#[derive(Debug)]
+struct LocalBuckets {
+    buckets: HashMap<String, LocalBucket>,
+    next_cleanup: Instant,
+}
+
+impl LocalBuckets {
+    fn new(now: Instant) -> Self {
+        LocalBuckets {
+            buckets: HashMap::new(),
+            next_cleanup: now + LOCAL_BUCKETS_CLEANUP_INTERVAL,
+        }
+    }
+
+    // проверка лимита по корзине клиента на маршруте
+    fn check(
+        &mut self,
+        route: &str,
+        key: String,
+        policy: &RatePolicy,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        // удаление полностью пополненных корзин не чаще раза в интервал, чтобы не копить память
+        if now >= self.next_cleanup {
+            self.buckets.retain(|_, bucket| !bucket.is_full(now));
+            self.next_cleanup = now + LOCAL_BUCKETS_CLEANUP_INTERVAL;
+        }
+
+        // при переполнении новые клиенты ограничиваются общей корзиной маршрута
+        let key = if self.buckets.len() >= LOCAL_BUCKETS_LIMIT && !self.buckets.contains_key(&key) {
+            format!("rate_limit:{}:overflow", route)
+        } else {
+            key
+        };
+
+        self.buckets
+            .entry(key)
+            .or_insert_with(|| LocalBucket::new(policy, now))
+            .take(now)
+    }
+}
+
+// общий для всех маршрутов ограничитель частоты запросов
+pub struct RateLimiter {
+    redis_instance: RedisDB,
+    local_buckets: Mutex<LocalBuckets>,
+}
+
+impl RateLimiter {
+    // инициализация ограничителя поверх существующего пула redis
+    pub fn new(redis_instance: RedisDB) -> Self {
+        RateLimiter {
+            redis_instance,
+            local_buckets: Mutex::new(LocalBuckets::new(Instant::now())),
+        }
+    }
+
+    // проверка лимита для клиента на маршруте, Err - время до следующего разрешённого запроса
+    pub async fn check(
+        &self,
+        route: &str,
+        client: &str,
+        policy: &RatePolicy,
+    ) -> Result<(), Duration> {
+        let key = format!("rate_limit:{}:{}", route, client);
+
+        // запрос к базе данных redis с тайм-аутом
+        let redis_result = timeout(Duration::from_millis(200), async {
+            self.redis_instance
+                .take_rate_limit_token(&key, policy.capacity, policy.refill_per_sec)
+                .await
+        })
+        .await;
+
+        // при недоступности redis - ограничение в памяти текущего инстанса
+        match redis_result {
+            Ok(Ok(None)) => Ok(()),
+            Ok(Ok(Some(retry_after_ms))) => Err(Duration::from_millis(retry_after_ms)),
+            Ok(Err(err)) => {
+                warn!(
+                    "Ошибка redis при проверке лимита, ограничение в памяти: {:?}",
+                    err
+                );
+                self.check_local(route, key, policy)
+            }
+            Err(Elapsed { .. }) => {
+                warn!("Тайм-аут redis при проверке лимита, ограничение в памяти");
+                self.check_local(route, key, policy)
+            }
+        }
+    }
+
+    // проверка лимита по корзинам в памяти
+    fn check_local(&self, route: &str, key: String, policy: &RatePolicy) -> Result<(), Duration> {
+        self.local_buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .check(route, key, policy, Instant::now())
+    }
+}
+
+// состояние middleware одного маршрута
+#[derive(Clone)]
+pub struct RouteRateLimit {
+    limiter: Arc<RateLimiter>,
+    route: &'static str,
+    policy: RatePolicy,
+}
+
+impl RouteRateLimit {
+    pub fn new(limiter: Arc<RateLimiter>, route: &'static str, policy: RatePolicy) -> Self {
+        RouteRateLimit {
+            limiter,
+            route,
+            policy,
+        }
+    }
+}
+
+// идентификатор клиента по IP-адресу
+pub fn ip_client_id(ip: Option<IpAddr>) -> String {
+    match ip {
+        Some(ip) => format!("ip:{}", ip),
+        None => "ip:unknown".to_string(),
+    }
+}
+
+// идентификатор клиента по проверенному ключу
+pub fn key_client_id(key_id: &Uuid) -> String {
+    format!("key:{}", key_id)
+}
+
+// IP-адрес клиента запроса
+fn request_ip(request: &Request) -> Option<IpAddr> {
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+// проверка лимита клиента маршрута, при превышении - 429 с заголовком Retry-After
+async fn limit(route_limit: &RouteRateLimit, client: &str, request: Request, next: Next) -> Response {
+    match route_limit
+        .limiter
+        .check(route_limit.route, client, &route_limit.policy)
+        .await
+    {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => ServerError::TooManyRequests(retry_after).into_response(),
+    }
+}
+
+// middleware ограничения по IP, ставится перед проверкой ключа: запросы с неверными
+// или выдуманными ключами расходуют корзину IP и не доходят до Postgres сверх лимита
+pub async fn rate_limit_ip(
+    State(route_limit): State<RouteRateLimit>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client = ip_client_id(request_ip(&request));
+    limit(&route_limit, &client, request, next).await
+}
+
+// middleware ограничения по проверенному ключу, ставится после проверки ключа;
+// без проверенного ключа в запросе - по IP
+pub async fn rate_limit_key(
+    State(route_limit): State<RouteRateLimit>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client = match request.extensions().get::<ApiKey>() {
+        Some(api_key) => key_client_id(&api_key.key_id),
+        None => ip_client_id(request_ip(&request)),
+    };
+    limit(&route_limit, &client, request, next).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LocalBucket, LocalBuckets, LOCAL_BUCKETS_CLEANUP_INTERVAL, LOCAL_BUCKETS_LIMIT};
+    use crate::config::RatePolicy;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    // корзина пропускает всплеск размером с capacity, затем ограничивает
+    fn test_local_bucket_burst() {
+        let policy = RatePolicy {
+            capacity: 3,
+            refill_per_sec: 1.0,
+        };
+        let now = Instant::now();
+        let mut bucket = LocalBucket::new(&policy, now);
+
+        for _ in 0..3 {
+            assert!(bucket.take(now).is_ok());
+        }
+
+        let retry_after = bucket.take(now).unwrap_err();
+        assert_eq!(retry_after, Duration::from_secs(1));
+    }
+
+    #[test]
+    // корзина пополняется со временем, но не больше capacity
+    fn test_local_bucket_refill() {
+        let policy = RatePolicy {
+            capacity: 2,
+            refill_per_sec: 4.0,
+        };
+        let now = Instant::now();
+        let mut bucket = LocalBucket::new(&policy, now);
+
+        assert!(bucket.take(now).is_ok());
+        assert!(bucket.take(now).is_ok());
+        assert!(bucket.take(now).is_err());
+
+        let later = now + Duration::from_millis(250);
+        assert!(bucket.take(later).is_ok());
+        assert!(bucket.take(later).is_err());
+
+        let much_later = now + Duration::from_secs(60);
+        assert!(bucket.is_full(much_later));
+        assert!(bucket.take(much_later).is_ok());
+        assert!(bucket.take(much_later).is_ok());
+        assert!(bucket.take(much_later).is_err());
+    }
+
+    #[test]
+    // очистка учитывает политику каждой корзины, а не текущего запроса
+    fn test_local_buckets_cleanup() {
+        let slow = RatePolicy {
+            capacity: 2,
+            refill_per_sec: 0.01,
+        };
+        let fast = RatePolicy {
+            capacity: 100,
+            refill_per_sec: 100.0,
+        };
+        let now = Instant::now();
+        let mut buckets = LocalBuckets::new(now);
+
+        assert!(buckets
+            .check("slow", "slow:a".to_string(), &slow, now)
+            .is_ok());
+        assert!(buckets
+            .check("slow", "slow:a".to_string(), &slow, now)
+            .is_ok());
+        assert!(buckets
+            .check("fast", "fast:a".to_string(), &fast, now)
+            .is_ok());
+
+        // быстрая корзина пополнилась и удалена, медленная - осталась пустой
+        let later = now + LOCAL_BUCKETS_CLEANUP_INTERVAL;
+        assert!(buckets
+            .check("fast", "fast:b".to_string(), &fast, later)
+            .is_ok());
+        assert!(!buckets.buckets.contains_key("fast:a"));
+        assert!(buckets
+            .check("slow", "slow:a".to_string(), &slow, later)
+            .is_err());
+    }
+
+    #[test]
+    // сверх предела новые клиенты делят одну корзину маршрута
+    fn test_local_buckets_limit() {
+        let policy = RatePolicy {
+            capacity: 1,
+            refill_per_sec: 0.01,
+        };
+        let now = Instant::now();
+        let mut buckets = LocalBuckets::new(now);
+
+        for client in 0..LOCAL_BUCKETS_LIMIT {
+            assert!(buckets
+                .check("route", client.to_string(), &policy, now)
+                .is_ok());
+        }
+        assert!(buckets
+            .check("route", "new".to_string(), &policy, now)
+            .is_ok());
+        assert!(buckets
+            .check("route", "other".to_string(), &policy, now)
+            .is_err());
+        assert_eq!(buckets.buckets.len(), LOCAL_BUCKETS_LIMIT + 1);
+    }
+
+    #[test]
+    // парсинг политики из переменной окружения
+    fn test_rate_policy_parse() {
+        assert_eq!(
+            RatePolicy::parse("100/10"),
+            Some(RatePolicy {
+                capacity: 100,
+                refill_per_sec: 10.0
+            })
+        );
+        assert_eq!(
+            RatePolicy::parse(" 5 / 0.5 "),
+            Some(RatePolicy {
+                capacity: 5,
+                refill_per_sec: 0.5
+            })
+        );
+        assert_eq!(RatePolicy::parse("0/10"), None);
+        assert_eq!(RatePolicy::parse("10/0"), None);
+        assert_eq!(RatePolicy::parse("10"), None);
+    }
+}
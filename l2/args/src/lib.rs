@@ -0,0 +1,321 @@
+//! разбор аргументов командной строки как в GNU getopt_long, общий для утилит l2:
+//! короткие флаги объединяются ("-nru"), значение короткого - слитно или следующим аргументом,
+//! длинные - "--имя=значение" или следующим аргументом и сокращаются до однозначного начала,
+//! "--" завершает параметры, "-" - операнд. Ошибки - варианты Error: по-русски их выводит
+//! Display, утилиты с сообщениями как у GNU формируют текст сами.
+//! Здесь же текст ошибок ввода-вывода в том виде, в каком их печатает coreutils
+use std::fmt;
+use std::io;
+
+// значение параметра
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    None,
+    Required,
+    // только у длинного и только через '=', короткий - без значения
+    Optional,
+}
+
+// описание параметра: короткое имя, длинное или оба
+#[derive(Debug, Clone, Copy)]
+pub struct Opt {
+    short: Option<char>,
+    long: Option<&'static str>,
+    value: Value,
+}
+
+impl Opt {
+    pub const fn short(short: char) -> Self {
+        Opt {
+            short: Some(short),
+            long: None,
+            value: Value::None,
+        }
+    }
+
+    pub const fn long(long: &'static str) -> Self {
+        Opt {
+            short: None,
+            long: Some(long),
+            value: Value::None,
+        }
+    }
+
+    pub const fn both(short: char, long: &'static str) -> Self {
+        Opt {
+            short: Some(short),
+            long: Some(long),
+            value: Value::None,
+        }
+    }
+
+    // параметр с обязательным значением
+    pub const fn value(mut self) -> Self {
+        self.value = Value::Required;
+        self
+    }
+
+    // длинный параметр с необязательным значением ("--color", "--color=always")
+    pub const fn optional_value(mut self) -> Self {
+        self.value = Value::Optional;
+        self
+    }
+}
+
+// разобранный аргумент
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Arg {
+    // параметр с коротким именем, в том числе заданный длинным
+    Short(char, Option<String>),
+    // параметр только с длинным именем
+    Long(&'static str, Option<String>),
+    Operand(String),
+}
+
+// ошибка разбора
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    // неизвестный короткий флаг
+    UnknownShort(char),
+    // у короткого флага нет значения
+    MissingShortValue(char),
+    // неизвестный длинный параметр, аргумент целиком
+    UnknownLong(String),
+    // начало имени подходит к нескольким параметрам, аргумент целиком
+    AmbiguousLong(String),
+    // значение у параметра без значения, имя параметра
+    UnexpectedValue(&'static str),
+    // у длинного параметра нет значения, имя параметра
+    MissingLongValue(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownShort(flag) => write!(f, "неверный параметр -- '{}'", flag),
+            Error::MissingShortValue(flag) => write!(f, "параметру -{} нужно значение", flag),
+            Error::UnknownLong(arg) => write!(f, "неизвестный параметр '{}'", arg),
+            Error::AmbiguousLong(arg) => write!(f, "неоднозначный параметр '{}'", arg),
+            Error::UnexpectedValue(name) => {
+                write!(f, "параметр '--{}' не принимает значения", name)
+            }
+            Error::MissingLongValue(name) => write!(f, "параметру '--{}' нужно значение", name),
+        }
+    }
+}
+
+// для разбора в функциях, возвращающих текст ошибки
+impl From<Error> for String {
+    fn from(err: Error) -> Self {
+        err.to_string()
+    }
+}
+
+// разбор аргументов по описаниям параметров
+pub struct Parser<'a> {
+    options: &'a [Opt],
+    args: std::slice::Iter<'a, String>,
+    // ещё не разобранные короткие флаги текущего аргумента ("-nru")
+    shorts: &'a str,
+    only_operands: bool,
+}
+
+impl<'a> Parser<'a> {
+    // args - без имени программы
+    pub fn new(options: &'a [Opt], args: &'a [String]) -> Self {
+        Parser {
+            options,
+            args: args.iter(),
+            shorts: "",
+            only_operands: false,
+        }
+    }
+
+    fn parse_next(&mut self) -> Result<Option<Arg>, Error> {
+        if let Some(flag) = self.shorts.chars().next() {
+            return self.short(flag).map(Some);
+        }
+
+        let Some(arg) = self.args.next() else {
+            return Ok(None);
+        };
+        if self.only_operands || arg == "-" || !arg.starts_with('-') {
+            Ok(Some(Arg::Operand(arg.clone())))
+        } else if arg == "--" {
+            self.only_operands = true;
+            self.parse_next()
+        } else if let Some(long) = arg.strip_prefix("--") {
+            self.long(arg, long).map(Some)
+        } else {
+            self.shorts = &arg[1..];
+            self.parse_next()
+        }
+    }
+
+    // первый флаг из shorts; флаг со значением забирает остаток аргумента
+    fn short(&mut self, flag: char) -> Result<Arg, Error> {
+        let rest = &self.shorts[flag.len_utf8()..];
+        self.shorts = "";
+        let option = self
+            .options
+            .iter()
+            .find(|option| option.short == Some(flag))
+            .ok_or(Error::UnknownShort(flag))?;
+
+        let value = if option.value == Value::Required {
+            Some(match rest {
+                "" => self
+                    .args
+                    .next()
+                    .ok_or(Error::MissingShortValue(flag))?
+                    .clone(),
+                rest => rest.to_string(),
+            })
+        } else {
+            self.shorts = rest;
+            None
+        };
+        Ok(Arg::Short(flag, value))
+    }
+
+    // длинный параметр: точное совпадение имени или однозначное начало
+    fn long(&mut self, arg: &str, long: &str) -> Result<Arg, Error> {
+        let (long, value) = match long.split_once('=') {
+            Some((long, value)) => (long, Some(value.to_string())),
+            None => (long, None),
+        };
+        let named = || {
+            self.options
+                .iter()
+                .filter_map(|option| Some((option.long?, option)))
+        };
+        let matched: Vec<(&str, &Opt)> = match named().find(|&(name, _)| name == long) {
+            Some(option) => vec![option],
+            None => named().filter(|(name, _)| name.starts_with(long)).collect(),
+        };
+        let (name, option) = match matched.as_slice() {
+            [option] => *option,
+            [] => return Err(Error::UnknownLong(arg.to_string())),
+            _ => return Err(Error::AmbiguousLong(arg.to_string())),
+        };
+
+        let value = match (option.value, value) {
+            (Value::None, Some(_)) => return Err(Error::UnexpectedValue(name)),
+            (Value::Required, None) => Some(
+                self.args
+                    .next()
+                    .ok_or(Error::MissingLongValue(name))?
+                    .clone(),
+            ),
+            (_, value) => value,
+        };
+        Ok(match option.short {
+            Some(flag) => Arg::Short(flag, value),
+            None => Arg::Long(name, value),
+        })
+    }
+}
+
+impl Iterator for Parser<'_> {
+    type Item = Result<Arg, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.parse_next().transpose()
+    }
+}
+
+// текст ошибки ввода-вывода без кода ошибки ОС: "No such file or directory"
+// вместо "No such file or directory (os error 2)"
+pub fn io_error(err: &io::Error) -> String {
+    let message = err.to_string();
+    match message.find(" (os error") {
+        Some(end) => message[..end].to_string(),
+        None => message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{io_error, Arg, Error, Opt, Parser};
+    use std::io;
+
+    const OPTIONS: [Opt; 6] = [
+        Opt::both('n', "numeric"),
+        Opt::both('r', "reverse"),
+        Opt::both('k', "key").value(),
+        Opt::short('C'),
+        Opt::long("no-ignore"),
+        Opt::long("color").optional_value(),
+    ];
+
+    fn parse(args: &str) -> Result<Vec<Arg>, Error> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        Parser::new(&OPTIONS, &args).collect()
+    }
+
+    fn short(flag: char, value: Option<&str>) -> Arg {
+        Arg::Short(flag, value.map(String::from))
+    }
+
+    #[test]
+    // объединённые флаги, значение слитно и отдельно, "-" и "--"
+    fn test_short() {
+        assert_eq!(
+            parse("-nr -k2 -Crk 3 - -- -n").unwrap(),
+            [
+                short('n', None),
+                short('r', None),
+                short('k', Some("2")),
+                short('C', None),
+                short('r', None),
+                short('k', Some("3")),
+                Arg::Operand("-".to_string()),
+                Arg::Operand("-n".to_string()),
+            ]
+        );
+        assert_eq!(parse("-kn").unwrap(), [short('k', Some("n"))]);
+    }
+
+    #[test]
+    // длинные с коротким именем возвращаются как короткие, сокращения, необязательное значение
+    fn test_long() {
+        assert_eq!(
+            parse("--num --key=1 --ke 2 --no --col --color=never a").unwrap(),
+            [
+                short('n', None),
+                short('k', Some("1")),
+                short('k', Some("2")),
+                Arg::Long("no-ignore", None),
+                Arg::Long("color", None),
+                Arg::Long("color", Some("never".to_string())),
+                Arg::Operand("a".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        for (invalid, error) in [
+            ("-x", Error::UnknownShort('x')),
+            ("-nk", Error::MissingShortValue('k')),
+            ("--key", Error::MissingLongValue("key")),
+            ("--re=1", Error::UnexpectedValue("reverse")),
+            ("--foo=1", Error::UnknownLong("--foo=1".to_string())),
+            ("--n", Error::AmbiguousLong("--n".to_string())),
+        ] {
+            assert_eq!(parse(invalid), Err(error), "{:?}", invalid);
+        }
+        assert_eq!(
+            String::from(Error::AmbiguousLong("--n".to_string())),
+            "неоднозначный параметр '--n'"
+        );
+    }
+
+    #[test]
+    fn test_io_error() {
+        let err = io::Error::from_raw_os_error(2);
+        assert!(err.to_string().ends_with("(os error 2)"));
+        assert_eq!(io_error(&err), "No such file or directory");
+        assert_eq!(io_error(&io::Error::other("ошибка")), "ошибка");
+    }
+}
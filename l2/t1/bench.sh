@@ -0,0 +1,74 @@
+#!/usr/bin/env bash
+# бенчмарк wc: GNU wc, исходная версия из базового коммита, потоковый режим (--threads=1)
+# и параллельный режим
+#   ./bench.sh [размер файла в МиБ, по умолчанию 512]
+#   BASE_REV=<коммит> ./bench.sh    # исходная версия из другого коммита
+set -euo pipefail
+
+SIZE_MIB=${1:-512}
+RUNS=${RUNS:-3}
+DATA=${DATA:-$(mktemp -d)/bench.txt}
+cd "$(dirname "$0")"
+
+cargo build --release --quiet
+BIN=$PWD/target/release/t1
+
+# исходная версия: t1 из первого коммита репозитория, собранная отдельно
+BASE_REV=${BASE_REV:-$(git rev-list --max-parents=0 HEAD | tail -1)}
+BASE_DIR=$(mktemp -d)
+git -C "$(git rev-parse --show-toplevel)" archive "$BASE_REV:$(git rev-parse --show-prefix)" \
+    | tar -x -C "$BASE_DIR"
+cargo build --release --quiet --manifest-path "$BASE_DIR/Cargo.toml"
+BASE_BIN=$BASE_DIR/target/release/t1
+
+# смешанный текст: латиница, кириллица, CJK, табуляции и длинные строки
+if [ ! -f "$DATA" ]; then
+    python3 - "$DATA" "$SIZE_MIB" <<'PY'
+import random, sys
+random.seed(42)
+words = ["log", "INFO", "запрос", "ответ", "2024-01-01T00:00:00Z", "你好", "naïve", "\t", "id=42", "ёлка"]
+line = lambda: " ".join(random.choice(words) for _ in range(random.randint(1, 40))) + "\n"
+block = "".join(line() for _ in range(20000)).encode()
+with open(sys.argv[1], "wb") as out:
+    for _ in range(int(sys.argv[2]) * 1024 * 1024 // len(block) + 1):
+        out.write(block)
+PY
+fi
+
+# лучшее время из RUNS запусков в секундах
+best_time() {
+    local best=""
+    for _ in $(seq "$RUNS"); do
+        local start end
+        start=$(date +%s.%N)
+        "$@" > /dev/null
+        end=$(date +%s.%N)
+        best=$(echo "$start $end ${best:-inf}" | awk '{t = $2 - $1; if ($3 == "inf" || t < $3) printf "%.3f", t; else print $3}')
+    done
+    echo "$best"
+}
+
+echo "файл: $DATA ($(du -h "$DATA" | cut -f1)), ядер: $(nproc), лучший из $RUNS запусков"
+# время в первых колонках, чтобы кириллица в названиях не сбивала выравнивание
+row() {
+    printf "%10s %10s %10s  %s\n" "$@"
+}
+# вариант: название и команда; колонки - без флагов, -w и -l
+bench() {
+    local name=$1
+    shift
+    row "$(best_time "$@" "$DATA")" "$(best_time "$@" -w "$DATA")" "$(best_time "$@" -l "$DATA")" "$name"
+}
+
+row "wc" "wc -w" "wc -l" "вариант"
+bench "GNU wc (C.UTF-8)" env LC_ALL=C.UTF-8 wc
+# исходная версия не знает -w: без флагов она и считает только слова
+row "-" "$(best_time "$BASE_BIN" "$DATA")" "$(best_time "$BASE_BIN" -l "$DATA")" \
+    "исходная версия ($(git rev-parse --short "$BASE_REV"))"
+bench "t1 --threads=1" "$BIN" --threads=1
+bench "t1 (потоков: $(nproc))" "$BIN"
+bench "t1 --threads=4" "$BIN" --threads=4
+
+# результаты всех вариантов должны совпадать
+diff <(LC_ALL=C.UTF-8 wc -lwmcL "$DATA") <("$BIN" --threads=4 -lwmcL "$DATA") > /dev/null \
+    && echo "результаты совпадают с GNU wc" || echo "РЕЗУЛЬТАТЫ ОТЛИЧАЮТСЯ ОТ GNU wc"
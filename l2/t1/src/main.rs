@@ -1,73 +1,821 @@
+//! wc: потоковый подсчёт строк, слов, символов и байт в файлах или stdin с выводом в формате GNU wc
+mod parallel;
+
+use args::{Arg, Opt, Parser};
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use std::env;
-use std::fs::read_to_string;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufWriter, ErrorKind, Read, Write};
+use std::process::exit;
+use std::str;
+use unicode_properties::{GeneralCategory, UnicodeGeneralCategory};
+use unicode_width::UnicodeWidthChar;
+
+// имя программы в сообщениях об ошибках, как у GNU wc
+const PROGRAM: &str = "wc";
+// размер блока чтения
+const BUFFER_SIZE: usize = 256 * 1024;
+
+const USAGE: &str = "Использование: wc [ПАРАМЕТР]... [ФАЙЛ]...
+Печатает количество строк, слов и байт для каждого ФАЙЛА и итоговую строку,
+если файлов больше одного. Без ФАЙЛА или если ФАЙЛ это -, читается stdin.
+
+  -c, --bytes            количество байт
+  -m, --chars            количество символов UTF-8
+  -l, --lines            количество переводов строки
+  -w, --words            количество слов (разделены пробельными символами Unicode)
+  -L, --max-line-length  ширина самой длинной строки
+      --help             показать эту справку и выйти
+
+По умолчанию: -l -w -c. Порядок вывода всегда: строки, слова, символы, байты, ширина строки.";
+
+// какие счётчики выводить и сколько потоков использовать
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Options {
+    lines: bool,
+    words: bool,
+    chars: bool,
+    bytes: bool,
+    max_line_length: bool,
+    // 0 - по числу ядер
+    threads: usize,
+}
+
+impl Options {
+    // количество выводимых счётчиков
+    fn enabled(&self) -> usize {
+        [
+            self.lines,
+            self.words,
+            self.chars,
+            self.bytes,
+            self.max_line_length,
+        ]
+        .iter()
+        .filter(|enabled| **enabled)
+        .count()
+    }
+}
+
+// результаты подсчёта одного входа
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Counts {
+    lines: u64,
+    words: u64,
+    chars: u64,
+    bytes: u64,
+    max_line_length: u64,
+}
+
+impl Counts {
+    // добавление результатов другого входа к итогу
+    fn add(&mut self, other: &Counts) {
+        self.lines += other.lines;
+        self.words += other.words;
+        self.chars += other.chars;
+        self.bytes += other.bytes;
+        self.max_line_length = self.max_line_length.max(other.max_line_length);
+    }
+}
+
+// разделитель слов как в GNU wc: пробельные символы Unicode, включая неразрывные пробелы и U+2060,
+// кроме U+0085, U+2028 и U+2029, которые glibc пробелами не считает
+fn is_word_separator(c: char) -> bool {
+    (c.is_whitespace() && !matches!(c, '\u{85}' | '\u{2028}' | '\u{2029}')) || c == '\u{2060}'
+}
+
+// печатаемый символ (аналог iswprint): только такие символы начинают слово
+fn is_printable(c: char) -> bool {
+    if c.is_ascii() {
+        return !c.is_ascii_control();
+    }
+
+    !matches!(
+        c.general_category(),
+        GeneralCategory::Control | GeneralCategory::Unassigned | GeneralCategory::Surrogate
+    )
+}
+
+// ширина печатаемого символа на экране
+fn char_width(c: char) -> u64 {
+    c.width().unwrap_or(0) as u64
+}
+
+// последовательность за пределами U+10FFFF, которую glibc (и GNU wc) декодирует как символ
+#[derive(Debug, PartialEq)]
+enum Extended {
+    Char(usize),
+    Incomplete,
+    Invalid,
+}
+
+// разбор 4-6 байтовой последовательности старого UTF-8 со значением больше U+10FFFF
+fn extended_char(bytes: &[u8]) -> Extended {
+    let (len, min_value) = match bytes.first() {
+        Some(0xF4..=0xF7) => (4, 0x11_0000),
+        Some(0xF8..=0xFB) => (5, 0x20_0000),
+        Some(0xFC..=0xFD) => (6, 0x400_0000),
+        _ => return Extended::Invalid,
+    };
+
+    let mut value = (bytes[0] & (0x7F >> len)) as u32;
+    for index in 1..len {
+        match bytes.get(index) {
+            Some(byte) if byte & 0xC0 == 0x80 => value = value << 6 | (byte & 0x3F) as u32,
+            Some(_) => return Extended::Invalid,
+            None => return Extended::Incomplete,
+        }
+    }
+
+    if value >= min_value {
+        Extended::Char(len)
+    } else {
+        Extended::Invalid
+    }
+}
+
+// следующая позиция табуляции
+fn next_tab_stop(position: u64) -> u64 {
+    position + 8 - position % 8
+}
+
+// ширина куска строки как функция от начальной позиции: табуляция выравнивает позицию
+// на кратную 8, поэтому после первой табуляции остаток от начальной позиции не зависит
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct LineWidth {
+    // ширина до первой табуляции
+    lead: u64,
+    // ширина после первой табуляции, отсчитанная от её позиции
+    after_tab: Option<u64>,
+}
 
-// записывает строки из файла в вектор
-fn read_lines_from_file(filename: &str) -> Vec<String> {
-    let mut file_lines = Vec::new();
-    for line in read_to_string(filename).unwrap().lines() {
-        file_lines.push(line.to_string())
+impl LineWidth {
+    // символ шириной width
+    fn advance(&mut self, width: u64) {
+        match &mut self.after_tab {
+            Some(after_tab) => *after_tab += width,
+            None => self.lead += width,
+        }
+    }
+
+    // табуляция
+    fn tab(&mut self) {
+        match &mut self.after_tab {
+            Some(after_tab) => *after_tab = next_tab_stop(*after_tab),
+            None => self.after_tab = Some(0),
+        }
+    }
+
+    // позиция в конце куска при известной начальной позиции
+    fn apply(&self, start: u64) -> u64 {
+        match self.after_tab {
+            Some(after_tab) => next_tab_stop(start + self.lead) + after_tab,
+            None => start + self.lead,
+        }
+    }
+
+    // кусок строки, за которым следует next
+    fn then(&self, next: &LineWidth) -> LineWidth {
+        match self.after_tab {
+            Some(after_tab) => LineWidth {
+                lead: self.lead,
+                after_tab: Some(next.apply(after_tab)),
+            },
+            None => LineWidth {
+                lead: self.lead + next.lead,
+                after_tab: next.after_tab,
+            },
+        }
     }
-    file_lines
 }
 
-// считает количество строк
-fn count_lines(vector_of_lines: &Vec<String>) -> usize {
-    vector_of_lines.len()
+// результат подсчёта куска входа, который можно склеить с соседним куском:
+// слова и ширина строк на стыке исправляются при склейке
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct ChunkCounts {
+    // ширина - только по строкам целиком внутри куска,
+    // слова - как будто перед куском стоял пробел
+    counts: Counts,
+    // ширина текста до первого конца строки (или всего куска)
+    first_line: LineWidth,
+    // есть ли в куске конец строки
+    has_line_end: bool,
+    // ширина текста после последнего конца строки
+    last_line: LineWidth,
+    // первое слово куска началось до первого разделителя и может продолжать слово прошлого куска
+    starts_in_word: bool,
+    // внутри слова ли конец куска, None - кусок не содержит ни разделителей, ни начала слова
+    ends_in_word: Option<bool>,
 }
 
-// считает количество слов
-fn count_words(vector_of_lines: &Vec<String>) -> usize {
-    let mut counter: usize = 0;
-    for line in vector_of_lines {
-        let word_vec: Vec<&str> = line.split(' ').collect();
-        counter += word_vec.len();
+impl ChunkCounts {
+    // склейка с куском, идущим следом
+    fn merge(self, next: ChunkCounts) -> ChunkCounts {
+        let mut counts = Counts {
+            lines: self.counts.lines + next.counts.lines,
+            words: self.counts.words + next.counts.words,
+            chars: self.counts.chars + next.counts.chars,
+            bytes: self.counts.bytes + next.counts.bytes,
+            max_line_length: self.counts.max_line_length.max(next.counts.max_line_length),
+        };
+
+        // слово, разрезанное границей кусков, посчитано дважды
+        if self.ends_in_word == Some(true) && next.starts_in_word {
+            counts.words -= 1;
+        }
+
+        let (first_line, last_line) = match (self.has_line_end, next.has_line_end) {
+            (false, _) => (self.first_line.then(&next.first_line), next.last_line),
+            (true, false) => (self.first_line, self.last_line.then(&next.first_line)),
+            (true, true) => {
+                // строка на стыке кусков теперь целиком внутри
+                let joined = self.last_line.then(&next.first_line).apply(0);
+                counts.max_line_length = counts.max_line_length.max(joined);
+                (self.first_line, next.last_line)
+            }
+        };
+
+        ChunkCounts {
+            counts,
+            first_line,
+            has_line_end: self.has_line_end || next.has_line_end,
+            last_line,
+            starts_in_word: match self.ends_in_word {
+                Some(_) => self.starts_in_word,
+                None => next.starts_in_word,
+            },
+            ends_in_word: next.ends_in_word.or(self.ends_in_word),
+        }
+    }
+
+    // итог, если кусок - весь вход
+    fn finish(self) -> Counts {
+        let mut counts = self.counts;
+        counts.max_line_length = counts.max_line_length.max(self.first_line.apply(0));
+        if self.has_line_end {
+            counts.max_line_length = counts.max_line_length.max(self.last_line.apply(0));
+        }
+        counts
     }
-    counter
 }
 
-// считает количество символов включая пробелы
-fn count_symbols(vector_of_lines: &Vec<String>) -> usize {
-    let mut counter: usize = 0;
-    for line in vector_of_lines {
-        counter += line.len();
+// счётчик куска входа
+#[derive(Debug, Default)]
+struct Counter {
+    // нужны ли слова, символы или ширина (иначе считаются только переводы строк)
+    decode: bool,
+    counts: Counts,
+    in_word: bool,
+    // встречался ли разделитель или начало слова
+    word_state_known: bool,
+    starts_in_word: bool,
+    first_line: Option<LineWidth>,
+    line: LineWidth,
+}
+
+impl Counter {
+    fn new(options: &Options) -> Self {
+        Counter {
+            decode: options.words || options.chars || options.max_line_length,
+            ..Counter::default()
+        }
+    }
+
+    // учёт одного символа
+    fn push_char(&mut self, c: char) {
+        self.counts.chars += 1;
+        let printable = is_printable(c);
+
+        match c {
+            '\n' => {
+                self.counts.lines += 1;
+                self.end_line();
+            }
+            '\r' | '\x0c' => self.end_line(),
+            '\t' => self.line.tab(),
+            // непечатаемые символы ширины не имеют
+            _ if printable => self.line.advance(char_width(c)),
+            _ => {}
+        }
+
+        // непечатаемые символы не разделяют слова и не начинают новое
+        if is_word_separator(c) {
+            self.in_word = false;
+            self.word_state_known = true;
+        } else if !self.in_word && printable {
+            self.starts_in_word |= !self.word_state_known;
+            self.in_word = true;
+            self.word_state_known = true;
+            self.counts.words += 1;
+        }
+    }
+
+    // учёт символа ASCII, то же что push_char без обращения к таблицам Unicode
+    fn push_ascii(&mut self, byte: u8) {
+        self.counts.chars += 1;
+
+        match byte {
+            b'\n' => {
+                self.counts.lines += 1;
+                self.end_line();
+            }
+            b'\r' | b'\x0c' => self.end_line(),
+            b'\t' => self.line.tab(),
+            b' '..=b'~' => self.line.advance(1),
+            _ => {}
+        }
+
+        match byte {
+            b'\t'..=b'\r' | b' ' => {
+                self.in_word = false;
+                self.word_state_known = true;
+            }
+            b'!'..=b'~' if !self.in_word => {
+                self.starts_in_word |= !self.word_state_known;
+                self.in_word = true;
+                self.word_state_known = true;
+                self.counts.words += 1;
+            }
+            _ => {}
+        }
+    }
+
+    // учёт корректного текста, ASCII без декодирования
+    fn push_str(&mut self, text: &str) {
+        let bytes = text.as_bytes();
+        let mut index = 0;
+
+        while index < bytes.len() {
+            if bytes[index].is_ascii() {
+                self.push_ascii(bytes[index]);
+                index += 1;
+            } else if let Some(c) = text[index..].chars().next() {
+                self.push_char(c);
+                index += c.len_utf8();
+            }
+        }
+    }
+
+    // конец строки для подсчёта максимальной ширины, первая строка куска может продолжать прошлый кусок
+    fn end_line(&mut self) {
+        match self.first_line {
+            Some(_) => {
+                self.counts.max_line_length = self.counts.max_line_length.max(self.line.apply(0))
+            }
+            None => self.first_line = Some(self.line),
+        }
+        self.line = LineWidth::default();
+    }
+
+    // учёт блока байт (без счётчика байт), возвращает длину неполного символа в конце блока
+    fn update(&mut self, buf: &[u8]) -> usize {
+        if !self.decode {
+            self.counts.lines += memchr::memchr_iter(b'\n', buf).count() as u64;
+            return 0;
+        }
+
+        let mut rest = buf;
+
+        loop {
+            match str::from_utf8(rest) {
+                Ok(text) => {
+                    self.push_str(text);
+                    return 0;
+                }
+                Err(err) => {
+                    let (valid, invalid) = rest.split_at(err.valid_up_to());
+                    if let Ok(text) = str::from_utf8(valid) {
+                        self.push_str(text);
+                    }
+
+                    match (extended_char(invalid), err.error_len()) {
+                        // символ вне Unicode - непечатаемый, на слова и ширину не влияет
+                        (Extended::Char(len), _) => {
+                            self.counts.chars += 1;
+                            rest = &invalid[len..];
+                        }
+                        // байты вне корректного UTF-8 не символы и не влияют на слова и ширину
+                        (Extended::Invalid, Some(len)) => rest = &invalid[len..],
+                        // символ может продолжиться в следующем блоке
+                        (Extended::Incomplete, _) | (Extended::Invalid, None) => {
+                            return invalid.len()
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // результат куска для склейки с соседними
+    fn into_chunk(self) -> ChunkCounts {
+        ChunkCounts {
+            counts: self.counts,
+            first_line: self.first_line.unwrap_or(self.line),
+            has_line_end: self.first_line.is_some(),
+            last_line: self.line,
+            starts_in_word: self.starts_in_word,
+            ends_in_word: self.word_state_known.then_some(self.in_word),
+        }
     }
-    counter
+}
+
+// потоковый подсчёт по блокам, при ошибке чтения возвращает посчитанное до неё
+fn count_reader(reader: &mut impl Read, options: &Options) -> (Counts, Option<io::Error>) {
+    let mut counter = Counter::new(options);
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    // неполный символ с конца прошлого блока лежит в начале буфера
+    let mut pending = 0;
+    let mut error = None;
+
+    loop {
+        let read = match reader.read(&mut buf[pending..]) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => {
+                error = Some(err);
+                break;
+            }
+        };
+        counter.counts.bytes += read as u64;
+
+        let end = pending + read;
+        let tail = counter.update(&buf[..end]);
+        buf.copy_within(end - tail..end, 0);
+        pending = tail;
+    }
+
+    // оборванный в конце входа символ - некорректные байты, они ни на что кроме байт не влияют
+    (counter.into_chunk().finish(), error)
+}
+
+// параметры; --threads - только длинный
+const OPTIONS: [Opt; 7] = [
+    Opt::both('c', "bytes"),
+    Opt::both('m', "chars"),
+    Opt::both('l', "lines"),
+    Opt::both('w', "words"),
+    Opt::both('L', "max-line-length"),
+    Opt::long("threads").value(),
+    Opt::long("help"),
+];
+
+// сообщение об ошибке разбора в формате GNU getopt
+fn usage_error(err: args::Error) -> String {
+    match err {
+        args::Error::UnknownShort(flag) => format!("invalid option -- '{}'", flag),
+        args::Error::MissingShortValue(flag) => {
+            format!("option requires an argument -- '{}'", flag)
+        }
+        args::Error::UnknownLong(arg) => format!("unrecognized option '{}'", arg),
+        args::Error::AmbiguousLong(arg) => format!("option '{}' is ambiguous", arg),
+        args::Error::UnexpectedValue(name) => {
+            format!("option '--{}' doesn't allow an argument", name)
+        }
+        args::Error::MissingLongValue(name) => format!("option '--{}' requires an argument", name),
+    }
+}
+
+// разбор аргументов: флаги можно объединять (-lw), длинные флаги можно сокращать (--line)
+fn parse_args(args: &[String]) -> Result<(Options, Vec<String>), String> {
+    let mut options = Options::default();
+    let mut files = Vec::new();
+
+    for arg in Parser::new(&OPTIONS, args) {
+        match arg.map_err(usage_error)? {
+            Arg::Short('c', _) => options.bytes = true,
+            Arg::Short('m', _) => options.chars = true,
+            Arg::Short('l', _) => options.lines = true,
+            Arg::Short('w', _) => options.words = true,
+            Arg::Short('L', _) => options.max_line_length = true,
+            Arg::Long("threads", value) => {
+                let value = value.unwrap_or_default();
+                options.threads = match value.parse::<usize>() {
+                    Ok(threads) if threads > 0 => threads,
+                    _ => return Err(format!("invalid number of threads: '{}'", value)),
+                };
+            }
+            Arg::Long("help", _) => {
+                println!("{}", USAGE);
+                exit(0);
+            }
+            Arg::Short(..) | Arg::Long(..) => {}
+            Arg::Operand(file) => files.push(file),
+        }
+    }
+
+    // по умолчанию строки, слова и байты
+    if options.enabled() == 0 {
+        options.lines = true;
+        options.words = true;
+        options.bytes = true;
+    }
+
+    Ok((options, files))
+}
+
+// сведения о файле stdin для расчёта ширины колонок
+#[cfg(unix)]
+fn stdin_metadata() -> io::Result<Metadata> {
+    use std::os::fd::AsFd;
+
+    File::from(io::stdin().as_fd().try_clone_to_owned()?).metadata()
+}
+
+#[cfg(not(unix))]
+fn stdin_metadata() -> io::Result<Metadata> {
+    Err(io::Error::from(ErrorKind::Unsupported))
+}
+
+// ширина колонки как в GNU wc: по суммарному размеру обычных файлов,
+// не меньше 7 если среди входов есть не обычные файлы, 1 для одного счётчика одного входа
+fn column_width(options: &Options, inputs: &[Option<&str>]) -> usize {
+    if inputs.len() == 1 && options.enabled() == 1 {
+        return 1;
+    }
+
+    // недоступные входы в ширине не учитываются
+    let metadata = inputs.iter().filter_map(|input| {
+        match input {
+            Some(path) if *path != "-" => fs::metadata(path),
+            _ => stdin_metadata(),
+        }
+        .ok()
+    });
+
+    let mut minimum_width = 1;
+    let mut regular_total: u64 = 0;
+    for metadata in metadata {
+        if metadata.is_file() {
+            regular_total += metadata.len();
+        } else {
+            minimum_width = 7;
+        }
+    }
+
+    regular_total.to_string().len().max(minimum_width)
+}
+
+// строка вывода: выбранные счётчики в порядке GNU wc и имя входа
+fn format_counts(counts: &Counts, options: &Options, width: usize, name: Option<&str>) -> String {
+    let fields = [
+        (options.lines, counts.lines),
+        (options.words, counts.words),
+        (options.chars, counts.chars),
+        (options.bytes, counts.bytes),
+        (options.max_line_length, counts.max_line_length),
+    ];
+
+    let mut line = fields
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, value)| format!("{:>width$}", value, width = width))
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    if let Some(name) = name {
+        line.push(' ');
+        line.push_str(name);
+    }
+
+    line
+}
+
+// подсчёт одного входа: файла или stdin (None или "-"),
+// большие обычные файлы считаются параллельно, остальные - потоково
+fn count_input(
+    input: Option<&str>,
+    options: &Options,
+    pool: Option<&ThreadPool>,
+) -> io::Result<(Counts, Option<io::Error>)> {
+    let path = match input {
+        Some(path) if path != "-" => path,
+        _ => return Ok(count_reader(&mut io::stdin().lock(), options)),
+    };
+
+    let mut file = File::open(path)?;
+    if let Some(pool) = pool {
+        let metadata = file.metadata()?;
+        if metadata.is_file() && metadata.len() >= parallel::PARALLEL_MIN_SIZE {
+            // если файл нельзя отобразить в память, он читается потоково
+            if let Ok(counts) = parallel::count_file(&file, options, pool) {
+                return Ok((counts, None));
+            }
+        }
+    }
+
+    Ok(count_reader(&mut file, options))
 }
 
 fn main() {
     // читает аргументы из командной строки
-    let args: Vec<String> = env::args().collect();
-
-    match args.len() {
-        // если аргумента 2 - читает строки и выводит количество слов
-        2 => {
-            let file_lines = read_lines_from_file(&args[1]);
-            println!("{}", count_words(&file_lines));
-        },
-        // если аргумента 3 - проверяет аргументы и выполняет нужную команду
-        3 => {
-            let file_lines = read_lines_from_file(&args[2]);
-            match args[1].as_str() {
-                // выводит количество символов, включая whitespace (пробел)
-                "-c" => {
-                    println!("{}", count_symbols(&file_lines))
-                },
-                // выводит количество строк
-                "-l" => {
-                    println!("{}", count_lines(&file_lines))
-                },
-                // выводит количество слов
-                "-w" => {
-                    println!("{}", count_words(&file_lines))
-                },
-                // паникует если аргумент неправильный
-                _ => {
-                    panic!("Допустимые аргументы: -c -l -w")
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let (options, files) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("{}: {}", PROGRAM, err);
+            eprintln!("Try '{} --help' for more information.", PROGRAM);
+            exit(1);
+        }
+    };
+
+    // без файлов читается stdin, и имя входа не выводится
+    let inputs: Vec<Option<&str>> = if files.is_empty() {
+        vec![None]
+    } else {
+        files.iter().map(|file| Some(file.as_str())).collect()
+    };
+
+    // пул потоков для больших файлов, с одним потоком подсчёт только потоковый
+    let pool = match options.threads {
+        1 => None,
+        threads => ThreadPoolBuilder::new().num_threads(threads).build().ok(),
+    }
+    .filter(|pool| pool.current_num_threads() > 1);
+
+    let width = column_width(&options, &inputs);
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    let mut total = Counts::default();
+    let mut failed = false;
+
+    for input in &inputs {
+        let name = input.unwrap_or("-");
+
+        match count_input(*input, &options, pool.as_ref()) {
+            Ok((counts, error)) => {
+                // при ошибке чтения, как в GNU wc, печатается посчитанное до неё
+                if let Some(err) = error {
+                    stdout.flush().ok();
+                    eprintln!("{}: {}: {}", PROGRAM, name, args::io_error(&err));
+                    failed = true;
                 }
+                total.add(&counts);
+                writeln!(stdout, "{}", format_counts(&counts, &options, width, *input)).ok();
+            }
+            Err(err) => {
+                stdout.flush().ok();
+                eprintln!("{}: {}: {}", PROGRAM, name, args::io_error(&err));
+                failed = true;
             }
-        },
-        _ => {
-            panic!("Неверное количество агрументов")
         }
     }
+
+    if inputs.len() > 1 {
+        writeln!(stdout, "{}", format_counts(&total, &options, width, Some("total"))).ok();
+    }
+
+    stdout.flush().ok();
+    if failed {
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{column_width, count_reader, format_counts, parse_args, Counts, Options};
+    use std::io::Read;
+
+    // все счётчики
+    fn all_counts() -> Options {
+        Options {
+            lines: true,
+            words: true,
+            chars: true,
+            bytes: true,
+            max_line_length: true,
+            threads: 0,
+        }
+    }
+
+    // счётчики для строки байт
+    fn count(input: &[u8]) -> Counts {
+        count_reader(&mut &input[..], &all_counts()).0
+    }
+
+    // источник, отдающий данные блоками заданного размера
+    struct Chunked<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    // несколько пробелов подряд не создают лишних слов, последняя строка без \n не считается
+    fn test_counts_ascii() {
+        let counts = count(b"  this is   a\n\ttest file\nlast");
+        assert_eq!(counts.lines, 2);
+        assert_eq!(counts.words, 6);
+        assert_eq!(counts.bytes, 29);
+        assert_eq!(counts.chars, 29);
+        assert_eq!(counts.max_line_length, 17);
+    }
+
+    #[test]
+    // пробельные символы Unicode, непечатаемые символы и некорректный UTF-8 (сверено с GNU wc 9.1 в C.UTF-8)
+    fn test_counts_unicode() {
+        let counts = count(b"a\xffb c\xc2\xa0d\xe2\x81\xa0e\n");
+        assert_eq!(
+            counts,
+            Counts {
+                lines: 1,
+                words: 4,
+                chars: 9,
+                bytes: 13,
+                max_line_length: 7
+            }
+        );
+
+        let counts = count(b"a \x01 \xff b\x01c\n");
+        assert_eq!((counts.words, counts.chars, counts.max_line_length), (2, 9, 6));
+
+        let counts = count(b"\xf4\x90\x80\x80 \xf8\x88\x80\x80\x80 \xed\xa0\x80\xfc\x80");
+        assert_eq!((counts.words, counts.chars, counts.max_line_length), (0, 4, 2));
+
+        let counts = count("你好\tx\n".as_bytes());
+        assert_eq!((counts.words, counts.chars, counts.max_line_length), (2, 5, 9));
+    }
+
+    #[test]
+    // многобайтовые символы на границе блоков чтения считаются один раз
+    fn test_counts_across_reads() {
+        let text = "привет, мир 你好\u{2003}world\n".repeat(3);
+        let expected = count(text.as_bytes());
+
+        for chunk in 1..8 {
+            let mut reader = Chunked {
+                data: text.as_bytes(),
+                chunk,
+            };
+            assert_eq!(count_reader(&mut reader, &all_counts()).0, expected);
+        }
+        assert_eq!(expected.words, 12);
+        assert_eq!(expected.chars, 63);
+    }
+
+    #[test]
+    // ширина по доступным входам: недоступный первый вход на неё не влияет (как в GNU wc 9.1)
+    fn test_column_width() {
+        let manifest = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+        let width = std::fs::metadata(manifest).unwrap().len().to_string().len();
+        let options = Options {
+            lines: true,
+            words: true,
+            bytes: true,
+            ..Options::default()
+        };
+
+        assert_eq!(column_width(&options, &[Some("/nonexistent"), Some(manifest)]), width);
+        assert_eq!(column_width(&options, &[Some(manifest), Some("/nonexistent")]), width);
+        assert_eq!(column_width(&options, &[Some("/nonexistent")]), 1);
+    }
+
+    #[test]
+    // объединение флагов, сокращения длинных флагов и формат строки вывода
+    fn test_args_and_format() {
+        let args: Vec<String> = ["-lw", "--ch", "a.txt", "--", "-c"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let (options, files) = parse_args(&args).unwrap();
+        assert_eq!(
+            options,
+            Options {
+                lines: true,
+                words: true,
+                chars: true,
+                ..Options::default()
+            }
+        );
+        assert_eq!(files, vec!["a.txt", "-c"]);
+        // сообщения как у GNU wc
+        for (arg, error) in [
+            ("-x", "invalid option -- 'x'"),
+            ("--foo", "unrecognized option '--foo'"),
+            ("--lines=1", "option '--lines' doesn't allow an argument"),
+            ("--threads", "option '--threads' requires an argument"),
+        ] {
+            assert_eq!(parse_args(&[arg.to_string()]).unwrap_err(), error);
+        }
+
+        let counts = Counts {
+            lines: 2,
+            words: 3,
+            chars: 6,
+            bytes: 6,
+            max_line_length: 3,
+        };
+        assert_eq!(
+            format_counts(&counts, &options, 2, Some("a.txt")),
+            " 2  3  6 a.txt"
+        );
+    }
 }
@@ -0,0 +1,117 @@
+//! параллельный подсчёт больших файлов: файл отображается в память и делится на куски для пула потоков
+use crate::{ChunkCounts, Counter, Counts, Options};
+use memmap2::Mmap;
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+
+// файлы меньше этого размера считаются потоково в одном потоке
+pub const PARALLEL_MIN_SIZE: u64 = 16 * 1024 * 1024;
+// минимальный размер куска, чтобы накладные расходы на склейку были незаметны
+const MIN_CHUNK_SIZE: usize = 1024 * 1024;
+// кусков на поток, чтобы потоки не простаивали при неравномерном тексте
+const CHUNKS_PER_THREAD: usize = 4;
+
+// является ли байт продолжением многобайтового символа UTF-8
+fn is_continuation(byte: u8) -> bool {
+    byte & 0xC0 == 0x80
+}
+
+// деление на куски примерно равного размера: граница сдвигается вперёд до байта,
+// не являющегося продолжением символа, так что ни один символ не разрезается
+fn chunk_ranges(data: &[u8], chunks: usize) -> Vec<Range<usize>> {
+    let chunk_size = (data.len() / chunks.max(1)).max(MIN_CHUNK_SIZE);
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    while start < data.len() {
+        let mut end = (start + chunk_size).min(data.len());
+        while end < data.len() && is_continuation(data[end]) {
+            end += 1;
+        }
+
+        ranges.push(start..end);
+        start = end;
+    }
+
+    ranges
+}
+
+// подсчёт одного куска, неполный символ в конце куска - некорректные байты,
+// так как следующий кусок начинается не с продолжения символа
+fn count_chunk(chunk: &[u8], options: &Options) -> ChunkCounts {
+    let mut counter = Counter::new(options);
+    counter.counts.bytes = chunk.len() as u64;
+    counter.update(chunk);
+    counter.into_chunk()
+}
+
+// параллельный подсчёт байт в памяти, куски склеиваются по порядку
+pub fn count_bytes(data: &[u8], options: &Options, pool: &ThreadPool) -> Counts {
+    let chunks = pool.current_num_threads() * CHUNKS_PER_THREAD;
+
+    pool.install(|| {
+        chunk_ranges(data, chunks)
+            .into_par_iter()
+            .map(|range| count_chunk(&data[range], options))
+            .reduce(ChunkCounts::default, ChunkCounts::merge)
+    })
+    .finish()
+}
+
+// параллельный подсчёт файла через отображение в память
+pub fn count_file(file: &File, options: &Options, pool: &ThreadPool) -> io::Result<Counts> {
+    // файл, изменённый другим процессом во время подсчёта, может дать неточный результат, как и при чтении
+    let data = unsafe { Mmap::map(file)? };
+
+    Ok(count_bytes(&data, options, pool))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunk_ranges, count_bytes, is_continuation, MIN_CHUNK_SIZE};
+    use crate::{count_reader, Options};
+    use rayon::ThreadPoolBuilder;
+
+    #[test]
+    // границы кусков не попадают внутрь символа и покрывают весь вход
+    fn test_chunk_ranges() {
+        let data = "ёж\u{80}".repeat(MIN_CHUNK_SIZE);
+        let ranges = chunk_ranges(data.as_bytes(), 5);
+
+        assert!(ranges.len() > 1);
+        assert_eq!(ranges.first().unwrap().start, 0);
+        assert_eq!(ranges.last().unwrap().end, data.len());
+        for pair in ranges.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+            assert!(!is_continuation(data.as_bytes()[pair[1].start]));
+        }
+    }
+
+    #[test]
+    // параллельный подсчёт совпадает с потоковым при любом разрезании слов, строк и символов
+    fn test_parallel_matches_streaming() {
+        let options = Options {
+            lines: true,
+            words: true,
+            chars: true,
+            bytes: true,
+            max_line_length: true,
+            ..Options::default()
+        };
+        let pool = ThreadPoolBuilder::new().num_threads(3).build().unwrap();
+
+        // короткие строки, затем одна строка длиннее нескольких кусков
+        let line = "слово\tword 你好\u{a0}x \u{1}\u{ff} tab\tend\r\u{2003}строка\n";
+        let long_line = "длинное_слово\tи 你好 ";
+        let mut data = line.repeat(MIN_CHUNK_SIZE / line.len() * 2).into_bytes();
+        data.extend(long_line.repeat(MIN_CHUNK_SIZE / long_line.len() * 2).into_bytes());
+        data.extend_from_slice(b"\xf8\x88\x80\x80\x80\xe2\x81\n\t\tlast");
+
+        let expected = count_reader(&mut &data[..], &options).0;
+        assert_eq!(count_bytes(&data, &options, &pool), expected);
+        assert!(expected.max_line_length > MIN_CHUNK_SIZE as u64);
+    }
+}
@@ -0,0 +1,807 @@
+//! распаковка и упаковка строк вида "a4bc2d5e" <-> "aaaabccddddde"
+//!
+//! символ может быть повторён числом любой длины ("a12"), escape - символ (по умолчанию `\`)
+//! делает следующий символ обычным, так можно повторять цифры и сам escape: "\45" -> "44444"
+use std::fmt;
+use std::io::{self, Read};
+
+// escape - символ по умолчанию
+pub const DEFAULT_ESCAPE: char = '\\';
+
+// вид ошибки распаковки
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnpackErrorKind {
+    // число без символа для повторения ("45")
+    UnexpectedDigit,
+    // escape - символ в конце строки
+    DanglingEscape,
+    // число повторений не помещается в usize
+    CountOverflow,
+    // результат длиннее заданного максимума
+    OutputTooLarge,
+    // результат длиннее входа больше, чем в заданное число раз
+    ExpansionTooLarge,
+}
+
+// ошибка распаковки с позицией (номер символа от начала входа, с нуля)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnpackError {
+    pub kind: UnpackErrorKind,
+    pub position: usize,
+}
+
+impl fmt::Display for UnpackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self.kind {
+            UnpackErrorKind::UnexpectedDigit => "число без символа для повторения",
+            UnpackErrorKind::DanglingEscape => "escape - символ в конце строки",
+            UnpackErrorKind::CountOverflow => "слишком большое число повторений",
+            UnpackErrorKind::OutputTooLarge => "превышен максимальный размер результата",
+            UnpackErrorKind::ExpansionTooLarge => "превышен максимальный коэффициент расширения",
+        };
+        write!(
+            f,
+            "некорректная строка: {} в позиции {}",
+            description, self.position
+        )
+    }
+}
+
+impl std::error::Error for UnpackError {}
+
+// настройки упаковки и распаковки
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Codec {
+    escape: char,
+    // максимум символов результата
+    max_output: Option<usize>,
+    // максимум символов результата на символ входа
+    max_expansion_ratio: Option<usize>,
+}
+
+impl Default for Codec {
+    fn default() -> Self {
+        Codec {
+            escape: DEFAULT_ESCAPE,
+            max_output: None,
+            max_expansion_ratio: None,
+        }
+    }
+}
+
+impl Codec {
+    // кодек со своим escape - символом, цифра escape - символом быть не может
+    pub fn with_escape(escape: char) -> Option<Self> {
+        if escape.is_ascii_digit() {
+            None
+        } else {
+            Some(Codec {
+                escape,
+                ..Codec::default()
+            })
+        }
+    }
+
+    // ограничение размера результата распаковки в символах
+    pub fn max_output(mut self, max_output: usize) -> Self {
+        self.max_output = Some(max_output);
+        self
+    }
+
+    // ограничение отношения длины результата к длине прочитанного входа (в символах)
+    pub fn max_expansion_ratio(mut self, max_expansion_ratio: usize) -> Self {
+        self.max_expansion_ratio = Some(max_expansion_ratio);
+        self
+    }
+
+    pub fn escape(&self) -> char {
+        self.escape
+    }
+
+    // распаковка строки целиком, лимиты проверяются до выделения памяти под серию
+    pub fn decode(&self, input: &str) -> Result<String, UnpackError> {
+        self.decode_iter(input.chars()).collect()
+    }
+
+    // ленивая распаковка: символы результата выдаются по одному, без копирования входа
+    pub fn decode_iter<I: Iterator<Item = char>>(&self, input: I) -> DecodeIter<I> {
+        DecodeIter {
+            input,
+            machine: Machine::new(*self),
+            run: None,
+            done: false,
+        }
+    }
+
+    // распаковка потока байт UTF-8 как io::Read
+    pub fn decode_reader<R: Read>(&self, inner: R) -> DecodeReader<R> {
+        DecodeReader {
+            inner,
+            machine: Machine::new(*self),
+            input: vec![0; READ_BUFFER_SIZE].into_boxed_slice(),
+            input_start: 0,
+            input_end: 0,
+            run: None,
+            partial: [0; 4],
+            partial_start: 0,
+            partial_end: 0,
+            done: false,
+            error: None,
+        }
+    }
+
+    // упаковка строки целиком, decode(encode(s)) == s для любой строки
+    pub fn encode(&self, input: &str) -> String {
+        let mut encoder = Encoder::new(*self);
+        let mut result = String::new();
+
+        encoder.push_str(input, &mut result);
+        encoder.finish(&mut result);
+
+        result
+    }
+}
+
+// распаковка с escape - символом по умолчанию
+pub fn decode(input: &str) -> Result<String, UnpackError> {
+    Codec::default().decode(input)
+}
+
+// упаковка с escape - символом по умолчанию
+pub fn encode(input: &str) -> String {
+    Codec::default().encode(input)
+}
+
+// состояние распаковки между символами входа
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    // нет символа, ожидающего вывода
+    Empty,
+    // был escape - символ (его позиция), следующий символ - обычный
+    Escaped(usize),
+    // символ без числа повторений
+    Pending(char),
+    // символ и уже прочитанная часть числа повторений
+    Counting(char, usize),
+}
+
+impl State {
+    // серия для вывода, когда символ больше не может получить число повторений
+    fn run(self) -> Option<(char, usize)> {
+        match self {
+            State::Pending(c) => Some((c, 1)),
+            State::Counting(c, count) => Some((c, count)),
+            State::Empty | State::Escaped(_) => None,
+        }
+    }
+}
+
+// конечный автомат распаковки: по символу входа выдаёт завершённую серию
+#[derive(Debug, Clone)]
+struct Machine {
+    codec: Codec,
+    state: State,
+    // символов входа прочитано
+    position: usize,
+    // символов результата выдано
+    produced: usize,
+}
+
+impl Machine {
+    fn new(codec: Codec) -> Self {
+        Machine {
+            codec,
+            state: State::Empty,
+            position: 0,
+            produced: 0,
+        }
+    }
+
+    // ошибка в позиции
+    fn error(kind: UnpackErrorKind, position: usize) -> UnpackError {
+        UnpackError { kind, position }
+    }
+
+    // проверка лимитов до вывода серии
+    fn emit(
+        &mut self,
+        run: Option<(char, usize)>,
+        position: usize,
+    ) -> Result<Option<(char, usize)>, UnpackError> {
+        let Some((_, count)) = run else {
+            return Ok(None);
+        };
+
+        let produced = self
+            .produced
+            .checked_add(count)
+            .ok_or(Self::error(UnpackErrorKind::OutputTooLarge, position))?;
+        if self
+            .codec
+            .max_output
+            .is_some_and(|max_output| produced > max_output)
+        {
+            return Err(Self::error(UnpackErrorKind::OutputTooLarge, position));
+        }
+        if self
+            .codec
+            .max_expansion_ratio
+            .is_some_and(|ratio| produced > ratio.saturating_mul(self.position.max(1)))
+        {
+            return Err(Self::error(UnpackErrorKind::ExpansionTooLarge, position));
+        }
+
+        self.produced = produced;
+        Ok(run)
+    }
+
+    // переход по символу входа
+    fn step(&mut self, c: char) -> Result<Option<(char, usize)>, UnpackError> {
+        let position = self.position;
+        self.position += 1;
+
+        let (state, run) = match (self.state, c.to_digit(10)) {
+            // символ после escape всегда обычный
+            (State::Escaped(_), _) => (State::Pending(c), None),
+            (State::Empty, Some(_)) => {
+                return Err(Self::error(UnpackErrorKind::UnexpectedDigit, position))
+            }
+            (State::Pending(pending), Some(digit)) => {
+                (State::Counting(pending, digit as usize), None)
+            }
+            (State::Counting(pending, count), Some(digit)) => {
+                let count = count
+                    .checked_mul(10)
+                    .and_then(|count| count.checked_add(digit as usize))
+                    .ok_or(Self::error(UnpackErrorKind::CountOverflow, position))?;
+                (State::Counting(pending, count), None)
+            }
+            // не цифра завершает прошлую серию
+            (state, None) if c == self.codec.escape => (State::Escaped(position), state.run()),
+            (state, None) => (State::Pending(c), state.run()),
+        };
+
+        let run = self.emit(run, position)?;
+        self.state = state;
+        Ok(run)
+    }
+
+    // конец входа
+    fn finish(&mut self) -> Result<Option<(char, usize)>, UnpackError> {
+        if let State::Escaped(position) = self.state {
+            return Err(Self::error(UnpackErrorKind::DanglingEscape, position));
+        }
+
+        let run = self.emit(self.state.run(), self.position)?;
+        self.state = State::Empty;
+        Ok(run)
+    }
+}
+
+// ленивая распаковка последовательности символов, после ошибки итератор заканчивается
+#[derive(Debug, Clone)]
+pub struct DecodeIter<I> {
+    input: I,
+    machine: Machine,
+    // выдаваемая серия: символ и сколько раз его ещё выдать
+    run: Option<(char, usize)>,
+    done: bool,
+}
+
+impl<I: Iterator<Item = char>> Iterator for DecodeIter<I> {
+    type Item = Result<char, UnpackError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((c, remaining)) = &mut self.run {
+                if *remaining > 0 {
+                    *remaining -= 1;
+                    return Some(Ok(*c));
+                }
+                self.run = None;
+            }
+
+            if self.done {
+                return None;
+            }
+
+            let step = match self.input.next() {
+                Some(c) => self.machine.step(c),
+                None => {
+                    self.done = true;
+                    self.machine.finish()
+                }
+            };
+
+            match step {
+                Ok(run) => self.run = run,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+// размер буфера чтения DecodeReader
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+// длина символа UTF-8 по первому байту, None - байт не может начинать символ
+fn utf8_char_len(first_byte: u8) -> Option<usize> {
+    match first_byte {
+        0x00..=0x7F => Some(1),
+        0xC2..=0xDF => Some(2),
+        0xE0..=0xEF => Some(3),
+        0xF0..=0xF4 => Some(4),
+        _ => None,
+    }
+}
+
+// распаковка потока байт: читает вход блоками и отдаёт результат по мере заполнения буфера вызывающего,
+// ошибки распаковки и некорректный UTF-8 - io::Error с видом InvalidData
+#[derive(Debug)]
+pub struct DecodeReader<R> {
+    inner: R,
+    machine: Machine,
+    input: Box<[u8]>,
+    input_start: usize,
+    input_end: usize,
+    run: Option<(char, usize)>,
+    // байты символа, не поместившегося в прошлый буфер вывода
+    partial: [u8; 4],
+    partial_start: usize,
+    partial_end: usize,
+    done: bool,
+    error: Option<UnpackError>,
+}
+
+impl<R: Read> DecodeReader<R> {
+    // ошибка некорректного UTF-8 на входе
+    fn invalid_utf8(&self) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("вход не в UTF-8 (символ {})", self.machine.position),
+        )
+    }
+
+    // следующий символ входа, None - конец входа; can_block = false - не читать inner, если буфер пуст
+    fn next_char(&mut self, can_block: bool) -> io::Result<Option<Option<char>>> {
+        loop {
+            let available = &self.input[self.input_start..self.input_end];
+            if let Some(&first_byte) = available.first() {
+                let len = utf8_char_len(first_byte).ok_or_else(|| self.invalid_utf8())?;
+                if available.len() >= len {
+                    let c = std::str::from_utf8(&available[..len])
+                        .map_err(|_| self.invalid_utf8())?
+                        .chars()
+                        .next();
+                    self.input_start += len;
+                    return Ok(Some(c));
+                }
+            }
+
+            if !can_block {
+                return Ok(None);
+            }
+
+            // остаток неполного символа переносится в начало буфера
+            self.input.copy_within(self.input_start..self.input_end, 0);
+            self.input_end -= self.input_start;
+            self.input_start = 0;
+
+            let read = loop {
+                match self.inner.read(&mut self.input[self.input_end..]) {
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    result => break result?,
+                }
+            };
+            if read == 0 {
+                return match self.input_end {
+                    0 => Ok(Some(None)),
+                    _ => Err(self.invalid_utf8()),
+                };
+            }
+            self.input_end += read;
+        }
+    }
+}
+
+impl<R: Read> Read for DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(err) = self.error {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+        }
+
+        let mut written = 0;
+        while written < buf.len() {
+            // остаток символа с прошлого вызова
+            if self.partial_start < self.partial_end {
+                let len = (self.partial_end - self.partial_start).min(buf.len() - written);
+                buf[written..written + len]
+                    .copy_from_slice(&self.partial[self.partial_start..self.partial_start + len]);
+                self.partial_start += len;
+                written += len;
+                continue;
+            }
+
+            // текущая серия: сколько целых символов влезает в буфер
+            if let Some((c, remaining)) = &mut self.run {
+                if *remaining > 0 {
+                    let mut encoded = [0; 4];
+                    let encoded = c.encode_utf8(&mut encoded).as_bytes();
+                    let fit = ((buf.len() - written) / encoded.len()).min(*remaining);
+
+                    if fit == 0 {
+                        self.partial[..encoded.len()].copy_from_slice(encoded);
+                        self.partial_start = 0;
+                        self.partial_end = encoded.len();
+                        *remaining -= 1;
+                        continue;
+                    }
+
+                    for chunk in
+                        buf[written..written + fit * encoded.len()].chunks_exact_mut(encoded.len())
+                    {
+                        chunk.copy_from_slice(encoded);
+                    }
+                    *remaining -= fit;
+                    written += fit * encoded.len();
+                    continue;
+                }
+                self.run = None;
+            }
+
+            if self.done {
+                break;
+            }
+
+            // уже записанное отдаётся, не дожидаясь чтения следующего блока входа
+            let step = match self.next_char(written == 0)? {
+                None => break,
+                Some(Some(c)) => self.machine.step(c),
+                Some(None) => {
+                    self.done = true;
+                    self.machine.finish()
+                }
+            };
+
+            match step {
+                Ok(run) => self.run = run,
+                Err(err) => {
+                    self.error = Some(err);
+                    // результат до ошибки отдаётся, ошибка - при следующем чтении
+                    if written > 0 {
+                        break;
+                    }
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+                }
+            }
+        }
+
+        Ok(written)
+    }
+}
+
+// потоковая упаковка: серия одинаковых символов может продолжаться в следующем куске
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    codec: Codec,
+    // текущая серия: символ и длина
+    run: Option<(char, usize)>,
+}
+
+impl Encoder {
+    pub fn new(codec: Codec) -> Self {
+        Encoder { codec, run: None }
+    }
+
+    // запись серии: цифры и escape экранируются, число - только для серий длиннее 1
+    fn write_run(&self, c: char, count: usize, output: &mut String) {
+        if c.is_ascii_digit() || c == self.codec.escape {
+            output.push(self.codec.escape);
+        }
+        output.push(c);
+        if count > 1 {
+            output.push_str(&count.to_string());
+        }
+    }
+
+    // упаковка очередного куска входа
+    pub fn push_str(&mut self, input: &str, output: &mut String) {
+        for c in input.chars() {
+            match &mut self.run {
+                Some((run_char, count)) if *run_char == c => *count += 1,
+                _ => {
+                    if let Some((run_char, count)) = self.run.replace((c, 1)) {
+                        self.write_run(run_char, count, output);
+                    }
+                }
+            }
+        }
+    }
+
+    // конец входа
+    pub fn finish(&mut self, output: &mut String) {
+        if let Some((c, count)) = self.run.take() {
+            self.write_run(c, count, output);
+        }
+    }
+}
+
+// тесты
+#[cfg(test)]
+mod tests {
+    use crate::{decode, encode, Codec, Machine, State, UnpackError, UnpackErrorKind};
+    use proptest::prelude::*;
+    use std::io::Read;
+
+    // чтение DecodeReader буферами заданного размера
+    fn read_in_chunks(codec: &Codec, input: &[u8], chunk: usize) -> std::io::Result<Vec<u8>> {
+        let mut reader = codec.decode_reader(input);
+        let mut buf = vec![0; chunk];
+        let mut output = Vec::new();
+        loop {
+            match reader.read(&mut buf)? {
+                0 => return Ok(output),
+                read => output.extend_from_slice(&buf[..read]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_str1() {
+        assert_eq!(decode("a4bc2d5e"), Ok("aaaabccddddde".to_string()));
+    }
+
+    #[test]
+    fn test_str2() {
+        assert_eq!(decode("abcd"), Ok("abcd".to_string()));
+    }
+
+    #[test]
+    fn test_str3() {
+        assert_eq!(
+            decode("45"),
+            Err(UnpackError {
+                kind: UnpackErrorKind::UnexpectedDigit,
+                position: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_str4() {
+        assert_eq!(decode(""), Ok("".to_string()));
+    }
+
+    #[test]
+    fn test_str5() {
+        assert_eq!(decode("qwe\\4\\5"), Ok("qwe45".to_string()));
+    }
+
+    #[test]
+    fn test_str6() {
+        assert_eq!(decode("qwe\\45"), Ok("qwe44444".to_string()));
+    }
+
+    #[test]
+    fn test_str7() {
+        assert_eq!(decode("qwe\\\\5"), Ok("qwe\\\\\\\\\\".to_string()));
+    }
+
+    #[test]
+    // многозначные числа и ноль повторений
+    fn test_multi_digit_counts() {
+        assert_eq!(decode("a12b"), Ok("a".repeat(12) + "b"));
+        assert_eq!(decode("\\1\\010"), Ok("1".to_string() + &"0".repeat(10)));
+        assert_eq!(decode("a0b"), Ok("b".to_string()));
+    }
+
+    #[test]
+    // ошибки с позицией в символах
+    fn test_errors() {
+        assert_eq!(
+            decode("ёж\\"),
+            Err(UnpackError {
+                kind: UnpackErrorKind::DanglingEscape,
+                position: 2
+            })
+        );
+        assert_eq!(
+            decode("a2\\33x\\"),
+            Err(UnpackError {
+                kind: UnpackErrorKind::DanglingEscape,
+                position: 6
+            })
+        );
+        assert_eq!(
+            decode("a99999999999999999999999"),
+            Err(UnpackError {
+                kind: UnpackErrorKind::CountOverflow,
+                position: 20
+            })
+        );
+        assert_eq!(
+            decode("x\\").unwrap_err().to_string(),
+            "некорректная строка: escape - символ в конце строки в позиции 1"
+        );
+    }
+
+    #[test]
+    // переходы автомата распаковки и выдаваемые серии
+    fn test_state_machine() {
+        let mut machine = Machine::new(Codec::default());
+        let steps = [
+            ('a', State::Pending('a'), None),
+            ('1', State::Counting('a', 1), None),
+            ('2', State::Counting('a', 12), None),
+            ('\\', State::Escaped(3), Some(('a', 12))),
+            ('\\', State::Pending('\\'), None),
+            ('3', State::Counting('\\', 3), None),
+            ('\\', State::Escaped(6), Some(('\\', 3))),
+            ('7', State::Pending('7'), None),
+            ('ж', State::Pending('ж'), Some(('7', 1))),
+            ('0', State::Counting('ж', 0), None),
+        ];
+        for (c, state, run) in steps {
+            assert_eq!(machine.step(c), Ok(run));
+            assert_eq!(machine.state, state);
+        }
+        assert_eq!(machine.finish(), Ok(Some(('ж', 0))));
+        assert_eq!(machine.state, State::Empty);
+        assert_eq!(machine.finish(), Ok(None));
+
+        // цифра в начале и после выданной серии
+        assert_eq!(
+            Machine::new(Codec::default()).step('5'),
+            Err(UnpackError {
+                kind: UnpackErrorKind::UnexpectedDigit,
+                position: 0
+            })
+        );
+    }
+
+    #[test]
+    // лимиты размера результата и коэффициента расширения
+    fn test_limits() {
+        let codec = Codec::default().max_output(10);
+        assert_eq!(codec.decode("a5b5"), Ok("aaaaabbbbb".to_string()));
+        assert_eq!(
+            codec.decode("a5b6"),
+            Err(UnpackError {
+                kind: UnpackErrorKind::OutputTooLarge,
+                position: 4
+            })
+        );
+
+        let codec = Codec::default().max_expansion_ratio(3);
+        assert_eq!(codec.decode("a5bc"), Ok("aaaaabc".to_string()));
+        assert_eq!(
+            codec.decode("a20bc"),
+            Err(UnpackError {
+                kind: UnpackErrorKind::ExpansionTooLarge,
+                position: 3
+            })
+        );
+
+        // огромное число повторений отвергается без выделения памяти
+        let hostile = format!("a{}", usize::MAX);
+        assert_eq!(
+            Codec::default()
+                .max_output(1 << 20)
+                .decode(&hostile)
+                .unwrap_err()
+                .kind,
+            UnpackErrorKind::OutputTooLarge
+        );
+        let escaped = "\\\\9".repeat(1000);
+        assert_eq!(
+            Codec::default().max_expansion_ratio(3).decode(&escaped),
+            Ok("\\".repeat(9000))
+        );
+        assert_eq!(
+            Codec::default()
+                .max_expansion_ratio(2)
+                .decode(&escaped)
+                .unwrap_err()
+                .kind,
+            UnpackErrorKind::ExpansionTooLarge
+        );
+    }
+
+    #[test]
+    // итератор выдаёт результат лениво и заканчивается после ошибки
+    fn test_decode_iter() {
+        let codec = Codec::default();
+        let head: String = codec
+            .decode_iter("x18446744073709551615".chars())
+            .take(3)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(head, "xxx");
+
+        let mut iter = codec.decode_iter("ab2\\".chars());
+        assert_eq!(iter.next(), Some(Ok('a')));
+        assert_eq!(iter.next(), Some(Ok('b')));
+        assert_eq!(iter.next(), Some(Ok('b')));
+        assert_eq!(
+            iter.next().unwrap().unwrap_err().kind,
+            UnpackErrorKind::DanglingEscape
+        );
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    // io::Read с буферами любого размера, символы режутся между вызовами read
+    fn test_decode_reader() {
+        let codec = Codec::default();
+        let expected = decode("ж3a2\\4😀5x").unwrap();
+        for chunk in 1..8 {
+            let output = read_in_chunks(&codec, "ж3a2\\4😀5x".as_bytes(), chunk).unwrap();
+            assert_eq!(output, expected.as_bytes());
+        }
+
+        let err = read_in_chunks(&codec, b"ab\\", 16).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.into_inner()
+                .unwrap()
+                .downcast::<UnpackError>()
+                .unwrap()
+                .kind,
+            UnpackErrorKind::DanglingEscape
+        );
+        assert!(read_in_chunks(&codec, b"a\xff", 16).is_err());
+        assert!(read_in_chunks(&codec, b"a\xd0", 16).is_err());
+    }
+
+    #[test]
+    // свой escape - символ
+    fn test_custom_escape() {
+        let codec = Codec::with_escape('#').unwrap();
+        assert_eq!(codec.decode("#45\\3##"), Ok("44444\\\\\\#".to_string()));
+        assert_eq!(codec.encode("1##\\\\"), "#1##2\\2");
+        assert!(Codec::with_escape('7').is_none());
+    }
+
+    #[test]
+    // упаковка серий
+    fn test_encode() {
+        assert_eq!(encode("aaaabccddddde"), "a4bc2d5e");
+        assert_eq!(encode("qwe44444"), "qwe\\45");
+        assert_eq!(encode(&"я".repeat(12)), "я12");
+        assert_eq!(encode(""), "");
+    }
+
+    proptest! {
+        #[test]
+        // распаковка упакованной строки возвращает исходную
+        fn prop_round_trip(input in "\\PC*") {
+            prop_assert_eq!(decode(&encode(&input)), Ok(input));
+        }
+
+        #[test]
+        // то же для строк с длинными сериями, цифрами и любым escape - символом
+        fn prop_round_trip_runs(
+            runs in prop::collection::vec((prop::char::any(), 1usize..30), 0..20),
+            escape in prop::char::any().prop_filter("не цифра", |c| !c.is_ascii_digit()),
+        ) {
+            let codec = Codec::with_escape(escape).unwrap();
+            let input: String = runs.iter().flat_map(|(c, count)| std::iter::repeat_n(*c, *count)).collect();
+            prop_assert_eq!(codec.decode(&codec.encode(&input)), Ok(input));
+        }
+
+        #[test]
+        // io::Read даёт тот же результат, что и распаковка строки
+        fn prop_reader_matches_decode(input in "[a-cж\\\\0-9]{0,40}", chunk in 1usize..10) {
+            let codec = Codec::default().max_output(1000);
+            let expected = codec.decode(&input);
+            let output = read_in_chunks(&codec, input.as_bytes(), chunk);
+            match expected {
+                Ok(expected) => prop_assert_eq!(output.unwrap(), expected.into_bytes()),
+                Err(_) => prop_assert!(output.is_err()),
+            }
+        }
+    }
+}
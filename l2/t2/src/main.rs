@@ -1,168 +1,161 @@
-/// для тестов - cargo test
-
-pub fn unpack(input: &str) -> String {
-    // стэк для обрабоки символов
-    let mut stack: Vec<char> = vec![];
-    // строчка для финального результата
-    let mut result = String::new();
-
-    // цикл проходящий по всем элементам
-    for c in input.chars() {
-        // если символ является числом
-        if c.is_numeric() {
-            let char_to_append: char;
-
-            // если символ в стэке один
-            if stack.len() == 1 {
-                // если в стэке: один escape - символ добавляет числовой символ в стэк
-                if stack[0] == '\\' {
-                    stack.push(c);
-                    continue;
-                }
-
-                // в противном случае записывает этот элемент для последующего дублирования
-                char_to_append = stack[0];
-            } else if stack.len() == 2 && stack[0] == '\\' {
-                // если элеметов в стэке два и первый: escape - символ; записыват второй элемент для дублирования
-                char_to_append = stack[1];
-            } else {
-                // в остальных случаях паникует
-                panic!("некорректная строка")
-            }
-
-            // дублирует нужный символ
-            let number = c.to_digit(10).unwrap();
-            let chars_to_push: String = (0..number).map(|_| char_to_append).collect();
-            // добавляет в строку
-            result.push_str(&chars_to_push);
-            // и очищает stack
-            stack.clear();
-
-            continue;
-        }
+//! потоковая распаковка (по умолчанию) или упаковка stdin в stdout
+use std::env;
+use std::io::{self, BufWriter, ErrorKind, Read, Write};
+use std::process::exit;
+use std::str;
+use t2::{Codec, Encoder, DEFAULT_ESCAPE};
+
+// размер блока чтения
+const BUFFER_SIZE: usize = 64 * 1024;
+
+const USAGE: &str =
+    "использование: t2 [-d | -e] [--escape C] [--max-output N] [--max-ratio N] < вход > выход
+    -d, --decode      распаковка \"a4bc2d5e\" -> \"aaaabccddddde\" (по умолчанию)
+    -e, --encode      упаковка \"aaaabccddddde\" -> \"a4bc2d5e\"
+    --escape C        escape - символ (по умолчанию \\)
+    --max-output N    ошибка, если результат распаковки длиннее N символов
+    --max-ratio N     ошибка, если результат распаковки длиннее входа больше чем в N раз";
+
+// число - значение аргумента
+fn parse_limit(name: &str, value: Option<&String>) -> Result<usize, String> {
+    let value = value.ok_or_else(|| format!("после {} нужно число", name))?;
+    value
+        .parse()
+        .map_err(|_| format!("{}: некорректное число {:?}", name, value))
+}
 
-        // если символ является escape - символом
-        if c == '\\' {
-            if stack.is_empty() {
-                // если стэк пуст, добавляет символ
-                stack.push(c);
-            } else if stack.len() == 1 {
-                // если первый символ в стэке - escape, добавляет ещё один в стэк
-                if stack[0] == '\\' {
-                    stack.push(c);
-                    continue;
-                }
-
-                // иначе записывает в финальную строку символ из стэка
-                result.push(stack[0]);
-                // чистит стэк
-                stack.clear();
-                // и записывает escape символ в стэк
-                stack.push(c);
-            } else if stack.len() == 2 && stack[0] == '\\' {
-                // если символов в стэке 2 и первый: escape, добавляет в финальную строку 2 элемент
-                result.push(stack[1]);
-                // чистит стэк
-                stack.clear();
-                // и добавляет escape в стэк
-                stack.push(c);
-            } else {
-                // в противном случае паникует
-                panic!("некорректная строка")
+// разбор аргументов командной строки
+fn parse_args(args: &[String]) -> Result<(bool, Codec), String> {
+    let mut encode = false;
+    let mut escape = DEFAULT_ESCAPE;
+    let mut max_output = None;
+    let mut max_ratio = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-d" | "--decode" => encode = false,
+            "-e" | "--encode" => encode = true,
+            "--escape" => {
+                let value = args.next().ok_or("после --escape нужен символ")?;
+                let mut chars = value.chars();
+                escape = match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => {
+                        return Err(format!(
+                            "escape - символ должен быть одним символом: {:?}",
+                            value
+                        ))
+                    }
+                };
             }
-
-            continue;
-        }
-
-        // для всех остальных символов
-        if stack.is_empty() {
-            // если в стэке ничего нет - записывает в стэк
-            stack.push(c);
-        } else if stack.len() == 1 {
-            // если первый символ в стэке - escape; записывает символ в стэк
-            if stack[0] == '\\' {
-                stack.push(c);
-            } else {
-                // в остальных случаях с одним элементов записывает символ в финальную строку
-                // элемент из стэка
-                result.push(stack[0]);
-                // очищает стэк
-                stack.clear();
-                // и записывает новый симво в стэк
-                stack.push(c);
+            "--max-output" => max_output = Some(parse_limit(arg, args.next())?),
+            "--max-ratio" => max_ratio = Some(parse_limit(arg, args.next())?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                exit(0);
             }
+            other => return Err(format!("неизвестный аргумент {:?}\n{}", other, USAGE)),
         }
     }
 
-    // после прохода цикла, если в стэке остались элементы
-    if stack.len() == 1 {
-        if stack[0] == '\\' || stack[0].is_numeric() {
-            // если элемент один и он - escape или число: паникует
-            panic!("некорректная строка")
-        } else {
-            // в противном случае записывает элемент в финальную строку
-            result.push(stack[0])
-        }
-    } else if stack.len() == 2 {
-        // если элементов в стеке два и первый - escape;
-        // добавляет в финальную строку второй символ из стека
-        if stack[0] == '\\' {
-            result.push(stack[1])
-        } else {
-            // иначе паникует
-            panic!("некорректная строка")
-        }
-    } else if !stack.is_empty() {
-        // в остальных случаях если стэк не пустой - паникует
-        panic!("некорректная строка")
+    let mut codec = Codec::with_escape(escape)
+        .ok_or_else(|| format!("escape - символ не может быть цифрой: {:?}", escape))?;
+    if let Some(max_output) = max_output {
+        codec = codec.max_output(max_output);
+    }
+    if let Some(max_ratio) = max_ratio {
+        codec = codec.max_expansion_ratio(max_ratio);
     }
 
-    result
+    Ok((encode, codec))
 }
 
-// тесты
-#[cfg(test)]
-mod tests {
-    use crate::unpack;
-
-    #[test]
-    fn test_str1() {
-        assert_eq!(unpack("a4bc2d5e"), "aaaabccddddde".to_string());
+// потоковая распаковка stdin: серии выдаются по мере записи, а не целиком в памяти
+fn run_decode(codec: Codec) -> Result<(), String> {
+    let mut reader = codec.decode_reader(io::stdin().lock());
+    let mut stdout = BufWriter::new(io::stdout().lock());
+
+    // ошибка распаковки прерывает вывод после уже распакованного
+    let result = io::copy(&mut reader, &mut stdout).and_then(|_| stdout.flush());
+    match result {
+        // вывод закрыт читателем (t2 | head)
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => Ok(()),
+        result => result.map(|_| ()).map_err(|err| err.to_string()),
     }
+}
 
-    #[test]
-    fn test_str2() {
-        assert_eq!(unpack("abcd"), "abcd".to_string());
+// упаковка stdin блоками, неполный символ UTF-8 в конце блока переносится в следующий
+fn run_encode(codec: Codec) -> Result<(), String> {
+    let mut encoder = Encoder::new(codec);
+    let mut stdin = io::stdin().lock();
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    let mut pending = 0;
+    let mut output = String::new();
+
+    loop {
+        let read = match stdin.read(&mut buf[pending..]) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(format!("ошибка чтения: {}", err)),
+        };
+        let end = pending + read;
+
+        let valid = match str::from_utf8(&buf[..end]) {
+            Ok(text) => text.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(err) => {
+                return Err(format!(
+                    "вход не в UTF-8 (байт {} блока)",
+                    err.valid_up_to()
+                ))
+            }
+        };
+        encoder.push_str(
+            str::from_utf8(&buf[..valid]).unwrap_or_default(),
+            &mut output,
+        );
+        stdout
+            .write_all(output.as_bytes())
+            .map_err(|err| format!("ошибка записи: {}", err))?;
+        output.clear();
+
+        buf.copy_within(valid..end, 0);
+        pending = end - valid;
     }
 
-    #[test]
-    #[should_panic]
-    fn test_str3() {
-        unpack("45");
+    if pending > 0 {
+        return Err("вход оканчивается неполным символом UTF-8".to_string());
     }
 
-    #[test]
-    fn test_str4() {
-        assert_eq!(unpack(""), "".to_string());
-    }
+    encoder.finish(&mut output);
+    stdout
+        .write_all(output.as_bytes())
+        .and_then(|_| stdout.flush())
+        .map_err(|err| format!("ошибка записи: {}", err))
+}
 
-    #[test]
-    fn test_str5() {
-        assert_eq!(unpack("qwe\\4\\5"), "qwe45".to_string());
-    }
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    #[test]
-    fn test_str6() {
-        assert_eq!(unpack("qwe\\45"), "qwe44444".to_string());
-    }
+    let (encode, codec) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(2);
+        }
+    };
+
+    let result = if encode {
+        run_encode(codec)
+    } else {
+        run_decode(codec)
+    };
 
-    #[test]
-    fn test_str7() {
-        assert_eq!(unpack("qwe\\\\5"), "qwe\\\\\\\\\\".to_string());
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        exit(1);
     }
 }
-
-fn main() {
-    // еще один тест =)
-    println!("{}", unpack("qwe\\\\5"));
-}
\ No newline at end of file
@@ -0,0 +1,459 @@
+//! сравнение строк по ключам: ключи выделяются один раз при чтении строки.
+//! Строки и ключи - байты, без модификаторов сравниваются побайтово; на символы ключ
+//! делится только для -d, -i, -f и --collate, неверные байты UTF-8 идут после всех символов
+use crate::key::{self, KeyOptions, KeySpec};
+use crate::order;
+use feruca::Collator;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::mem::size_of;
+use std::ops::Range;
+
+// строка с заранее найденными диапазонами ключей
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub text: Vec<u8>,
+    keys: Vec<Range<usize>>,
+}
+
+impl Line {
+    // байты i - го ключа
+    fn key(&self, index: usize) -> &[u8] {
+        &self.text[self.keys[index].clone()]
+    }
+
+    // примерный размер строки в памяти вместе с ключами
+    pub fn memory_size(&self) -> usize {
+        size_of::<Line>() + self.text.capacity() + self.keys.capacity() * size_of::<Range<usize>>()
+    }
+}
+
+// настройки сортировки
+#[derive(Debug, Clone)]
+pub struct Config {
+    keys: Vec<KeySpec>,
+    separator: Option<char>,
+    // -s: без сравнения строк целиком при равных ключах
+    stable: bool,
+    // глобальный -r, разворачивает и сравнение строк целиком
+    reverse: bool,
+    // --collate: текст по правилам Unicode (UCA, корень CLDR) вместо байтов
+    collate: bool,
+}
+
+impl Config {
+    // ключи без своих модификаторов берут глобальные, без -k ключ - вся строка
+    pub fn new(
+        keys: Vec<KeySpec>,
+        global: KeyOptions,
+        separator: Option<char>,
+        stable: bool,
+    ) -> Self {
+        let mut keys: Vec<KeySpec> = keys
+            .into_iter()
+            .map(|mut key| {
+                if key.options.is_empty() {
+                    key.options = global;
+                }
+                key
+            })
+            .collect();
+        if keys.is_empty() {
+            keys.push(KeySpec::whole_line(global));
+        }
+
+        Config {
+            keys,
+            separator,
+            stable,
+            reverse: global.reverse,
+            collate: false,
+        }
+    }
+
+    // включение сравнения текста по правилам Unicode
+    pub fn collate(mut self, collate: bool) -> Self {
+        self.collate = collate;
+        self
+    }
+
+    // разбор строки на ключи
+    pub fn line(&self, text: Vec<u8>) -> Line {
+        let keys = self
+            .keys
+            .iter()
+            .map(|key| key.range(&text, self.separator))
+            .collect();
+
+        Line { text, keys }
+    }
+
+    // сравнение по ключам по порядку, при равенстве всех - строк целиком (кроме -s)
+    pub fn compare(&self, a: &Line, b: &Line) -> Ordering {
+        for (index, key) in self.keys.iter().enumerate() {
+            let ordering = compare_key(a.key(index), b.key(index), &key.options, self.collate);
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+
+        if self.stable {
+            return Ordering::Equal;
+        }
+        // равные по правилам Unicode строки различаются байтами, иначе -u склеил бы их
+        let ordering = if self.collate {
+            collate_text(&text(&a.text), &text(&b.text)).then_with(|| a.text.cmp(&b.text))
+        } else {
+            a.text.cmp(&b.text)
+        };
+        if self.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+// текст байтов: без копирования, если это верный UTF-8
+fn text(bytes: &[u8]) -> Cow<'_, str> {
+    String::from_utf8_lossy(bytes)
+}
+
+// сравнение ключей с учётом модификаторов
+fn compare_key(a: &[u8], b: &[u8], options: &KeyOptions, collate: bool) -> Ordering {
+    let ordering = if options.numeric {
+        order::compare_numeric(a, b)
+    } else if options.general {
+        order::compare_general(a, b)
+    } else if options.human {
+        order::compare_human(a, b)
+    } else if options.month {
+        order::compare_month(a, b)
+    } else if options.version {
+        order::compare_version(a, b)
+    } else {
+        compare_text(a, b, options, collate)
+    };
+
+    if options.reverse {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+// сравнение текста с -d, -i, -f и --collate, без них - побайтово
+fn compare_text(a: &[u8], b: &[u8], options: &KeyOptions, collate: bool) -> Ordering {
+    if !(options.dictionary || options.ignore_nonprinting || options.fold || collate) {
+        return a.cmp(b);
+    }
+
+    if collate {
+        let text = |bytes| {
+            text_units(bytes, options)
+                .map(|unit| match unit {
+                    Unit::Char(c) => c,
+                    Unit::Byte(_) => char::REPLACEMENT_CHARACTER,
+                })
+                .collect::<String>()
+        };
+        collate_text(&text(a), &text(b))
+    } else {
+        text_units(a, options).cmp(text_units(b, options))
+    }
+}
+
+// символ ключа или неверный байт UTF-8: байты после всех символов, между собой по значению
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Unit {
+    Char(char),
+    Byte(u8),
+}
+
+// символы ключа после -d или -i и -f; как в GNU sort, d важнее i: табуляция при -d остаётся,
+// неверные байты -d и -i отбрасывают как непечатные
+fn text_units<'a>(bytes: &'a [u8], options: &'a KeyOptions) -> impl Iterator<Item = Unit> + 'a {
+    bytes
+        .utf8_chunks()
+        .flat_map(|chunk| {
+            chunk
+                .valid()
+                .chars()
+                .map(Unit::Char)
+                .chain(chunk.invalid().iter().copied().map(Unit::Byte))
+        })
+        .filter(move |&unit| match unit {
+            Unit::Char(c) if options.dictionary => c.is_alphanumeric() || key::is_blank(c),
+            Unit::Char(c) if options.ignore_nonprinting => !c.is_control(),
+            Unit::Char(_) => true,
+            Unit::Byte(_) => !(options.dictionary || options.ignore_nonprinting),
+        })
+        .map(move |unit| match unit {
+            Unit::Char(c) if options.fold => Unit::Char(fold_char(c)),
+            unit => unit,
+        })
+}
+
+thread_local! {
+    // сравнение меняет внутренние буферы Collator, поэтому он свой у каждого потока
+    static COLLATOR: RefCell<Collator> = RefCell::new(Collator::default());
+}
+
+// сравнение по алгоритму Unicode: сначала буквы без диакритики (ё рядом с е, латиница
+// перед кириллицей), затем диакритика, затем регистр (строчные раньше)
+fn collate_text(a: &str, b: &str) -> Ordering {
+    COLLATOR.with(|collator| collator.borrow_mut().collate(a, b))
+}
+
+// символ в верхнем регистре для -f
+fn fold_char(c: char) -> char {
+    c.to_uppercase().next().unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::key::{KeyOptions, KeySpec};
+
+    // сортировка строк с ключами -k и глобальными модификаторами
+    fn sort(
+        lines: &[&str],
+        keys: &[&str],
+        global: KeyOptions,
+        separator: Option<char>,
+        stable: bool,
+    ) -> Vec<String> {
+        let keys = keys
+            .iter()
+            .map(|key| KeySpec::parse(key).unwrap())
+            .collect();
+        let config = Config::new(keys, global, separator, stable);
+        sort_lines(lines, &config)
+    }
+
+    // сортировка строк с готовыми настройками
+    fn sort_lines(lines: &[&str], config: &Config) -> Vec<String> {
+        let mut lines: Vec<_> = lines
+            .iter()
+            .map(|line| config.line(line.as_bytes().to_vec()))
+            .collect();
+
+        lines.sort_by(|a, b| config.compare(a, b));
+        lines
+            .into_iter()
+            .map(|line| String::from_utf8(line.text).unwrap())
+            .collect()
+    }
+
+    #[test]
+    // несколько ключей со своими модификаторами
+    fn test_multiple_keys() {
+        let lines = ["b 10 x", "a 9 y", "c 10 z", "d 9 w", "e -3 v"];
+        assert_eq!(
+            sort(
+                &lines,
+                &["2,2n", "1,1r"],
+                KeyOptions::default(),
+                None,
+                false
+            ),
+            ["e -3 v", "d 9 w", "a 9 y", "c 10 z", "b 10 x"]
+        );
+        assert_eq!(
+            sort(
+                &["x:2K", "y:1G", "z:512", "w:-1M"],
+                &["2h"],
+                KeyOptions::default(),
+                Some(':'),
+                false
+            ),
+            ["w:-1M", "z:512", "x:2K", "y:1G"]
+        );
+        assert_eq!(
+            sort(
+                &["1 MAR", "2 jan", "3 JAN"],
+                &["2M"],
+                KeyOptions::default(),
+                None,
+                false
+            ),
+            ["2 jan", "3 JAN", "1 MAR"]
+        );
+    }
+
+    #[test]
+    // равные ключи: без -s сравниваются строки целиком, с -s сохраняется порядок ввода
+    fn test_stable() {
+        let lines = ["b 1", "a 1", "c 0"];
+        let numeric = KeyOptions {
+            numeric: true,
+            ..KeyOptions::default()
+        };
+        assert_eq!(
+            sort(&lines, &["2"], numeric, None, false),
+            ["c 0", "a 1", "b 1"]
+        );
+        assert_eq!(
+            sort(&lines, &["2"], numeric, None, true),
+            ["c 0", "b 1", "a 1"]
+        );
+
+        let reverse = KeyOptions {
+            reverse: true,
+            ..numeric
+        };
+        assert_eq!(
+            sort(&lines, &["2"], reverse, None, false),
+            ["b 1", "a 1", "c 0"]
+        );
+        assert_eq!(
+            sort(&lines, &["2"], reverse, None, true),
+            ["b 1", "a 1", "c 0"]
+        );
+        assert_eq!(
+            sort(&lines, &["2,2r"], numeric, None, false),
+            ["a 1", "b 1", "c 0"]
+        );
+    }
+
+    #[test]
+    // глобальные модификаторы без -k и -f
+    fn test_whole_line() {
+        let fold = KeyOptions {
+            fold: true,
+            ..KeyOptions::default()
+        };
+        assert_eq!(
+            sort(&["b", "B", "a", "A"], &[], fold, None, false),
+            ["A", "a", "B", "b"]
+        );
+        assert_eq!(
+            sort(
+                &["b", "B", "a", "A"],
+                &[],
+                KeyOptions::default(),
+                None,
+                false
+            ),
+            ["A", "B", "a", "b"]
+        );
+    }
+
+    #[test]
+    // -d и -i отбрасывают символы только при сравнении, -f к ним добавляется
+    fn test_dictionary() {
+        let dictionary = KeyOptions {
+            dictionary: true,
+            ..KeyOptions::default()
+        };
+        assert_eq!(
+            sort(
+                &["b-2", "a_3", "(b)1", "ё\t1"],
+                &[],
+                dictionary,
+                None,
+                false
+            ),
+            ["a_3", "(b)1", "b-2", "ё\t1"]
+        );
+
+        let nonprinting = KeyOptions {
+            ignore_nonprinting: true,
+            fold: true,
+            ..KeyOptions::default()
+        };
+        assert_eq!(
+            sort(
+                &["b\x01", "\x02B", "a", "\x7fC"],
+                &[],
+                nonprinting,
+                None,
+                false
+            ),
+            ["a", "\x02B", "b\x01", "\x7fC"]
+        );
+    }
+
+    #[test]
+    // неверные байты UTF-8 идут после всех символов и не равны друг другу, -i их отбрасывает
+    fn test_invalid_utf8() {
+        let lines: [&[u8]; 4] = [b"\xff:z", b"\xfe\xff:a", "я".as_bytes(), b"A"];
+        let sorted = |options: KeyOptions| {
+            let config = Config::new(vec![], options, None, false);
+            let mut lines: Vec<_> = lines
+                .iter()
+                .map(|line| config.line(line.to_vec()))
+                .collect();
+            lines.sort_by(|a, b| config.compare(a, b));
+            lines.into_iter().map(|line| line.text).collect::<Vec<_>>()
+        };
+
+        let fold = KeyOptions {
+            fold: true,
+            ..KeyOptions::default()
+        };
+        assert_eq!(sorted(fold), [lines[3], lines[2], lines[1], lines[0]]);
+
+        let nonprinting = KeyOptions {
+            ignore_nonprinting: true,
+            ..KeyOptions::default()
+        };
+        assert_eq!(
+            sorted(nonprinting),
+            [lines[1], lines[0], lines[3], lines[2]]
+        );
+    }
+
+    #[test]
+    // по правилам Unicode ё сравнивается как е, регистр влияет только на равные иначе слова
+    fn test_collate() {
+        let words = [
+            "яблоко",
+            "Ёж",
+            "ель",
+            "Жук",
+            "ёлка",
+            "Еда",
+            "zebra",
+            "Apple",
+            "apple",
+        ];
+        let config = Config::new(vec![], KeyOptions::default(), None, false);
+        assert_eq!(
+            sort_lines(&words, &config),
+            [
+                "Apple",
+                "apple",
+                "zebra",
+                "Ёж",
+                "Еда",
+                "Жук",
+                "ель",
+                "яблоко",
+                "ёлка"
+            ]
+        );
+        assert_eq!(
+            sort_lines(&words, &config.collate(true)),
+            [
+                "apple",
+                "Apple",
+                "zebra",
+                "Еда",
+                "Ёж",
+                "ёлка",
+                "ель",
+                "Жук",
+                "яблоко"
+            ]
+        );
+
+        let keys = vec![KeySpec::parse("2").unwrap()];
+        let config = Config::new(keys, KeyOptions::default(), None, false).collate(true);
+        assert_eq!(
+            sort_lines(&["1 ежи", "2 Ёж", "3 ёж", "4 еж"], &config),
+            ["4 еж", "3 ёж", "2 Ёж", "1 ежи"]
+        );
+    }
+}
@@ -0,0 +1,426 @@
+//! внешняя сортировка: вход делится на серии в пределах бюджета памяти, серии сортируются
+//! параллельно и сбрасываются во временные файлы, затем сливаются через кучу
+use crate::compare::{Config, Line};
+use rayon::slice::ParallelSliceMut;
+use std::cmp::Ordering;
+use std::collections::binary_heap::{BinaryHeap, PeekMut};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::{mem, vec};
+
+// бюджет памяти по умолчанию
+pub const DEFAULT_BUFFER_SIZE: usize = 256 * 1024 * 1024;
+// серий, сливаемых за один проход, чтобы не упереться в лимит открытых файлов
+const MERGE_FAN_IN: usize = 64;
+
+// настройки внешней сортировки
+#[derive(Debug, Clone)]
+pub struct ExternalOptions {
+    // -S: сколько памяти занимают строки одной серии
+    pub buffer_size: usize,
+    // -T: каталог временных файлов, по умолчанию системный
+    pub temp_dir: Option<PathBuf>,
+}
+
+impl Default for ExternalOptions {
+    fn default() -> Self {
+        ExternalOptions {
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            temp_dir: None,
+        }
+    }
+}
+
+// разбор размера -S как в GNU sort: число с суффиксом b, K, M, G, T (без суффикса - K)
+pub fn parse_size(value: &str) -> Result<usize, String> {
+    let invalid = || format!("неверный размер буфера {:?}", value);
+    let digits_end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, suffix) = value.split_at(digits_end);
+
+    let number: usize = number.parse().map_err(|_| invalid())?;
+    let multiplier: usize = match suffix {
+        "b" => 1,
+        "" | "K" | "k" => 1 << 10,
+        "M" | "m" => 1 << 20,
+        "G" | "g" => 1 << 30,
+        "T" | "t" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+
+    number.checked_mul(multiplier).ok_or_else(invalid)
+}
+
+// чтение строки без завершающего \n, None - конец входа; байты как есть, без проверки UTF-8
+pub fn read_line<R: BufRead + ?Sized>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    if reader.read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+
+    if line.ends_with(b"\n") {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+// отсортированная серия для слияния: в памяти, во временном файле или готовый вход для -m
+pub enum Run {
+    Memory(vec::IntoIter<Line>),
+    Reader(Box<dyn BufRead>),
+}
+
+impl Run {
+    // серия из уже отсортированного входа
+    pub fn reader<R: BufRead + 'static>(reader: R) -> Self {
+        Run::Reader(Box::new(reader))
+    }
+
+    // следующая строка серии
+    fn next(&mut self, config: &Config) -> io::Result<Option<Line>> {
+        match self {
+            Run::Memory(lines) => Ok(lines.next()),
+            Run::Reader(reader) => Ok(read_line(reader)?.map(|text| config.line(text))),
+        }
+    }
+}
+
+// очередная строка серии в куче; при равных строках раньше идёт более ранняя серия,
+// поэтому слияние устойчиво и совпадает с сортировкой в памяти
+struct Head<'a> {
+    line: Line,
+    run: usize,
+    config: &'a Config,
+}
+
+impl Ord for Head<'_> {
+    // куча максимальная, поэтому порядок обратный
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.config
+            .compare(&self.line, &other.line)
+            .then(self.run.cmp(&other.run))
+            .reverse()
+    }
+}
+
+impl PartialOrd for Head<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head<'_> {}
+
+// запись строки с переводом строки
+fn write_line<W: Write>(output: &mut W, line: &Line) -> io::Result<()> {
+    output.write_all(&line.text)?;
+    output.write_all(b"\n")
+}
+
+// параллельная устойчивая сортировка серии
+fn sort_run(config: &Config, run: &mut [Line]) {
+    run.par_sort_by(|a, b| config.compare(a, b));
+}
+
+// временный файл без имени в каталоге: удаляется системой при закрытии, даже при ошибке
+pub fn temp_file(options: &ExternalOptions) -> io::Result<File> {
+    match &options.temp_dir {
+        Some(dir) => tempfile::tempfile_in(dir),
+        None => tempfile::tempfile(),
+    }
+}
+
+// сортировка серии и запись во временный файл, серия очищается
+fn spill(config: &Config, run: &mut Vec<Line>, options: &ExternalOptions) -> io::Result<File> {
+    sort_run(config, run);
+
+    let mut writer = BufWriter::new(temp_file(options)?);
+    for line in run.drain(..) {
+        write_line(&mut writer, &line)?;
+    }
+    rewind(writer)
+}
+
+// дописанный временный файл для чтения с начала
+pub fn rewind(writer: BufWriter<File>) -> io::Result<File> {
+    let mut file = writer.into_inner().map_err(|err| err.into_error())?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(file)
+}
+
+// k - путевое слияние отсортированных серий, unique - из равных строк выводится первая
+pub fn merge<W: Write>(
+    mut runs: Vec<Run>,
+    config: &Config,
+    unique: bool,
+    output: &mut W,
+) -> io::Result<()> {
+    let mut heap = BinaryHeap::with_capacity(runs.len());
+    for (index, run) in runs.iter_mut().enumerate() {
+        if let Some(line) = run.next(config)? {
+            heap.push(Head {
+                line,
+                run: index,
+                config,
+            });
+        }
+    }
+
+    let mut last: Option<Line> = None;
+    while let Some(mut head) = heap.peek_mut() {
+        let line = match runs[head.run].next(config)? {
+            Some(next) => mem::replace(&mut head.line, next),
+            None => PeekMut::pop(head).line,
+        };
+
+        if unique {
+            if last
+                .as_ref()
+                .is_some_and(|last| config.compare(last, &line) == Ordering::Equal)
+            {
+                continue;
+            }
+            write_line(output, &line)?;
+            last = Some(line);
+        } else {
+            write_line(output, &line)?;
+        }
+    }
+
+    Ok(())
+}
+
+// серия из временного файла
+fn file_run(file: File) -> Run {
+    Run::reader(BufReader::new(file))
+}
+
+// накопление строк в серии; если вход поместился в бюджет, временные файлы не создаются
+pub struct Sorter<'a> {
+    config: &'a Config,
+    options: &'a ExternalOptions,
+    files: Vec<File>,
+    run: Vec<Line>,
+    run_size: usize,
+}
+
+impl<'a> Sorter<'a> {
+    pub fn new(config: &'a Config, options: &'a ExternalOptions) -> Self {
+        Sorter {
+            config,
+            options,
+            files: Vec::new(),
+            run: Vec::new(),
+            run_size: 0,
+        }
+    }
+
+    // добавление строки, переполненная серия сбрасывается во временный файл
+    pub fn push(&mut self, text: Vec<u8>) -> io::Result<()> {
+        let line = self.config.line(text);
+        self.run_size += line.memory_size();
+        self.run.push(line);
+
+        if self.run_size >= self.options.buffer_size {
+            self.files
+                .push(spill(self.config, &mut self.run, self.options)?);
+            self.run_size = 0;
+        }
+        Ok(())
+    }
+
+    // отсортированные серии для слияния
+    pub fn finish(mut self) -> io::Result<Vec<Run>> {
+        // слияние в несколько проходов: соседние серии сливаются по MERGE_FAN_IN,
+        // результат встаёт на их место, так что порядок серий сохраняется
+        while self.files.len() >= MERGE_FAN_IN {
+            let group: Vec<Run> = self.files.drain(..MERGE_FAN_IN).map(file_run).collect();
+            let mut writer = BufWriter::new(temp_file(self.options)?);
+            merge(group, self.config, false, &mut writer)?;
+            self.files.insert(0, rewind(writer)?);
+        }
+
+        sort_run(self.config, &mut self.run);
+        let mut runs: Vec<Run> = self.files.into_iter().map(file_run).collect();
+        runs.push(Run::Memory(self.run.into_iter()));
+
+        Ok(runs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge, parse_size, read_line, ExternalOptions, Sorter};
+    use crate::compare::Config;
+    use crate::key::{KeyOptions, KeySpec};
+    use std::io::{self, BufRead, Write};
+
+    // сортировка входа в выход через серии
+    fn sort<R: BufRead, W: Write>(
+        mut input: R,
+        output: &mut W,
+        config: &Config,
+        options: &ExternalOptions,
+    ) -> io::Result<()> {
+        let mut sorter = Sorter::new(config, options);
+        while let Some(text) = read_line(&mut input)? {
+            sorter.push(text)?;
+        }
+
+        merge(sorter.finish()?, config, false, output)
+    }
+
+    // строки с повторяющимися ключами, чтобы была видна устойчивость
+    fn input() -> String {
+        (0..2000)
+            .map(|i| format!("{} {} ключ{}\n", i % 7, (i * 7919) % 101, i % 13))
+            .collect()
+    }
+
+    // сортировка в памяти для сравнения
+    fn sort_in_memory(input: &str, config: &Config) -> String {
+        let mut lines: Vec<_> = input
+            .lines()
+            .map(|line| config.line(line.as_bytes().to_vec()))
+            .collect();
+        lines.sort_by(|a, b| config.compare(a, b));
+        lines
+            .into_iter()
+            .map(|line| String::from_utf8(line.text).unwrap() + "\n")
+            .collect()
+    }
+
+    #[test]
+    // внешняя сортировка с маленьким бюджетом совпадает с сортировкой в памяти
+    fn test_matches_in_memory() {
+        let input = input();
+        let numeric = KeyOptions {
+            numeric: true,
+            ..KeyOptions::default()
+        };
+        let configs = [
+            Config::new(vec![], KeyOptions::default(), None, false),
+            Config::new(vec![KeySpec::parse("2,2n").unwrap()], numeric, None, true),
+            Config::new(
+                vec![
+                    KeySpec::parse("3r").unwrap(),
+                    KeySpec::parse("1,1").unwrap(),
+                ],
+                numeric,
+                None,
+                true,
+            ),
+        ];
+
+        for config in &configs {
+            // 1 КиБ - десятки серий, 100 байт - больше MERGE_FAN_IN серий и слияние в несколько проходов
+            for buffer_size in [100, 1024, usize::MAX] {
+                let options = ExternalOptions {
+                    buffer_size,
+                    temp_dir: None,
+                };
+                let mut output = Vec::new();
+                sort(input.as_bytes(), &mut output, config, &options).unwrap();
+                assert_eq!(
+                    String::from_utf8(output).unwrap(),
+                    sort_in_memory(&input, config)
+                );
+            }
+        }
+    }
+
+    #[test]
+    // байты, не являющиеся UTF-8, сортируются и выводятся как есть, в том числе через файлы
+    fn test_invalid_utf8() {
+        let input = b"b\n\xff\na\n\xfe x\n10 \xc3\n9\n";
+        let numeric = KeyOptions {
+            numeric: true,
+            ..KeyOptions::default()
+        };
+        let cases: [(Config, &[u8]); 2] = [
+            (
+                Config::new(vec![], KeyOptions::default(), None, false),
+                b"10 \xc3\n9\na\nb\n\xfe x\n\xff\n",
+            ),
+            (
+                Config::new(vec![], numeric, None, false),
+                b"a\nb\n\xfe x\n\xff\n9\n10 \xc3\n",
+            ),
+        ];
+
+        for (config, expected) in &cases {
+            for buffer_size in [10, usize::MAX] {
+                let options = ExternalOptions {
+                    buffer_size,
+                    temp_dir: None,
+                };
+                let mut output = Vec::new();
+                sort(&input[..], &mut output, config, &options).unwrap();
+                assert_eq!(output, *expected);
+            }
+        }
+    }
+
+    // выход, который ломается после нескольких строк
+    struct FailingWriter(usize);
+
+    impl Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.0 == 0 {
+                return Err(io::Error::other("диск заполнен"));
+            }
+            self.0 -= 1;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    // при ошибке записи во временном каталоге не остаётся файлов
+    fn test_cleanup_on_error() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let options = ExternalOptions {
+            buffer_size: 1024,
+            temp_dir: Some(temp_dir.path().to_path_buf()),
+        };
+        let config = Config::new(vec![], KeyOptions::default(), None, false);
+
+        let result = sort(
+            input().as_bytes(),
+            &mut FailingWriter(10),
+            &config,
+            &options,
+        );
+        assert_eq!(result.unwrap_err().to_string(), "диск заполнен");
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+
+        let options = ExternalOptions {
+            temp_dir: Some(temp_dir.path().join("нет")),
+            ..options
+        };
+        assert!(sort(input().as_bytes(), &mut Vec::new(), &config, &options).is_err());
+    }
+
+    #[test]
+    // размеры -S
+    fn test_parse_size() {
+        assert_eq!(parse_size("10"), Ok(10 << 10));
+        assert_eq!(parse_size("512b"), Ok(512));
+        assert_eq!(parse_size("2M"), Ok(2 << 20));
+        assert_eq!(parse_size("1G"), Ok(1 << 30));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("5X").is_err());
+        assert!(parse_size("50%").is_err());
+    }
+}
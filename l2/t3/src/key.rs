@@ -0,0 +1,331 @@
+//! ключи сортировки: разбор `-k F[.C][OPTS][,F[.C][OPTS]]` и выделение ключа из строки как в GNU sort.
+//! Строка - байты: символы считаются по UTF-8, каждый неверный байт - отдельный символ
+use std::ops::Range;
+
+// модификаторы сравнения ключа
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyOptions {
+    // n - по числовому значению
+    pub numeric: bool,
+    // g - по числу с плавающей точкой (1.5e3, inf)
+    pub general: bool,
+    // h - по числу с суффиксом размера (2K, 1G)
+    pub human: bool,
+    // M - по месяцам
+    pub month: bool,
+    // V - по номерам версий
+    pub version: bool,
+    // f - без учёта регистра
+    pub fold: bool,
+    // d - только пробелы, буквы и цифры
+    pub dictionary: bool,
+    // i - без управляющих символов
+    pub ignore_nonprinting: bool,
+    // r - в обратном порядке
+    pub reverse: bool,
+    // b у начала ключа - пропуск пробелов перед ним
+    pub skip_start_blanks: bool,
+    // b у конца ключа - пропуск пробелов перед его последним полем
+    pub skip_end_blanks: bool,
+}
+
+impl KeyOptions {
+    // модификатор по букве, end - буква стоит после запятой (важно только для b)
+    pub fn set(&mut self, flag: char, end: bool) -> Result<(), String> {
+        match flag {
+            'n' => self.numeric = true,
+            'g' => self.general = true,
+            'h' => self.human = true,
+            'M' => self.month = true,
+            'V' => self.version = true,
+            'f' => self.fold = true,
+            'd' => self.dictionary = true,
+            'i' => self.ignore_nonprinting = true,
+            'r' => self.reverse = true,
+            'b' if end => self.skip_end_blanks = true,
+            'b' => self.skip_start_blanks = true,
+            _ => return Err(format!("неизвестный модификатор ключа {:?}", flag)),
+        }
+
+        if [
+            self.numeric,
+            self.general,
+            self.human,
+            self.month,
+            self.version,
+            // d и i меняют сравнение текста, с числами и версиями не сочетаются
+            self.dictionary || self.ignore_nonprinting,
+        ]
+        .iter()
+        .filter(|&&set| set)
+        .count()
+            > 1
+        {
+            return Err("несколько способов сортировки задано для одного ключа".to_string());
+        }
+        Ok(())
+    }
+
+    // не задано ни одного модификатора - ключ берёт глобальные
+    pub fn is_empty(&self) -> bool {
+        *self == KeyOptions::default()
+    }
+}
+
+// позиция в строке: поле и символ с 1, символ 0 у конца ключа - до конца поля
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub field: usize,
+    pub char: usize,
+}
+
+// ключ сортировки
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeySpec {
+    pub start: Position,
+    // None - до конца строки
+    pub end: Option<Position>,
+    pub options: KeyOptions,
+}
+
+// пробельные символы, разделяющие поля без -t
+pub fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+// разбор "F[.C]" и букв модификаторов после него
+fn parse_position(spec: &str, end: bool, options: &mut KeyOptions) -> Result<Position, String> {
+    let digits_end = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let invalid = || format!("неверное описание ключа {:?}", spec);
+
+    let (field, rest) = spec.split_at(digits_end(spec));
+    let field: usize = field.parse().map_err(|_| invalid())?;
+
+    let (char, rest) = match rest.strip_prefix('.') {
+        Some(rest) => {
+            let (char, rest) = rest.split_at(digits_end(rest));
+            (char.parse().map_err(|_| invalid())?, rest)
+        }
+        None => (if end { 0 } else { 1 }, rest),
+    };
+
+    // поле всегда с 1, символ начала тоже, у конца допустим 0
+    if field == 0 || (!end && char == 0) {
+        return Err(invalid());
+    }
+    for flag in rest.chars() {
+        options.set(flag, end)?;
+    }
+
+    Ok(Position { field, char })
+}
+
+impl KeySpec {
+    // вся строка как ключ
+    pub fn whole_line(options: KeyOptions) -> Self {
+        KeySpec {
+            start: Position { field: 1, char: 1 },
+            end: None,
+            options,
+        }
+    }
+
+    // разбор значения -k
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut options = KeyOptions::default();
+        let (start, end) = match spec.split_once(',') {
+            Some((start, end)) => (start, Some(end)),
+            None => (spec, None),
+        };
+
+        let start = parse_position(start, false, &mut options)?;
+        let end = end
+            .map(|end| parse_position(end, true, &mut options))
+            .transpose()?;
+
+        Ok(KeySpec {
+            start,
+            end,
+            options,
+        })
+    }
+
+    // байтовый диапазон ключа в строке, пустой - если конец ключа раньше начала
+    pub fn range(&self, line: &[u8], separator: Option<char>) -> Range<usize> {
+        let start = self.start_offset(line, separator);
+        let end = match self.end {
+            Some(end) => self.end_offset(line, separator, end),
+            None => line.len(),
+        };
+
+        start..end.max(start)
+    }
+
+    // начало ключа: пропуск field - 1 полей, затем char - 1 символов
+    fn start_offset(&self, line: &[u8], separator: Option<char>) -> usize {
+        let mut offset = skip_fields(line, 0, self.start.field - 1, separator, false);
+        if self.options.skip_start_blanks {
+            offset = skip_blanks(line, offset);
+        }
+        skip_chars(line, offset, self.start.char - 1)
+    }
+
+    // конец ключа: конец поля при char == 0, иначе char символов от начала поля
+    fn end_offset(&self, line: &[u8], separator: Option<char>, end: Position) -> usize {
+        if end.char == 0 {
+            return skip_fields(line, 0, end.field, separator, true);
+        }
+
+        let mut offset = skip_fields(line, 0, end.field - 1, separator, false);
+        if self.options.skip_end_blanks {
+            offset = skip_blanks(line, offset);
+        }
+        skip_chars(line, offset, end.char)
+    }
+}
+
+// символы строки с offset и их смещения, None - неверный байт UTF-8
+fn chars_from(line: &[u8], offset: usize) -> impl Iterator<Item = (usize, Option<char>)> + '_ {
+    let mut start = offset;
+    line[offset..].utf8_chunks().flat_map(move |chunk| {
+        let valid_start = start;
+        let invalid_start = valid_start + chunk.valid().len();
+        start = invalid_start + chunk.invalid().len();
+        chunk
+            .valid()
+            .char_indices()
+            .map(move |(index, c)| (valid_start + index, Some(c)))
+            .chain((invalid_start..start).map(|index| (index, None)))
+    })
+}
+
+// смещение первого символа с offset, подходящего под условие, или конец строки
+fn find_char(line: &[u8], offset: usize, matches: impl Fn(Option<char>) -> bool) -> usize {
+    chars_from(line, offset)
+        .find(|&(_, c)| matches(c))
+        .map_or(line.len(), |(found, _)| found)
+}
+
+// пропуск fields полей; без -t поле начинается с пробелов перед ним, с -t разделитель
+// пропускается, кроме последнего при to_field_end (ключ заканчивается перед разделителем)
+fn skip_fields(
+    line: &[u8],
+    mut offset: usize,
+    fields: usize,
+    separator: Option<char>,
+    to_field_end: bool,
+) -> usize {
+    let mut buf = [0; 4];
+    let separator = separator.map(|separator| separator.encode_utf8(&mut buf).as_bytes());
+
+    for skipped in 1..=fields {
+        if offset >= line.len() {
+            break;
+        }
+
+        match separator {
+            Some(separator) => {
+                offset = line[offset..]
+                    .windows(separator.len())
+                    .position(|window| window == separator)
+                    .map_or(line.len(), |found| offset + found);
+                if offset < line.len() && !(to_field_end && skipped == fields) {
+                    offset += separator.len();
+                }
+            }
+            None => {
+                offset = skip_blanks(line, offset);
+                offset = find_char(line, offset, |c| c.is_some_and(is_blank));
+            }
+        }
+    }
+
+    offset
+}
+
+// пропуск пробелов
+fn skip_blanks(line: &[u8], offset: usize) -> usize {
+    find_char(line, offset, |c| !c.is_some_and(is_blank))
+}
+
+// сдвиг на chars символов, не дальше конца строки
+fn skip_chars(line: &[u8], offset: usize, chars: usize) -> usize {
+    chars_from(line, offset)
+        .nth(chars)
+        .map_or(line.len(), |(found, _)| found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyOptions, KeySpec, Position};
+
+    // текст ключа в строке
+    fn key<'a>(spec: &str, line: &'a str, separator: Option<char>) -> &'a str {
+        &line[KeySpec::parse(spec)
+            .unwrap()
+            .range(line.as_bytes(), separator)]
+    }
+
+    #[test]
+    // разбор описаний ключей и модификаторов
+    fn test_parse() {
+        let spec = KeySpec::parse("2.3nr,4b").unwrap();
+        assert_eq!(spec.start, Position { field: 2, char: 3 });
+        assert_eq!(spec.end, Some(Position { field: 4, char: 0 }));
+        assert_eq!(
+            spec.options,
+            KeyOptions {
+                numeric: true,
+                reverse: true,
+                skip_end_blanks: true,
+                ..KeyOptions::default()
+            }
+        );
+        assert_eq!(KeySpec::parse("1").unwrap().end, None);
+        assert!(KeySpec::parse("1dfi,2b").is_ok());
+
+        for invalid in [
+            "", "0", "1.0", "a", "1,x", "1z", "1nM", "1gV", "2,3hn", "1,2.3.4", "1dn", "1,1iV",
+        ] {
+            assert!(KeySpec::parse(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    // поля без -t включают пробелы перед собой, как в GNU sort
+    fn test_blank_fields() {
+        let line = "  ab  cd\tёжик 5";
+        assert_eq!(key("1,1", line, None), "  ab");
+        assert_eq!(key("2,2", line, None), "  cd");
+        assert_eq!(key("2b,2", line, None), "cd");
+        assert_eq!(key("3", line, None), "\tёжик 5");
+        assert_eq!(key("3.2,3.3", line, None), "ёж");
+        assert_eq!(key("3.2b,3.3b", line, None), "жи");
+        assert_eq!(key("5,5", line, None), "");
+        assert_eq!(key("2,1", line, None), "");
+    }
+
+    #[test]
+    // поля через -t, в том числе пустые
+    fn test_separator_fields() {
+        let line = "a:;b::ё:;c";
+        assert_eq!(key("2,2", line, Some(':')), ";b");
+        assert_eq!(key("3,3", line, Some(':')), "");
+        assert_eq!(key("4,4", line, Some(':')), "ё");
+        assert_eq!(key("2,4", line, Some(':')), ";b::ё");
+        assert_eq!(key("4", line, Some(':')), "ё:;c");
+        assert_eq!(key("2,3", "a:b:c", Some(':')), "b:c");
+        assert_eq!(key("2,2", "a;;b;;c", Some(';')), "");
+        assert_eq!(key("7,7", line, Some(':')), "");
+    }
+
+    #[test]
+    // неверный байт UTF-8 - отдельный символ, не пробел и не разделитель
+    fn test_invalid_utf8() {
+        let line = b"a \xff\xfeb c";
+        let range = |spec: &str| KeySpec::parse(spec).unwrap().range(line, None);
+        assert_eq!(&line[range("2,2")], b" \xff\xfeb");
+        assert_eq!(&line[range("2.2b,2.3b")], b"\xfeb");
+        assert_eq!(&line[range("3")], b" c");
+    }
+}
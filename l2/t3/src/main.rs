@@ -1,320 +1,422 @@
-use std::collections::{HashMap, HashSet};
+//! сортировка строк файлов или stdin, совместимая с GNU sort: ключи, порядки сравнения,
+//! проверка порядка, слияние готовых файлов и внешняя сортировка файлов больше памяти;
+//! с --collate текст сравнивается по правилам Unicode, а не по байтам
+//!
+//! Пример использования: t3 -t ' ' -k 3M -k 2,2nr -S 100M -o sorted.txt input.txt
+mod compare;
+mod external;
+mod key;
+mod order;
+
+use args::{Arg, Opt, Parser};
+use compare::{Config, Line};
+use external::{ExternalOptions, Run, Sorter};
+use key::{KeyOptions, KeySpec};
+use std::cmp::Ordering;
 use std::env;
-use std::fs::{read_to_string, File};
-use std::io::Write;
-
-/// Колонки в аргументах 1-индексированные =)
-///
-/// Имплементированные аргументы: "-k", "-n", "-r", "-u", "-M", "-c"
-///
-/// Пример использования: t3.exe -M -k 3 -c -r input.txt output
-
-
-// записывает строки из файла в вектор
-fn read_lines_from_file(filename: &str) -> Vec<String> {
-    let mut file_lines = Vec::new();
-    for line in read_to_string(filename)
-        .expect("не удалось записать строку из файла в вектор")
-        .lines()
-    {
-        file_lines.push(line.to_string())
-    }
-    file_lines
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Write};
+use std::process::exit;
+
+const USAGE: &str = "использование: t3 [ПАРАМЕТРЫ] [ФАЙЛ]...
+сортирует строки файлов (без файлов или для \"-\" - stdin) и выводит в stdout
+    -b, --ignore-leading-blanks    пропускать пробелы в начале ключей
+    -d, --dictionary-order         учитывать только пробелы, буквы и цифры
+    -f, --ignore-case              без учёта регистра
+    -i, --ignore-nonprinting       пропускать управляющие символы
+        --collate                  сравнивать текст по правилам Unicode, а не по байтам:
+                                   ё рядом с е, строчные и прописные вместе
+    -g, --general-numeric-sort     по числу с плавающей точкой
+    -h, --human-numeric-sort       по размеру с суффиксом (2K, 1G)
+    -M, --month-sort               по месяцам
+    -n, --numeric-sort             по числу
+    -V, --version-sort             по номерам версий
+    -r, --reverse                  в обратном порядке
+    -c, --check                    проверить порядок, первое нарушение - в stderr, код выхода 1
+    -C, --check=quiet              то же без сообщения
+    -k, --key=F[.C][OPTS][,F[.C][OPTS]]
+                                   ключ сортировки, можно несколько
+    -m, --merge                    слить уже отсортированные файлы
+    -o, --output=ФАЙЛ              вывод в файл, он может быть и входным
+    -s, --stable                   не сравнивать строки целиком при равных ключах
+    -S, --buffer-size=РАЗМЕР       память на серию (b, K, M, G, T), больший вход - через временные файлы
+    -T, --temporary-directory=КАТАЛОГ
+                                   каталог временных файлов
+    -t, --field-separator=СИМВОЛ   разделитель полей
+    -u, --unique                   из строк с равными ключами только первая
+        --help                     эта справка";
+
+// параметры; -C - то же, что --check=quiet, значение --check пишется только через '='
+const OPTIONS: [Opt; 23] = [
+    Opt::both('b', "ignore-leading-blanks"),
+    Opt::both('d', "dictionary-order"),
+    Opt::both('f', "ignore-case"),
+    Opt::both('i', "ignore-nonprinting"),
+    Opt::both('g', "general-numeric-sort"),
+    Opt::both('h', "human-numeric-sort"),
+    Opt::both('M', "month-sort"),
+    Opt::both('n', "numeric-sort"),
+    Opt::both('V', "version-sort"),
+    Opt::both('r', "reverse"),
+    Opt::both('c', "check").optional_value(),
+    Opt::short('C'),
+    Opt::both('k', "key").value(),
+    Opt::both('m', "merge"),
+    Opt::both('o', "output").value(),
+    Opt::both('s', "stable"),
+    Opt::both('S', "buffer-size").value(),
+    Opt::both('T', "temporary-directory").value(),
+    Opt::both('t', "field-separator").value(),
+    Opt::both('u', "unique"),
+    Opt::long("collate"),
+    Opt::long("help"),
+    Opt::long("version"),
+];
+
+// короткие параметры - модификаторы сравнения
+const ORDERING_FLAGS: &str = "bdfghiMnrV";
+
+// режим проверки порядка
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Check {
+    // -c: первое нарушение в stderr
+    Diagnose,
+    // -C: только код выхода
+    Quiet,
 }
 
-// записывает отсортированные строки из вектора в файл
-fn write_lines_to_file(filename: &str, lines: Vec<String>) {
-    let mut file = File::create(format!("{}.txt", filename)).expect("не удалось создать файл");
+// разобранные аргументы
+#[derive(Debug)]
+struct Options {
+    config: Config,
+    external: ExternalOptions,
+    // входные файлы, "-" - stdin
+    files: Vec<String>,
+    // -o, по умолчанию stdout
+    output: Option<String>,
+    unique: bool,
+    check: Option<Check>,
+    merge: bool,
+}
 
-    for line in &lines {
-        writeln!(file, "{}", line).expect("ошибка при записи строки в файл");
-    }
+// параметры по мере разбора, Config строится в конце, когда известны глобальные модификаторы
+#[derive(Default)]
+struct Parsed {
+    keys: Vec<KeySpec>,
+    global: KeyOptions,
+    separator: Option<char>,
+    stable: bool,
+    external: ExternalOptions,
+    output: Option<String>,
+    unique: bool,
+    check: Option<Check>,
+    merge: bool,
+    collate: bool,
 }
 
-// сортировка по числовому значению
-fn sort_by_numbers(
-    lines_to_sort: &mut [String],
-    column_to_sort_by: usize,
-    reversed: bool,
-    check_sorted: bool,
-) {
-    // проверка отсортированы ли данные
-    if check_sorted
-        && lines_to_sort.windows(2).all(|pair| {
-        let a_columns: Vec<&str> = pair[0].split_whitespace().collect();
-        let b_columns: Vec<&str> = pair[1].split_whitespace().collect();
-
-        let num1 = a_columns[column_to_sort_by].parse::<i32>()
-            .expect("невозможно конвертировать текст в колонке в число");
-        let num2 = b_columns[column_to_sort_by].parse::<i32>()
-            .expect("невозможно конвертировать текст в колонке в число");
-
-        if reversed {
-            num1 >= num2
-        } else {
-            num1 <= num2
+impl Parsed {
+    // применение короткого параметра
+    fn apply(&mut self, flag: char, value: Option<String>) -> Result<(), String> {
+        let value = value.unwrap_or_default();
+        match flag {
+            'k' => self.keys.push(KeySpec::parse(&value)?),
+            't' => {
+                let mut chars = value.chars();
+                self.separator = match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(c),
+                    _ => {
+                        return Err(format!(
+                            "разделитель должен быть одним символом: {:?}",
+                            value
+                        ))
+                    }
+                };
+            }
+            'o' => self.output = Some(value),
+            'S' => self.external.buffer_size = external::parse_size(&value)?,
+            'T' => self.external.temp_dir = Some(value.into()),
+            's' => self.stable = true,
+            'u' => self.unique = true,
+            'm' => self.merge = true,
+            'c' => self.check = Some(Check::Diagnose),
+            'C' => self.check = Some(Check::Quiet),
+            _ if ORDERING_FLAGS.contains(flag) => self.global.set(flag, false)?,
+            // неизвестные параметры отсеивает Parser
+            _ => {}
         }
-    })
-    {
-        println!("колонки уже отсортированы по числовому значению");
-        return;
-    };
-
-    // сортировка по числовому значению
-    lines_to_sort.sort_by(|a, b| {
-        let a_columns: Vec<&str> = a.split_whitespace().collect();
-        let b_columns: Vec<&str> = b.split_whitespace().collect();
-
-        let num1 = a_columns[column_to_sort_by].parse::<i32>()
-            .expect("невозможно конвертировать текст в колонке в число");
-        let num2 = b_columns[column_to_sort_by].parse::<i32>()
-            .expect("невозможно конвертировать текст в колонке в число");
-
-        num1.cmp(&num2)
-    });
-
-    // если нужна сортировка в обратном порядке
-    if reversed {
-        lines_to_sort.reverse()
+        Ok(())
     }
 }
 
-// сортировка по тексту
-fn sort_by_text(
-    lines_to_sort: &mut [String],
-    column_to_sort_by: usize,
-    reversed: bool,
-    check_sorted: bool,
-) {
-    // проверка отсортированы ли данные
-    if check_sorted
-        && lines_to_sort.windows(2).all(|pair| {
-            let a_columns: Vec<&str> = pair[0].split_whitespace().collect();
-            let b_columns: Vec<&str> = pair[1].split_whitespace().collect();
-
-            if reversed {
-                a_columns[column_to_sort_by] >= b_columns[column_to_sort_by]
-            } else {
-                a_columns[column_to_sort_by] <= b_columns[column_to_sort_by]
+// разбор аргументов командной строки (без имени программы)
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut parsed = Parsed::default();
+    let mut files = Vec::new();
+
+    for arg in Parser::new(&OPTIONS, args) {
+        match arg? {
+            Arg::Short('c', Some(value)) => match value.as_str() {
+                "diagnose-first" => parsed.apply('c', None)?,
+                "quiet" | "silent" => parsed.apply('C', None)?,
+                _ => return Err(format!("неверное значение для '--check': '{}'", value)),
+            },
+            Arg::Short(flag, value) => parsed.apply(flag, value)?,
+            // только длинный параметр, у GNU sort его нет
+            Arg::Long("collate", _) => parsed.collate = true,
+            Arg::Long("version", _) => {
+                println!("t3 {}", env!("CARGO_PKG_VERSION"));
+                exit(0);
             }
-        })
-    {
-        println!("колонки уже отсортированы по тексту");
-        return;
-    };
+            Arg::Long("help", _) => {
+                println!("{}", USAGE);
+                exit(0);
+            }
+            Arg::Long(..) => {}
+            Arg::Operand(file) => files.push(file),
+        }
+    }
 
-    // сортировка по месяцам
-    lines_to_sort.sort_by(|a, b| {
-        let a_columns: Vec<&str> = a.split_whitespace().collect();
-        let b_columns: Vec<&str> = b.split_whitespace().collect();
+    if files.is_empty() {
+        files.push("-".to_string());
+    }
+    if parsed.check.is_some() && files.len() > 1 {
+        return Err(format!(
+            "лишний операнд '{}' при проверке порядка",
+            files[1]
+        ));
+    }
 
-        a_columns[column_to_sort_by].cmp(b_columns[column_to_sort_by])
-    });
+    // b в глобальных модификаторах относится и к началу, и к концу ключа
+    parsed.global.skip_end_blanks = parsed.global.skip_start_blanks;
 
-    // если нужна сортировка в обратном порядке
-    if reversed {
-        lines_to_sort.reverse()
-    }
+    Ok(Options {
+        // при -u равные по ключам строки не различаются сравнением целиком
+        config: Config::new(
+            parsed.keys,
+            parsed.global,
+            parsed.separator,
+            parsed.stable || parsed.unique,
+        )
+        .collate(parsed.collate),
+        external: parsed.external,
+        files,
+        output: parsed.output,
+        unique: parsed.unique,
+        check: parsed.check,
+        merge: parsed.merge,
+    })
 }
 
-// сортировка по месяцам
-fn sort_by_month(
-    lines_to_sort: &mut [String],
-    column_to_sort_by: usize,
-    reversed: bool,
-    check_sorted: bool,
-) {
-    // возможные месяцы
-    let possible_months: HashMap<&str, usize> = HashMap::from([
-        ("JAN", 1),
-        ("FEB", 2),
-        ("MAR", 3),
-        ("APR", 4),
-        ("MAY", 5),
-        ("JUN", 6),
-        ("JUL", 7),
-        ("AUG", 8),
-        ("SEP", 9),
-        ("OCT", 10),
-        ("NOV", 11),
-        ("DEC", 12),
-    ]);
-
-    // проверка отсортированы ли данные
-    if check_sorted
-        && lines_to_sort.windows(2).all(|pair| {
-            let a_columns: Vec<&str> = pair[0].split_whitespace().collect();
-            let b_columns: Vec<&str> = pair[1].split_whitespace().collect();
-
-            let mapped_month_one = possible_months
-                .get(a_columns[column_to_sort_by])
-                .expect("неверный формат месяца");
-            let mapped_month_two = possible_months
-                .get(b_columns[column_to_sort_by])
-                .expect("неверный формат месяца");
-
-            if reversed {
-                mapped_month_one >= mapped_month_two
-            } else {
-                mapped_month_one <= mapped_month_two
-            }
-        })
-    {
-        println!("колонки уже отсортированы по месяцам");
-        return;
-    };
+// открытие входа, "-" - stdin
+fn open_input(name: &str) -> Result<Box<dyn BufRead>, String> {
+    if name == "-" {
+        return Ok(Box::new(io::stdin().lock()));
+    }
 
-    // сортировка по месяцам
-    lines_to_sort.sort_by(|a, b| {
-        let a_columns: Vec<&str> = a.split_whitespace().collect();
-        let b_columns: Vec<&str> = b.split_whitespace().collect();
+    File::open(name)
+        .map(|file| Box::new(BufReader::new(file)) as Box<dyn BufRead>)
+        .map_err(|err| format!("не удалось открыть {}: {}", name, args::io_error(&err)))
+}
 
-        let mapped_month_one = possible_months
-            .get(a_columns[column_to_sort_by])
-            .expect("неверный формат месяца");
-        let mapped_month_two = possible_months
-            .get(b_columns[column_to_sort_by])
-            .expect("неверный формат месяца");
+// первая строка не по порядку: номер с 1 и текст; при -u равные строки тоже нарушение
+fn check<R: BufRead + ?Sized>(
+    input: &mut R,
+    config: &Config,
+    unique: bool,
+) -> io::Result<Option<(usize, Vec<u8>)>> {
+    let mut previous: Option<Line> = None;
+    let mut number = 0;
+
+    while let Some(text) = external::read_line(input)? {
+        number += 1;
+        let line = config.line(text);
+        if let Some(previous) = &previous {
+            let ordering = config.compare(previous, &line);
+            if ordering == Ordering::Greater || (unique && ordering == Ordering::Equal) {
+                return Ok(Some((number, line.text)));
+            }
+        }
+        previous = Some(line);
+    }
 
-        mapped_month_one.cmp(mapped_month_two)
-    });
+    Ok(None)
+}
 
-    // если нужна сортировка в обратном порядке
-    if reversed {
-        lines_to_sort.reverse()
+// совпадает ли выходной файл с входным
+fn is_same_file(input: &str, output: Option<&str>) -> bool {
+    match (fs::canonicalize(input), output.map(fs::canonicalize)) {
+        (Ok(input), Some(Ok(output))) => input == output,
+        _ => false,
     }
 }
 
-// записывает в вектор и проверяет входные агрументы
-fn parse_args() -> (usize, String, String, String, bool, bool) {
-    // возможные аргументы
-    let possible_arguments: HashSet<&str> =
-        HashSet::from(["-k", "-n", "-r", "-u", "-M", "-c"]);
-
-    // аргументы командной строки
-    let args: Vec<String> = env::args().collect();
+// текст ошибки чтения входа
+fn read_error(name: &str, err: io::Error) -> String {
+    format!("ошибка чтения {}: {}", name, args::io_error(&err))
+}
 
-    // обработка аргументов
-    if args.len() < 3 {
-        panic!("недостаточное количество аргументов")
+// серии для слияния: входы как есть при -m, иначе отсортированные строки всех входов
+fn read_runs(options: &Options) -> Result<Vec<Run>, String> {
+    let config = &options.config;
+
+    if options.merge {
+        let mut runs = Vec::new();
+        for name in &options.files {
+            let mut input = open_input(name)?;
+            // вход, который будет перезаписан выводом, сначала копируется во временный файл
+            if name != "-" && is_same_file(name, options.output.as_deref()) {
+                let mut copy = BufWriter::new(
+                    external::temp_file(&options.external).map_err(|err| read_error(name, err))?,
+                );
+                io::copy(&mut input, &mut copy).map_err(|err| read_error(name, err))?;
+                let copy = external::rewind(copy).map_err(|err| read_error(name, err))?;
+                input = Box::new(BufReader::new(copy));
+            }
+            runs.push(Run::Reader(input));
+        }
+        return Ok(runs);
     }
 
-    // имя входного файла (предпоследний аргумент)
-    let output_file_name = args.last().unwrap().to_owned();
-    // имя выходного файла (последний аргумент)
-    let input_file_name = args[args.len() - 2].to_owned();
-
-    // массив для обработанных аргументов
-    let mut command_arguments = HashSet::new();
-    // колонка по которой сортировать > 0
-    let mut column_to_sort_by: usize = 0;
-    // в каком порядке сортировать
-    let mut reversed: bool = false;
-    // проверять отсортирован ли ввод
-    let mut check_sorted: bool = false;
-    // по каком типу сортировать
-    let mut mode: String = String::new();
-
-    // проверка и парсинг аргументов
-    let mut i = 1;
-    while i < args.len() - 2 {
-        // если аргумент не присутсвует в сете возможных
-        if !possible_arguments.contains(args[i].as_str()) {
-            panic!("неверный аргумент {:?}", args[i]);
+    let mut sorter = Sorter::new(config, &options.external);
+    for name in &options.files {
+        let mut input = open_input(name)?;
+        while let Some(text) =
+            external::read_line(&mut input).map_err(|err| read_error(name, err))?
+        {
+            sorter.push(text).map_err(|err| {
+                format!("ошибка записи временного файла: {}", args::io_error(&err))
+            })?;
         }
+    }
+    sorter
+        .finish()
+        .map_err(|err| format!("ошибка записи временного файла: {}", args::io_error(&err)))
+}
 
-        // если аргумент повторный
-        if command_arguments.contains(args[i].as_str()) {
-            panic!("повторный аргумент {:?}", args[i]);
+// сортировка, слияние или проверка; Ok(false) - вход не упорядочен
+fn run(options: &Options) -> Result<bool, String> {
+    if let Some(mode) = options.check {
+        let name = &options.files[0];
+        let disorder = check(&mut open_input(name)?, &options.config, options.unique)
+            .map_err(|err| read_error(name, err))?;
+
+        if let (Some((number, line)), Check::Diagnose) = (&disorder, mode) {
+            eprintln!(
+                "t3: {}:{}: нарушение порядка: {}",
+                name,
+                number,
+                String::from_utf8_lossy(line)
+            );
         }
+        return Ok(disorder.is_none());
+    }
 
-        // аргумент колонки для сортировки
-        if args[i] == "-k" {
-            column_to_sort_by = args[i + 1]
-                .parse::<usize>()
-                .expect("неверный номер колонки для сортировки");
-            i += 2;
-            continue;
-        }
+    // вход читается целиком до открытия вывода, поэтому -o может указывать на входной файл
+    let runs = read_runs(options)?;
+    let output: Box<dyn Write> = match &options.output {
+        Some(name) => Box::new(
+            File::create(name)
+                .map_err(|err| format!("не удалось создать {}: {}", name, args::io_error(&err)))?,
+        ),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut output = BufWriter::new(output);
+
+    let result = external::merge(runs, &options.config, options.unique, &mut output)
+        .and_then(|_| output.flush());
+    match result {
+        // вывод закрыт читателем (t3 | head)
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => Ok(true),
+        result => result
+            .map(|_| true)
+            .map_err(|err| format!("ошибка сортировки: {}", args::io_error(&err))),
+    }
+}
 
-        // возиожные варианты сортировки - по умолчанию - по текстовому значению
-        if args[i] == "-M" || args[i] == "-n" {
-            if mode.is_empty() {
-                mode = args[i].to_owned();
-            } else {
-                panic!("несколько способов сортировки задано в аргументах")
-            }
-        }
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
 
-        // навправление сортировки
-        if args[i] == "-r" {
-            reversed = true;
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("t3: {}\nподробнее: t3 --help", err);
+            exit(2);
         }
+    };
 
-        // проверять ли отсортированы ли строчки
-        if args[i] == "-c" {
-            check_sorted = true;
+    match run(&options) {
+        Ok(true) => {}
+        Ok(false) => exit(1),
+        Err(err) => {
+            eprintln!("t3: {}", err);
+            exit(2);
         }
-
-        command_arguments.insert(args[i].as_str());
-        i += 1
     }
+}
 
-    // если не была задана колонка для сортировки
-    if column_to_sort_by == 0 {
-        panic!(
-            "не задана или неверна колонка для сортировки: {}",
-            column_to_sort_by
-        )
-    }
+#[cfg(test)]
+mod tests {
+    use super::{check, parse_args, Check};
 
-    // -1 в колонке в связи с 0-индексированием =)
-    (
-        column_to_sort_by - 1,
-        input_file_name,
-        output_file_name,
-        mode,
-        reversed,
-        check_sorted,
-    )
-}
+    // разбор строки аргументов
+    fn parse(args: &str) -> Result<super::Options, String> {
+        let args: Vec<String> = args.split_whitespace().map(str::to_string).collect();
+        parse_args(&args)
+    }
 
-fn main() {
-    // чтение аргументов командной строки
-    let (
-        column_to_sort_by,
-        input_file_name,
-        output_file_name,
-        mode,
-        reversed,
-        check_sorted,
-    ) = parse_args();
-    // чтение колонок для сортировки
-    let mut lines_to_sort = read_lines_from_file(&input_file_name);
-
-    // сортировка в зависимости от вида
-    match mode.as_str()
-    {
-        "-n" => sort_by_numbers(
-            &mut lines_to_sort,
-            column_to_sort_by,
-            reversed,
-            check_sorted,
-        ),
-        "-M" => sort_by_month(
-            &mut lines_to_sort,
-            column_to_sort_by,
-            reversed,
-            check_sorted,
-        ),
-        _ => sort_by_text(
-            &mut lines_to_sort,
-            column_to_sort_by,
-            reversed,
-            check_sorted,
-        )
+    #[test]
+    // короткие, слитные, длинные и сокращённые параметры
+    fn test_parse_args() {
+        let options = parse("-nru -k2,2 -t: -o out.txt --buf=1M a.txt - -- -b").unwrap();
+        assert_eq!(options.files, ["a.txt", "-", "-b"]);
+        assert_eq!(options.output.as_deref(), Some("out.txt"));
+        assert!(options.unique);
+        assert_eq!(options.external.buffer_size, 1 << 20);
+
+        let options = parse("-mk 1n --key 2 -ob").unwrap();
+        assert!(options.merge);
+        assert_eq!(options.output.as_deref(), Some("b"));
+        assert_eq!(options.files, ["-"]);
+
+        assert_eq!(parse("--check=quiet").unwrap().check, Some(Check::Quiet));
+        assert_eq!(parse("--ch a").unwrap().check, Some(Check::Diagnose));
+
+        for invalid in [
+            "-x",
+            "--nope",
+            "--m",
+            "-k",
+            "-k0",
+            "-t ab",
+            "-nM",
+            "--unique=1",
+            "-c a b",
+            "-l",
+            "-dn",
+            "--collate=1",
+        ] {
+            assert!(parse(invalid).is_err(), "{:?}", invalid);
+        }
     }
 
-    // запись в файл
-    write_lines_to_file(&output_file_name, lines_to_sort);
+    #[test]
+    // первое нарушение порядка, при -u равные строки - тоже нарушение
+    fn test_check() {
+        let options = parse("-n").unwrap();
+        let input = "1\n2\n2\n10\n3\n";
+        assert_eq!(
+            check(&mut input.as_bytes(), &options.config, false).unwrap(),
+            Some((5, b"3".to_vec()))
+        );
+        assert_eq!(
+            check(&mut "1\n2\n2\n".as_bytes(), &options.config, false).unwrap(),
+            None
+        );
+
+        let options = parse("-nu").unwrap();
+        assert_eq!(
+            check(&mut input.as_bytes(), &options.config, true).unwrap(),
+            Some((3, b"2".to_vec()))
+        );
+    }
 }
@@ -0,0 +1,525 @@
+//! порядки сравнения ключей как в GNU sort: числа (-n, -g), размеры (-h), месяцы (-M) и версии (-V);
+//! ключ, который не удалось разобрать, не ошибка, а значение с определённым местом в порядке.
+//! Ключи - байты: числа и версии разбираются побайтово, в текст переводится только начало месяца
+use std::cmp::Ordering;
+
+// пропуск пробелов в начале ключа
+fn trim_blanks(key: &[u8]) -> &[u8] {
+    let start = key
+        .iter()
+        .position(|&byte| byte != b' ' && byte != b'\t')
+        .unwrap_or(key.len());
+    &key[start..]
+}
+
+// десятичное число в начале ключа: целая часть без ведущих нулей, дробная без конечных
+struct Decimal<'a> {
+    negative: bool,
+    integer: &'a [u8],
+    fraction: &'a [u8],
+    // байт сразу после числа
+    rest: &'a [u8],
+}
+
+impl<'a> Decimal<'a> {
+    // разбор "[-]цифры[.цифры]", без цифр - ноль
+    fn parse(key: &'a [u8]) -> Self {
+        let bytes = trim_blanks(key);
+        let (negative, bytes) = match bytes.split_first() {
+            Some((b'-', rest)) => (true, rest),
+            _ => (false, bytes),
+        };
+
+        let digits = |bytes: &'a [u8]| {
+            let end = bytes
+                .iter()
+                .position(|byte| !byte.is_ascii_digit())
+                .unwrap_or(bytes.len());
+            bytes.split_at(end)
+        };
+        let (integer, rest) = digits(bytes);
+        let (fraction, rest) = match rest.split_first() {
+            Some((b'.', rest)) => digits(rest),
+            _ => (&rest[..0], rest),
+        };
+
+        let leading_zeros = integer.iter().take_while(|&&byte| byte == b'0').count();
+        let trailing_zeros = fraction
+            .iter()
+            .rev()
+            .take_while(|&&byte| byte == b'0')
+            .count();
+        let integer = &integer[leading_zeros..];
+        let fraction = &fraction[..fraction.len() - trailing_zeros];
+
+        Decimal {
+            // -0 равен 0
+            negative: negative && !(integer.is_empty() && fraction.is_empty()),
+            integer,
+            fraction,
+            rest,
+        }
+    }
+
+    // сравнение без перевода в число, поэтому длина не ограничена
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (negative, _) => {
+                let ordering = self
+                    .integer
+                    .len()
+                    .cmp(&other.integer.len())
+                    .then(self.integer.cmp(other.integer))
+                    .then(self.fraction.cmp(other.fraction));
+                if negative {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.integer.is_empty() && self.fraction.is_empty()
+    }
+}
+
+// -n: числа любой длины с дробной частью, не число - 0
+pub fn compare_numeric(a: &[u8], b: &[u8]) -> Ordering {
+    Decimal::parse(a).cmp(&Decimal::parse(b))
+}
+
+// суффиксы размеров по возрастанию
+const SIZE_SUFFIXES: &[u8] = b"KMGTPEZYRQ";
+
+// порядок суффикса со знаком числа, у нуля и числа без суффикса - 0
+fn unit_order(number: &Decimal) -> isize {
+    if number.is_zero() {
+        return 0;
+    }
+
+    let order = number
+        .rest
+        .first()
+        .map(|&suffix| if suffix == b'k' { b'K' } else { suffix })
+        .and_then(|suffix| SIZE_SUFFIXES.iter().position(|&known| known == suffix))
+        .map_or(0, |index| index as isize + 1);
+    if number.negative {
+        -order
+    } else {
+        order
+    }
+}
+
+// -h: сначала суффикс (с учётом знака), затем число: -1M < -5 < 0 < 512 < 2K < 1G
+pub fn compare_human(a: &[u8], b: &[u8]) -> Ordering {
+    let a = Decimal::parse(a);
+    let b = Decimal::parse(b);
+
+    unit_order(&a).cmp(&unit_order(&b)).then_with(|| a.cmp(&b))
+}
+
+// значение для -g: ошибки разбора идут первыми, затем NaN, затем числа
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum General {
+    Invalid,
+    NaN,
+    Number(f64),
+}
+
+// длина цифр в начале
+fn digits_len(bytes: &[u8], radix: u32) -> usize {
+    bytes
+        .iter()
+        .take_while(|&&byte| (byte as char).is_digit(radix))
+        .count()
+}
+
+// показатель степени "e[+-]цифры" / "p[+-]цифры", None - если его нет
+fn exponent(bytes: &[u8], marker: u8) -> Option<(i32, usize)> {
+    let (&first, rest) = bytes.split_first()?;
+    if first.to_ascii_lowercase() != marker {
+        return None;
+    }
+
+    let sign_len = usize::from(matches!(rest.first(), Some(b'+' | b'-')));
+    let len = digits_len(&rest[sign_len..], 10);
+    if len == 0 {
+        return None;
+    }
+
+    let text = std::str::from_utf8(&rest[..sign_len + len]).ok()?;
+    // огромные показатели насыщаются, результат всё равно ноль или бесконечность
+    let value = text.parse::<i32>().unwrap_or(if text.starts_with('-') {
+        i32::MIN
+    } else {
+        i32::MAX
+    });
+    Some((value, 1 + sign_len + len))
+}
+
+// шестнадцатеричное число после "0x": цифры, точка, показатель "p"
+fn parse_hex(bytes: &[u8]) -> Option<f64> {
+    let integer_len = digits_len(bytes, 16);
+    let (fraction_len, fraction_start) = match bytes.get(integer_len) {
+        Some(b'.') => (digits_len(&bytes[integer_len + 1..], 16), integer_len + 1),
+        _ => (0, integer_len),
+    };
+    if integer_len + fraction_len == 0 {
+        return None;
+    }
+
+    let digit = |byte: &u8| (*byte as char).to_digit(16).unwrap_or(0) as f64;
+    let mut value = bytes[..integer_len]
+        .iter()
+        .fold(0.0, |value, byte| value * 16.0 + digit(byte));
+    let mut scale = 1.0 / 16.0;
+    for byte in &bytes[fraction_start..fraction_start + fraction_len] {
+        value += digit(byte) * scale;
+        scale /= 16.0;
+    }
+
+    if let Some((power, _)) = exponent(&bytes[fraction_start + fraction_len..], b'p') {
+        value *= 2f64.powi(power);
+    }
+    Some(value)
+}
+
+// разбор начала ключа как strtold: знак, десятичное или шестнадцатеричное число с показателем,
+// inf, infinity, nan
+fn parse_general(key: &[u8]) -> General {
+    let start = key
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .unwrap_or(key.len());
+    let bytes = &key[start..];
+    let (negative, bytes) = match bytes.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        Some((b'+', rest)) => (false, rest),
+        _ => (false, bytes),
+    };
+    let signed = |value: f64| General::Number(if negative { -value } else { value });
+    let starts_with =
+        |word: &[u8]| bytes.len() >= word.len() && bytes[..word.len()].eq_ignore_ascii_case(word);
+
+    if starts_with(b"inf") {
+        return signed(f64::INFINITY);
+    }
+    if starts_with(b"nan") {
+        return General::NaN;
+    }
+    if starts_with(b"0x") {
+        if let Some(value) = parse_hex(&bytes[2..]) {
+            return signed(value);
+        }
+    }
+
+    let integer_len = digits_len(bytes, 10);
+    let mut len = integer_len;
+    let mut fraction_len = 0;
+    if bytes.get(len) == Some(&b'.') {
+        fraction_len = digits_len(&bytes[len + 1..], 10);
+        len += 1 + fraction_len;
+    }
+    if integer_len + fraction_len == 0 {
+        return General::Invalid;
+    }
+    if let Some((_, exponent_len)) = exponent(&bytes[len..], b'e') {
+        len += exponent_len;
+    }
+
+    // текст из цифр, точки и показателя всегда разбирается f64
+    let text = std::str::from_utf8(&bytes[..len]).unwrap_or("0");
+    signed(text.parse().unwrap_or(0.0))
+}
+
+// -g: числа с плавающей точкой и показателем, -0 == 0
+pub fn compare_general(a: &[u8], b: &[u8]) -> Ordering {
+    match (parse_general(a), parse_general(b)) {
+        (General::Number(a), General::Number(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        (General::Invalid, General::Invalid) | (General::NaN, General::NaN) => Ordering::Equal,
+        (General::Invalid, _) | (General::NaN, General::Number(_)) => Ordering::Less,
+        _ => Ordering::Greater,
+    }
+}
+
+// начала названий месяцев: английские сокращения, как в GNU sort, и русские
+const MONTHS: [&[&str]; 12] = [
+    &["JAN", "ЯНВ"],
+    &["FEB", "ФЕВ"],
+    &["MAR", "МАР"],
+    &["APR", "АПР"],
+    &["MAY", "МАЙ", "МАЯ"],
+    &["JUN", "ИЮН"],
+    &["JUL", "ИЮЛ"],
+    &["AUG", "АВГ"],
+    &["SEP", "СЕН"],
+    &["OCT", "ОКТ"],
+    &["NOV", "НОЯ"],
+    &["DEC", "ДЕК"],
+];
+
+// номер месяца с 1 по началу ключа без учёта регистра ("jan", "January", "января"), не месяц - 0
+fn month(key: &[u8]) -> usize {
+    // неверный байт UTF-8 среди первых трёх символов - уже не месяц
+    let text = trim_blanks(key)
+        .utf8_chunks()
+        .next()
+        .map_or("", |chunk| chunk.valid());
+    let prefix: String = text.chars().take(3).flat_map(char::to_uppercase).collect();
+
+    MONTHS
+        .iter()
+        .position(|names| names.contains(&prefix.as_str()))
+        .map_or(0, |index| index + 1)
+}
+
+// -M: не месяц раньше января
+pub fn compare_month(a: &[u8], b: &[u8]) -> Ordering {
+    month(a).cmp(&month(b))
+}
+
+// длина начала имени без суффиксов вида ".tar.gz" (точка, затем буква или ~, затем буквы, цифры, ~)
+fn file_prefix_len(name: &[u8]) -> usize {
+    let mut prefix_len = 0;
+    let mut i = 0;
+
+    while i < name.len() {
+        i += 1;
+        prefix_len = i;
+        while i + 1 < name.len()
+            && name[i] == b'.'
+            && (name[i + 1].is_ascii_alphabetic() || name[i + 1] == b'~')
+        {
+            i += 2;
+            while i < name.len() && (name[i].is_ascii_alphanumeric() || name[i] == b'~') {
+                i += 1;
+            }
+        }
+    }
+
+    prefix_len
+}
+
+// вес символа в нечисловой части версии: ~ раньше конца строки, буквы раньше остальных символов
+fn version_char_order(version: &[u8], index: usize) -> i32 {
+    match version.get(index) {
+        None => -1,
+        Some(byte) if byte.is_ascii_digit() => 0,
+        Some(byte) if byte.is_ascii_alphabetic() => *byte as i32,
+        Some(b'~') => -2,
+        Some(&byte) => byte as i32 + 256,
+    }
+}
+
+// сравнение чередующихся нечисловых и числовых частей версий
+fn compare_version_parts(a: &[u8], b: &[u8]) -> Ordering {
+    let is_digit =
+        |version: &[u8], index: usize| version.get(index).is_some_and(u8::is_ascii_digit);
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !a[i].is_ascii_digit()) || (j < b.len() && !b[j].is_ascii_digit()) {
+            let ordering = version_char_order(a, i).cmp(&version_char_order(b, j));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            i += 1;
+            j += 1;
+        }
+
+        while a.get(i) == Some(&b'0') {
+            i += 1;
+        }
+        while b.get(j) == Some(&b'0') {
+            j += 1;
+        }
+
+        // числа без ведущих нулей: длиннее - больше, при равной длине решает первая разная цифра
+        let mut first_difference = Ordering::Equal;
+        while is_digit(a, i) && is_digit(b, j) {
+            if first_difference == Ordering::Equal {
+                first_difference = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if is_digit(a, i) {
+            return Ordering::Greater;
+        }
+        if is_digit(b, j) {
+            return Ordering::Less;
+        }
+        if first_difference != Ordering::Equal {
+            return first_difference;
+        }
+    }
+
+    Ordering::Equal
+}
+
+// -V: порядок версий как в GNU filevercmp ("1.2" < "1.10", "1.0~rc1" < "1.0")
+pub fn compare_version(a: &[u8], b: &[u8]) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    // пустая строка первая, затем ".", "..", остальные имена с точкой, потом все прочие
+    match (a.is_empty(), b.is_empty()) {
+        (true, _) => return Ordering::Less,
+        (_, true) => return Ordering::Greater,
+        _ => {}
+    }
+    match (a[0] == b'.', b[0] == b'.') {
+        (true, false) => return Ordering::Less,
+        (false, true) => return Ordering::Greater,
+        (true, true) => {
+            for special in [&b"."[..], b".."] {
+                match (a == special, b == special) {
+                    (true, _) => return Ordering::Less,
+                    (_, true) => return Ordering::Greater,
+                    _ => {}
+                }
+            }
+        }
+        (false, false) => {}
+    }
+
+    // сначала без суффиксов файлов, при равенстве - целиком
+    let a_prefix = file_prefix_len(a);
+    let b_prefix = file_prefix_len(b);
+    let ordering = compare_version_parts(&a[..a_prefix], &b[..b_prefix]);
+    if ordering != Ordering::Equal || (a_prefix == a.len() && b_prefix == b.len()) {
+        return ordering;
+    }
+    compare_version_parts(a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare_general, compare_human, compare_month, compare_numeric, compare_version};
+    use std::cmp::Ordering;
+
+    // значения должны идти строго по возрастанию, равные - в одной группе
+    fn assert_order(compare: fn(&[u8], &[u8]) -> Ordering, groups: &[&[&str]]) {
+        for (i, a_group) in groups.iter().enumerate() {
+            for (j, b_group) in groups.iter().enumerate() {
+                for a in a_group.iter() {
+                    for b in b_group.iter() {
+                        assert_eq!(
+                            compare(a.as_bytes(), b.as_bytes()),
+                            i.cmp(&j),
+                            "{:?} {:?}",
+                            a,
+                            b
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    // числа любой длины и дробные, не числа равны нулю
+    fn test_numeric() {
+        assert_order(
+            compare_numeric,
+            &[
+                &["-123456789012345678901234567890"],
+                &["-2.5", " -2.50"],
+                &["-0.001"],
+                &["0", "-0", "", "abc", "-", ".", "+5", "0.000"],
+                &[".5", "0.50x"],
+                &["1", "01", "1.", "  1abc"],
+                &["99999999999999999999999999.1"],
+                &["99999999999999999999999999.11"],
+            ],
+        );
+    }
+
+    #[test]
+    // числа с плавающей точкой: ошибки, NaN, затем -inf .. inf
+    fn test_general() {
+        assert_order(
+            compare_general,
+            &[
+                &["", "abc", "-", ".", "e5", "-x"],
+                &["nan", "-NaN"],
+                &["-inf", "-Infinity"],
+                &["-1e10"],
+                &["-2.5", "-25e-1"],
+                &["0", "-0", "0e99", "+0.0", "0x"],
+                &["1.5e-3", " 0.0015"],
+                &["1", "1.", "0x1", "1e0x"],
+                &["0x1.8p1", "3"],
+                &["1e308"],
+                &["1e999", "inf", "+INF"],
+            ],
+        );
+    }
+
+    #[test]
+    // размеры: сначала суффикс со знаком, затем число
+    fn test_human() {
+        assert_order(
+            compare_human,
+            &[
+                &["-1M"],
+                &["-2K"],
+                &["-1K"],
+                &["-999"],
+                &["0", "0K", "-0G", "x"],
+                &["999"],
+                &["1k", "1K"],
+                &["1.5K"],
+                &["1000K"],
+                &["1M"],
+                &["0.5G"],
+            ],
+        );
+    }
+
+    #[test]
+    // месяцы без учёта регистра, полные названия, русские
+    fn test_month() {
+        assert_order(
+            compare_month,
+            &[
+                &["", "foo", "JA", "ЯН"],
+                &["JAN", "jan", " January", "января", "ЯНВ"],
+                &["mar", "Март"],
+                &["May", "май", "мая"],
+                &["December", "дек."],
+            ],
+        );
+        assert_eq!(compare_month(b"JAN\xff", b"jan"), Ordering::Equal);
+        assert_eq!(compare_month(b"J\xffN", b""), Ordering::Equal);
+    }
+
+    #[test]
+    // версии: числа по значению, ~ раньше всего, суффиксы файлов сравниваются последними
+    fn test_version() {
+        assert_order(
+            compare_version,
+            &[
+                &[""],
+                &["."],
+                &[".."],
+                &[".hidden"],
+                &["1.0~rc1"],
+                &["1.0"],
+                &["1.0a"],
+                &["1.0.1"],
+                &["1.2"],
+                &["1.10"],
+                &["1.010.1"],
+                &["a"],
+                &["a.tar.gz"],
+                &["a1.tar.gz"],
+            ],
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! индекс словаря для игр в слова: точные анаграммы, слова из части букв и `?` - пустые
+//! фишки, заменяющие любую букву
+//!
+//! слова с одной подписью хранятся вместе; подпись хранится как номера букв алфавита словаря
+//! с числом вхождений и маской, по которой большинство подписей отбрасывается без подсчёта
+use crate::{Normalization, Signature};
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, BufRead};
+
+// пустая фишка в запросе
+pub const BLANK: char = '?';
+
+// слова с одной подписью
+#[derive(Debug, Clone)]
+struct Entry {
+    // номер буквы в алфавите словаря и число вхождений
+    letters: Vec<(usize, usize)>,
+    // бит (номер буквы % 64) для каждой буквы
+    mask: u64,
+    len: usize,
+    words: BTreeSet<String>,
+}
+
+// запрос: буквы по номерам алфавита словаря и число пустых фишек
+struct Query {
+    counts: Vec<usize>,
+    mask: u64,
+    // все буквы и фишки, включая буквы не из алфавита словаря
+    len: usize,
+    blanks: usize,
+}
+
+impl Entry {
+    // сколько букв слова не хватает в запросе (их заменяют пустые фишки)
+    fn missing(&self, query: &Query) -> usize {
+        self.letters
+            .iter()
+            .map(|&(letter, count)| count.saturating_sub(query.counts[letter]))
+            .sum()
+    }
+
+    // слово можно составить из букв запроса: маска отсекает подписи, в которых больше
+    // отсутствующих в запросе букв, чем пустых фишек
+    fn fits(&self, query: &Query) -> bool {
+        if (self.mask & !query.mask).count_ones() as usize > query.blanks {
+            return false;
+        }
+        self.missing(query) <= query.blanks
+    }
+}
+
+// индекс словаря
+#[derive(Debug, Clone, Default)]
+pub struct AnagramIndex {
+    normalization: Normalization,
+    // номера графем в порядке появления
+    alphabet: HashMap<String, usize>,
+    entries: Vec<Entry>,
+    by_signature: HashMap<Signature, usize>,
+}
+
+impl AnagramIndex {
+    pub fn new(normalization: Normalization) -> Self {
+        AnagramIndex {
+            normalization,
+            ..AnagramIndex::default()
+        }
+    }
+
+    // добавление слова словаря, слова без букв и цифр пропускаются
+    pub fn insert(&mut self, word: &str) {
+        let word = self.normalization.apply(word);
+        let signature = Signature::new(&word);
+        if signature.is_empty() {
+            return;
+        }
+
+        if let Some(&entry) = self.by_signature.get(&signature) {
+            self.entries[entry].words.insert(word);
+            return;
+        }
+
+        let mut letters = Vec::new();
+        let mut mask = 0;
+        for (grapheme, count) in signature.counts() {
+            let next = self.alphabet.len();
+            let letter = *self.alphabet.entry(grapheme.to_string()).or_insert(next);
+            letters.push((letter, count));
+            mask |= 1 << (letter % 64);
+        }
+
+        self.by_signature
+            .insert(signature.clone(), self.entries.len());
+        self.entries.push(Entry {
+            letters,
+            mask,
+            len: signature.len(),
+            words: BTreeSet::from([word]),
+        });
+    }
+
+    // чтение словаря: слова, разделённые пробельными символами
+    pub fn read_from<R: BufRead>(&mut self, mut reader: R) -> io::Result<()> {
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 {
+            for word in line.split_whitespace() {
+                self.insert(word);
+            }
+            line.clear();
+        }
+        Ok(())
+    }
+
+    // разных слов в словаре
+    pub fn len(&self) -> usize {
+        self.entries.iter().map(|entry| entry.words.len()).sum()
+    }
+
+    // словарь пуст
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // разбор запроса: `?` - пустые фишки, буквы нормализуются как слова словаря
+    fn query(&self, letters: &str) -> Query {
+        let letters = self.normalization.apply(letters);
+        let blanks = letters.chars().filter(|&c| c == BLANK).count();
+        let signature = Signature::new(&letters);
+
+        let mut counts = vec![0; self.alphabet.len()];
+        let mut mask = 0;
+        for (grapheme, count) in signature.counts() {
+            // буквы не из алфавита ни в одно слово не войдут, но занимают место
+            if let Some(&letter) = self.alphabet.get(grapheme) {
+                counts[letter] = count;
+                mask |= 1 << (letter % 64);
+            }
+        }
+
+        Query {
+            counts,
+            mask,
+            len: signature.len() + blanks,
+            blanks,
+        }
+    }
+
+    // слова ровно из всех букв запроса ("пятак" -> пятак, пятка, тяпка), по алфавиту
+    pub fn anagrams(&self, letters: &str) -> Vec<&str> {
+        // без пустых фишек подпись слова совпадает с подписью запроса
+        if !letters.contains(BLANK) {
+            let signature = Signature::new(&self.normalization.apply(letters));
+            return self
+                .by_signature
+                .get(&signature)
+                .map(|&entry| {
+                    self.entries[entry]
+                        .words
+                        .iter()
+                        .map(String::as_str)
+                        .collect()
+                })
+                .unwrap_or_default();
+        }
+
+        let query = self.query(letters);
+        let mut words: Vec<&str> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.len == query.len && entry.fits(&query))
+            .flat_map(|entry| entry.words.iter().map(String::as_str))
+            .collect();
+        words.sort_unstable();
+        words
+    }
+
+    // слова из части букв запроса (каждая буква - не больше раз, чем в запросе),
+    // сначала длинные, при равной длине - по алфавиту
+    pub fn sub_anagrams(&self, letters: &str) -> Vec<&str> {
+        let query = self.query(letters);
+
+        let mut found: Vec<(usize, &str)> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.len <= query.len && entry.fits(&query))
+            .flat_map(|entry| entry.words.iter().map(|word| (entry.len, word.as_str())))
+            .collect();
+        found.sort_unstable_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        found.into_iter().map(|(_, word)| word).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnagramIndex;
+    use crate::Normalization;
+
+    // индекс по словам из строки
+    fn index(words: &str) -> AnagramIndex {
+        let mut index = AnagramIndex::new(Normalization::default());
+        index.read_from(words.as_bytes()).unwrap();
+        index
+    }
+
+    #[test]
+    // точные анаграммы с пустыми фишками и без
+    fn test_anagrams() {
+        let index = index("пятак тяпка Пятка\nпятак кот ток окт кто тик кит\nкиты");
+        assert_eq!(index.len(), 10);
+        assert_eq!(index.anagrams("ТЯПКА"), ["пятак", "пятка", "тяпка"]);
+        assert_eq!(index.anagrams("тко"), ["кот", "кто", "окт", "ток"]);
+        assert!(index.anagrams("пятакк").is_empty());
+        assert_eq!(
+            index.anagrams("к?т"),
+            ["кит", "кот", "кто", "окт", "тик", "ток"]
+        );
+        assert_eq!(index.anagrams("??ты"), ["киты"]);
+        assert!(index.anagrams("фт?").is_empty());
+        assert!(index.anagrams("").is_empty());
+    }
+
+    #[test]
+    // слова из части букв: каждая буква - не больше раз, чем в запросе
+    fn test_sub_anagrams() {
+        let index = index("пятак пятка тяпка пятак кот ток как пак пакт тапка");
+        assert_eq!(
+            index.sub_anagrams("пятак"),
+            ["пятак", "пятка", "тяпка", "пакт", "пак"]
+        );
+        assert_eq!(index.sub_anagrams("тапок"), ["пакт", "кот", "пак", "ток"]);
+        assert_eq!(
+            index.sub_anagrams("тапок?"),
+            [
+                "пятак",
+                "пятка",
+                "тапка",
+                "тяпка",
+                "пакт",
+                "как",
+                "кот",
+                "пак",
+                "ток"
+            ]
+        );
+        assert!(index.sub_anagrams("я").is_empty());
+    }
+
+    #[test]
+    // буквы за пределами 64 номеров маски и буквы не из словаря
+    fn test_large_alphabet() {
+        let words: Vec<String> = ('a'..='z')
+            .chain('а'..='я')
+            .chain('α'..='ω')
+            .map(|c| format!("{}{}", c, c))
+            .collect();
+        let index = index(&words.join(" "));
+        assert_eq!(index.sub_anagrams("ωωa"), ["ωω"]);
+        assert_eq!(index.sub_anagrams("ω?"), ["ωω"]);
+        assert_eq!(index.anagrams("ю?").len(), 1);
+        assert!(index.sub_anagrams("€ω").is_empty());
+    }
+}
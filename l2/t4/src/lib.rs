@@ -0,0 +1,285 @@
+//! группировка анаграмм для любого алфавита
+//!
+//! подпись слова - сколько раз в нём встречается каждая графема (буква вместе с диакритикой)
+//! после нормализации: регистр, ё -> е, удаление диакритики. Знаки вроде дефиса и апострофа
+//! в подпись не входят, так что "пят-ак" - анаграмма "тяпка"
+pub mod index;
+
+pub use index::AnagramIndex;
+use serde::ser::{Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, BufRead};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+// две точки над буквой, в NFD ё - это е и этот знак
+const COMBINING_DIAERESIS: char = '\u{308}';
+
+// нормализация слов перед сравнением
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Normalization {
+    // к нижнему регистру
+    pub fold_case: bool,
+    // ё -> е
+    pub merge_yo: bool,
+    // без диакритики: é -> e, й -> и, ё -> е
+    pub strip_diacritics: bool,
+}
+
+impl Default for Normalization {
+    // как раньше: только нижний регистр
+    fn default() -> Self {
+        Normalization {
+            fold_case: true,
+            merge_yo: false,
+            strip_diacritics: false,
+        }
+    }
+}
+
+impl Normalization {
+    // нормализованное слово в NFC
+    pub fn apply(&self, word: &str) -> String {
+        let mut decomposed = String::with_capacity(word.len());
+        let mut previous = None;
+
+        for c in word.nfd() {
+            let skip = is_combining_mark(c)
+                && (self.strip_diacritics
+                    || (self.merge_yo
+                        && c == COMBINING_DIAERESIS
+                        && matches!(previous, Some('е' | 'Е'))));
+            if !skip {
+                if self.fold_case {
+                    decomposed.extend(c.to_lowercase());
+                } else {
+                    decomposed.push(c);
+                }
+            }
+            previous = Some(c);
+        }
+
+        decomposed.nfc().collect()
+    }
+}
+
+// подпись слова: число вхождений каждой графемы, начинающейся с буквы или цифры
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Signature(BTreeMap<String, usize>);
+
+impl Signature {
+    // подпись уже нормализованного слова
+    pub fn new(word: &str) -> Self {
+        let mut counts = BTreeMap::new();
+        for grapheme in word.graphemes(true) {
+            if grapheme.starts_with(char::is_alphanumeric) {
+                *counts.entry(grapheme.to_string()).or_insert(0) += 1;
+            }
+        }
+
+        Signature(counts)
+    }
+
+    // всего графем
+    pub fn len(&self) -> usize {
+        self.0.values().sum()
+    }
+
+    // в слове нет ни одной буквы или цифры
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // графемы и их число вхождений по порядку
+    pub fn counts(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.0
+            .iter()
+            .map(|(grapheme, &count)| (grapheme.as_str(), count))
+    }
+}
+
+// группа анаграмм: первое встреченное слово и все слова группы по порядку
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub key: String,
+    pub members: BTreeSet<String>,
+}
+
+// группировка слов по мере поступления, память - только на разные слова
+#[derive(Debug, Clone, Default)]
+pub struct Anagrams {
+    normalization: Normalization,
+    // группы в порядке первого слова
+    groups: Vec<Group>,
+    index: HashMap<Signature, usize>,
+}
+
+impl Anagrams {
+    pub fn new(normalization: Normalization) -> Self {
+        Anagrams {
+            normalization,
+            ..Anagrams::default()
+        }
+    }
+
+    // добавление слова, слова без букв и цифр пропускаются
+    pub fn push(&mut self, word: &str) {
+        let word = self.normalization.apply(word);
+        let signature = Signature::new(&word);
+        if signature.is_empty() {
+            return;
+        }
+
+        match self.index.get(&signature) {
+            Some(&group) => {
+                self.groups[group].members.insert(word);
+            }
+            None => {
+                self.index.insert(signature, self.groups.len());
+                self.groups.push(Group {
+                    key: word.clone(),
+                    members: BTreeSet::from([word]),
+                });
+            }
+        }
+    }
+
+    // чтение слов, разделённых пробельными символами, построчно
+    pub fn read_from<R: BufRead>(&mut self, mut reader: R) -> io::Result<()> {
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 {
+            for word in line.split_whitespace() {
+                self.push(word);
+            }
+            line.clear();
+        }
+        Ok(())
+    }
+
+    // группы хотя бы из двух разных слов в порядке первого слова
+    pub fn groups(&self) -> impl Iterator<Item = &Group> {
+        self.groups.iter().filter(|group| group.members.len() > 1)
+    }
+}
+
+// JSON - объект "первое слово": [слова группы], ключи в порядке появления
+impl Serialize for Anagrams {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.groups().map(|group| (&group.key, &group.members)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Anagrams, Normalization, Signature};
+
+    // группы для слов с заданной нормализацией
+    fn groups(words: &[&str], normalization: Normalization) -> Vec<(String, Vec<String>)> {
+        let mut anagrams = Anagrams::new(normalization);
+        for word in words {
+            anagrams.push(word);
+        }
+
+        anagrams
+            .groups()
+            .map(|group| (group.key.clone(), group.members.iter().cloned().collect()))
+            .collect()
+    }
+
+    #[test]
+    // регистр, ё и диакритика
+    fn test_normalization() {
+        let all = Normalization {
+            fold_case: true,
+            merge_yo: true,
+            strip_diacritics: true,
+        };
+        assert_eq!(Normalization::default().apply("ЁЛКА Café"), "ёлка café");
+        assert_eq!(all.apply("ЁЛКА Café"), "елка cafe");
+        assert_eq!(all.apply("Йод"), "иод");
+
+        let yo = Normalization {
+            merge_yo: true,
+            ..Normalization::default()
+        };
+        assert_eq!(yo.apply("Ёжик й Ü"), "ежик й ü");
+        // разложенная форма совпадает с составной
+        assert_eq!(yo.apply("е\u{308}ж"), yo.apply("ёж"));
+    }
+
+    #[test]
+    // любые буквы и цифры, графема с диакритикой - одна буква
+    fn test_signature() {
+        assert_eq!(Signature::new("пят-ак"), Signature::new("тяпка"));
+        assert_eq!(Signature::new("a1b2"), Signature::new("2b1a"));
+        assert_ne!(Signature::new("ёж"), Signature::new("еж"));
+        assert_eq!(Signature::new("е\u{308}ж").len(), 2);
+        assert!(Signature::new("--").is_empty());
+    }
+
+    #[test]
+    // группы в порядке первого слова, слова внутри - по порядку и без повторов
+    fn test_groups() {
+        let words = [
+            "АБВГДКК",
+            "Пятак",
+            "тяпка",
+            "листок",
+            "слиток",
+            "столик",
+            "пятка",
+            "пятак",
+            "ёлка",
+            "лека",
+            "Rat",
+            "tar",
+            "art",
+            "—",
+        ];
+        assert_eq!(
+            groups(&words, Normalization::default()),
+            [
+                (
+                    "пятак".to_string(),
+                    vec![
+                        "пятак".to_string(),
+                        "пятка".to_string(),
+                        "тяпка".to_string()
+                    ]
+                ),
+                (
+                    "листок".to_string(),
+                    vec![
+                        "листок".to_string(),
+                        "слиток".to_string(),
+                        "столик".to_string()
+                    ]
+                ),
+                (
+                    "rat".to_string(),
+                    vec!["art".to_string(), "rat".to_string(), "tar".to_string()]
+                ),
+            ]
+        );
+
+        let yo = Normalization {
+            merge_yo: true,
+            ..Normalization::default()
+        };
+        assert_eq!(groups(&words, yo)[2].0, "елка");
+    }
+
+    #[test]
+    // JSON не зависит от порядка хеширования
+    fn test_json() {
+        let mut anagrams = Anagrams::default();
+        anagrams
+            .read_from("tar rat\nстолик листок\n\nart\n".as_bytes())
+            .unwrap();
+        assert_eq!(
+            serde_json::to_string(&anagrams).unwrap(),
+            r#"{"tar":["art","rat","tar"],"столик":["листок","столик"]}"#
+        );
+    }
+}
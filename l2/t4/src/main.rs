@@ -1,59 +1,170 @@
-use std::collections::{HashMap, HashSet};
+//! поиск анаграмм в словах из файлов или stdin, результат - JSON вида
+//! {"пятак": ["пятак", "пятка", "тяпка"], ...}: ключ - первое встреченное слово группы
+//!
+//! с --dict - запросы к словарю: t4 --dict words.txt --sub пятак "к?т", ответ на каждый
+//! запрос - строка JSON {"query": "пятак", "words": [...]}; без запросов в аргументах они
+//! читаются из stdin по одному на строку
+use serde_json::json;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::process::exit;
+use t4::{AnagramIndex, Anagrams, Normalization};
 
-// функция подсчета букв в слове
-fn word_to_count(word: &str) -> [i32; 33] {
-    let mut letter_count = [0; 33];
+const USAGE: &str = "использование: t4 [ПАРАМЕТРЫ] [ФАЙЛ]...
+       t4 --dict СЛОВАРЬ [--sub] [ПАРАМЕТРЫ] [ЗАПРОС]...
+группирует анаграммы среди слов файлов (без файлов или для \"-\" - stdin)
+или ищет в словаре слова из букв запросов (без запросов - из stdin, по одному на строку),
+? в запросе - пустая фишка, заменяющая любую букву
+    --dict СЛОВАРЬ        файл словаря
+    --sub                 слова из части букв запроса, а не из всех
+    --keep-case           различать регистр
+    --merge-yo            считать ё и е одной буквой
+    --strip-diacritics    не учитывать диакритику (é -> e, й -> и, ё -> е)
+    --compact             JSON в одну строку
+    -h, --help            эта справка";
 
-    for c in word.chars() {
-        letter_count[(c.to_ascii_lowercase() as u32 - 'а'.to_ascii_lowercase() as u32) as usize] += 1;
-    }
-
-    letter_count
+// разобранные аргументы
+struct Options {
+    normalization: Normalization,
+    compact: bool,
+    // входные файлы, "-" - stdin, а со словарём - запросы
+    files: Vec<String>,
+    dictionary: Option<String>,
+    sub: bool,
 }
 
-// функция создания нужной HashMap
-fn create_anagram_map(input_words: &Vec<&str>) -> HashMap<String, Vec<String>> {
-    // промежуточкая HashMap для группировки анаграмм
-    let mut anagram_map: HashMap<[i32; 33], HashSet<String>> = HashMap::new();
-    // промежуточкая HashMap для учёта первого встретившегося слова
-    let mut first_word_map: HashMap<[i32; 33], String> = HashMap::new();
-
-    // цикл, который считает количество определлёных букв в слове и хэшируя массив со счётом группирует анаграммы
-    for word in input_words {
-        let word_lowercase = word.to_lowercase();
-        let count = word_to_count(&word_lowercase);
-
-        match anagram_map.get_mut(&count) {
-            Some(value) => { value.insert(word_lowercase); },
-            None => {
-                first_word_map.insert(count, word_lowercase.clone());
-                anagram_map.insert(count, HashSet::from([word_lowercase]));
+// разбор аргументов командной строки
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        normalization: Normalization::default(),
+        compact: false,
+        files: Vec::new(),
+        dictionary: None,
+        sub: false,
+    };
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dict" => {
+                let value = args.next().ok_or("после --dict нужен файл словаря")?;
+                options.dictionary = Some(value.clone());
             }
+            "--sub" => options.sub = true,
+            "--keep-case" => options.normalization.fold_case = false,
+            "--merge-yo" => options.normalization.merge_yo = true,
+            "--strip-diacritics" => options.normalization.strip_diacritics = true,
+            "--compact" => options.compact = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                exit(0);
+            }
+            other if other.starts_with('-') && other != "-" => {
+                return Err(format!("неизвестный аргумент {:?}\n{}", other, USAGE))
+            }
+            file => options.files.push(file.to_string()),
         }
     }
 
-    // удаляет из HashMap векторы из одного элемента
-    anagram_map.retain(|_, v| v.len() > 1);
+    if options.sub && options.dictionary.is_none() {
+        return Err("--sub работает только со словарём (--dict)".to_string());
+    }
+    if options.files.is_empty() && options.dictionary.is_none() {
+        options.files.push("-".to_string());
+    }
+    Ok(options)
+}
+
+// открытие входа, "-" - stdin
+fn open_input(name: &str) -> Result<Box<dyn BufRead>, String> {
+    if name == "-" {
+        return Ok(Box::new(io::stdin().lock()));
+    }
 
-    // преобразует данные из двух HashMap в финальную форму, при этом сортируя массивы
-    let mut resulting_map: HashMap<String, Vec<String>> = HashMap::new();
-    for (k, v) in anagram_map {
-        let mut word_vec: Vec<String> = v.into_iter().collect();
-        word_vec.sort_by(|a, b| {
-            a.cmp(b)
-        });
+    File::open(name)
+        .map(|file| Box::new(BufReader::new(file)) as Box<dyn BufRead>)
+        .map_err(|err| format!("не удалось открыть {}: {}", name, err))
+}
+
+// чтение всех входов и вывод групп
+fn run(options: &Options) -> Result<(), String> {
+    let mut anagrams = Anagrams::new(options.normalization);
+
+    for name in &options.files {
+        anagrams
+            .read_from(open_input(name)?)
+            .map_err(|err| format!("ошибка чтения {}: {}", name, err))?;
+    }
 
-        resulting_map.insert(first_word_map[&k].to_owned(), word_vec);
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    let result = if options.compact {
+        serde_json::to_writer(&mut stdout, &anagrams)
+    } else {
+        serde_json::to_writer_pretty(&mut stdout, &anagrams)
     };
+    result
+        .map_err(io::Error::from)
+        .and_then(|_| writeln!(stdout))
+        .and_then(|_| stdout.flush())
+        .map_err(|err| format!("ошибка записи: {}", err))
+}
+
+// ответы на запросы к словарю, по строке JSON на запрос
+fn run_queries(options: &Options, dictionary: &str) -> Result<(), String> {
+    let mut index = AnagramIndex::new(options.normalization);
+    index
+        .read_from(open_input(dictionary)?)
+        .map_err(|err| format!("ошибка чтения {}: {}", dictionary, err))?;
+
+    let answer = |query: &str| {
+        let words = if options.sub {
+            index.sub_anagrams(query)
+        } else {
+            index.anagrams(query)
+        };
+        json!({ "query": query, "words": words })
+    };
+    let write_error = |err: io::Error| format!("ошибка записи: {}", err);
+
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    if options.files.is_empty() {
+        for line in io::stdin().lock().lines() {
+            let line = line.map_err(|err| format!("ошибка чтения запроса: {}", err))?;
+            let query = line.trim();
+            if !query.is_empty() {
+                // ответ сразу, запросы могут приходить по одному
+                writeln!(stdout, "{}", answer(query))
+                    .and_then(|_| stdout.flush())
+                    .map_err(write_error)?;
+            }
+        }
+    } else {
+        for query in &options.files {
+            writeln!(stdout, "{}", answer(query)).map_err(write_error)?;
+        }
+    }
 
-    resulting_map
+    stdout.flush().map_err(write_error)
 }
 
 fn main() {
-    // тест
-    let input_words= vec!["АБВГДКК", "Пятак", "тяпка", "листок", "слиток", "столик", "пятка"];
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    let resulting_map = create_anagram_map(&input_words);
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(2);
+        }
+    };
 
-    println!("{:?}", resulting_map);
-}
\ No newline at end of file
+    let result = match &options.dictionary {
+        Some(dictionary) => run_queries(&options, dictionary),
+        None => run(&options),
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        exit(1);
+    }
+}
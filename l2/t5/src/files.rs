@@ -0,0 +1,269 @@
+//! входные файлы: операнды по порядку, каталоги при -r/-R обходятся в порядке имён
+//! с учётом .gitignore и .ignore; --include/--exclude сверяются с именем файла без каталога
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+
+// сколько байт начала файла проверять на двоичность
+pub const BINARY_CHECK_SIZE: usize = 64 * 1024;
+
+// настройки обхода
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    // -r: обход каталогов без перехода по символьным ссылкам внутри них
+    pub recursive: bool,
+    // -R: обход с переходом по ссылкам
+    pub follow_links: bool,
+    // --no-ignore: не читать .gitignore и .ignore
+    pub no_ignore: bool,
+    // --include: искать только в файлах с подходящим именем
+    pub include: Vec<String>,
+    // --exclude: пропускать файлы с подходящим именем
+    pub exclude: Vec<String>,
+    // --exclude-dir: не заходить в каталоги с подходящим именем
+    pub exclude_dir: Vec<String>,
+}
+
+// вход для поиска
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+impl Input {
+    // имя для вывода
+    pub fn name(&self) -> String {
+        match self {
+            Input::Stdin => "(standard input)".to_string(),
+            Input::File(path) => path.display().to_string(),
+        }
+    }
+}
+
+// набор шаблонов имён
+fn glob_set(globs: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).map_err(|err| format!("неверный шаблон имени: {}", err))?);
+    }
+    builder
+        .build()
+        .map_err(|err| format!("неверный шаблон имени: {}", err))
+}
+
+// обход операндов с фильтрами
+pub struct Walker {
+    options: WalkOptions,
+    include: GlobSet,
+    exclude: GlobSet,
+    exclude_dir: GlobSet,
+}
+
+impl Walker {
+    pub fn new(options: WalkOptions) -> Result<Self, String> {
+        Ok(Walker {
+            include: glob_set(&options.include)?,
+            exclude: glob_set(&options.exclude)?,
+            exclude_dir: glob_set(&options.exclude_dir)?,
+            options,
+        })
+    }
+
+    // обходятся ли каталоги
+    pub fn recursive(&self) -> bool {
+        self.options.recursive || self.options.follow_links
+    }
+
+    // файл проходит --include и --exclude
+    fn accepts(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name() else {
+            return true;
+        };
+        (self.options.include.is_empty() || self.include.is_match(name))
+            && !self.exclude.is_match(name)
+    }
+
+    // входы по порядку операндов, ошибки - на месте операнда или файла, где они случились;
+    // без операндов - stdin, а с -r - текущий каталог без "./" в именах
+    pub fn inputs(&self, operands: &[String]) -> Vec<Result<Input, String>> {
+        let mut inputs = Vec::new();
+        if operands.is_empty() {
+            if self.recursive() {
+                self.walk(Path::new("."), true, &mut inputs);
+            } else {
+                inputs.push(Ok(Input::Stdin));
+            }
+        }
+
+        for operand in operands {
+            let path = Path::new(operand);
+            if operand == "-" {
+                inputs.push(Ok(Input::Stdin));
+            } else if !path.is_dir() {
+                // ошибки открытия - при поиске
+                if self.accepts(path) {
+                    inputs.push(Ok(Input::File(path.to_path_buf())));
+                }
+            } else if self.recursive() {
+                self.walk(path, false, &mut inputs);
+            } else {
+                inputs.push(Err(format!("{}: это каталог", operand)));
+            }
+        }
+
+        inputs
+    }
+
+    // файлы каталога в порядке имён
+    fn walk(&self, root: &Path, strip_root: bool, inputs: &mut Vec<Result<Input, String>>) {
+        let use_ignore = !self.options.no_ignore;
+        let exclude_dir = self.exclude_dir.clone();
+
+        let walker = WalkBuilder::new(root)
+            .hidden(false)
+            .parents(use_ignore)
+            .ignore(use_ignore)
+            .git_ignore(use_ignore)
+            .git_global(use_ignore)
+            .git_exclude(use_ignore)
+            .require_git(false)
+            .follow_links(self.options.follow_links)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .filter_entry(move |entry| {
+                let is_dir = entry.file_type().is_some_and(|kind| kind.is_dir());
+                // сам .git при учёте .gitignore не обходится, как у git
+                entry.depth() == 0
+                    || !is_dir
+                    || !(exclude_dir.is_match(entry.file_name())
+                        || (use_ignore && entry.file_name() == ".git"))
+            })
+            .build();
+
+        for entry in walker {
+            match entry {
+                Ok(entry) => {
+                    if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+                        continue;
+                    }
+                    let path = entry.path();
+                    let path = match path.strip_prefix(root) {
+                        Ok(relative) if strip_root => relative,
+                        _ => path,
+                    };
+                    if self.accepts(path) {
+                        inputs.push(Ok(Input::File(path.to_path_buf())));
+                    }
+                }
+                Err(err) => inputs.push(Err(walk_error(&err))),
+            }
+        }
+    }
+}
+
+// операнд - каталог, по которому пойдёт обход
+pub fn is_dir(operand: &str) -> bool {
+    operand != "-" && fs::metadata(operand).is_ok_and(|meta| meta.is_dir())
+}
+
+// двоичный файл: есть нулевой байт в начале (как у GNU grep), вход не расходуется
+pub fn is_binary<R: BufRead>(reader: &mut R) -> io::Result<bool> {
+    let buf = reader.fill_buf()?;
+    Ok(buf[..buf.len().min(BINARY_CHECK_SIZE)].contains(&0))
+}
+
+// ошибка обхода каталога: путь и текст ошибки ОС без её кода
+fn walk_error(err: &ignore::Error) -> String {
+    match err {
+        ignore::Error::WithPath { path, err } => format!("{}: {}", path.display(), walk_error(err)),
+        ignore::Error::WithDepth { err, .. } => walk_error(err),
+        ignore::Error::Io(err) => {
+            // ошибка walkdir завёрнута в io::Error вместе с путём, который уже есть в WithPath
+            let inner = err
+                .get_ref()
+                .and_then(|inner| inner.source())
+                .and_then(|source| source.downcast_ref::<io::Error>());
+            args::io_error(inner.unwrap_or(err))
+        }
+        err => err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_binary, Input, WalkOptions, Walker};
+    use std::fs;
+    use std::path::Path;
+
+    // имена входов относительно каталога
+    fn names(walker: &Walker, root: &Path) -> Vec<String> {
+        walker
+            .inputs(&[root.display().to_string()])
+            .into_iter()
+            .map(|input| match input.unwrap() {
+                Input::File(path) => path.strip_prefix(root).unwrap().display().to_string(),
+                Input::Stdin => "-".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    // порядок имён, .gitignore, --include/--exclude/--exclude-dir
+    fn test_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for file in [
+            "b.txt",
+            "a.rs",
+            "sub/c.txt",
+            "sub/d.log",
+            "target/e.txt",
+            ".hidden",
+        ] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "x\n").unwrap();
+        }
+        fs::write(root.join(".gitignore"), "*.log\ntarget/\n").unwrap();
+
+        let options = WalkOptions {
+            recursive: true,
+            ..WalkOptions::default()
+        };
+        let walker = Walker::new(options.clone()).unwrap();
+        assert_eq!(
+            names(&walker, root),
+            [".gitignore", ".hidden", "a.rs", "b.txt", "sub/c.txt"]
+        );
+
+        let walker = Walker::new(WalkOptions {
+            no_ignore: true,
+            include: vec!["*.txt".to_string(), "*.log".to_string()],
+            exclude: vec!["b*".to_string()],
+            exclude_dir: vec!["sub".to_string()],
+            ..options
+        })
+        .unwrap();
+        assert_eq!(names(&walker, root), ["target/e.txt"]);
+
+        // каталог без -r - ошибка
+        let walker = Walker::new(WalkOptions::default()).unwrap();
+        assert!(walker.inputs(&[root.display().to_string()])[0].is_err());
+        assert!(Walker::new(WalkOptions {
+            include: vec!["[".to_string()],
+            ..WalkOptions::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    // нулевой байт в начале - двоичный файл, чтение после проверки - с начала
+    fn test_binary() {
+        let mut text: &[u8] = b"text\nmore\n";
+        assert!(!is_binary(&mut text).unwrap());
+        assert_eq!(text, b"text\nmore\n");
+        assert!(is_binary(&mut &b"ab\0cd"[..]).unwrap());
+    }
+}
@@ -1,346 +1,544 @@
-use regex::Regex;
-use std::collections::HashSet;
+//! поиск строк по шаблону в файлах, каталогах или stdin, вывод как у GNU grep
+//!
+//! Пример: t5 -i -c -C 1 helloWorld input.txt
+//!         t5 -rn --include '*.rs' TODO src
+//!         t5 -Fw -e foo -e bar -f words.txt a.txt b.txt
+//!         t5 -r --json -m 10 'fn \w+' src
+//!
+//! каждый вход читается за один проход, в памяти - только строки контекста -B;
+//! файлы ищутся параллельно пачками, вывод - в порядке файлов
+mod files;
+mod matcher;
+mod output;
+mod search;
+
+use args::{Arg, Opt, Parser};
+use files::{Input, WalkOptions, Walker, BINARY_CHECK_SIZE};
+use matcher::{MatchOptions, Matcher};
+use output::{Discard, JsonPrinter, Printer, SEPARATOR_COLOR};
+use rayon::prelude::*;
+use search::{SearchOptions, Searcher};
 use std::env;
-use std::fs::read_to_string;
-
-///
-/// Пример: t5 -i -c -C 1  helloWorld input.txt
-///
-/// -v работает с -A -B -C не выводя N строк до/после/вокруг
-///
-
-// записывает строки из файла в вектор
-fn read_lines_from_file(filename: &str) -> Vec<String> {
-    let mut file_lines = Vec::new();
-    for line in read_to_string(filename)
-        .expect("не удалось записать строку из файла в вектор")
-        .lines()
-    {
-        file_lines.push(line.to_string())
-    }
-    file_lines
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, IsTerminal, Write};
+use std::process::exit;
+
+// файлов в одной параллельной пачке: вывод пачки держится в памяти до печати
+const PARALLEL_BATCH: usize = 64;
+
+const USAGE: &str = "использование: t5 [ПАРАМЕТРЫ] ШАБЛОН [ФАЙЛ]...
+       t5 [ПАРАМЕТРЫ] -e ШАБЛОН... [-f ФАЙЛ]... [ФАЙЛ]...
+ищет строки с шаблоном в файлах (без файлов или для \"-\" - в stdin, с -r - в текущем каталоге),
+двоичные файлы (с нулевым байтом в начале) пропускаются
+    -A, --after-context=N     выводить N строк после выбранной
+    -B, --before-context=N    выводить N строк перед выбранной
+    -C, --context=N           выводить N строк вокруг выбранной
+    -c, --count               вывести только число выбранных строк
+    -l, --files-with-matches  вывести только имена файлов с выбранными строками
+    -L, --files-without-match вывести только имена файлов без выбранных строк
+    -m, --max-count=N         остановиться после N выбранных строк
+    -o, --only-matching       выводить только совпадения, каждое с новой строки
+    -e, --regexp=ШАБЛОН       шаблон, можно несколько
+    -f, --file=ФАЙЛ           шаблоны из файла, по одному на строку
+    -F, --fixed-strings       шаблоны - строки, а не регулярные выражения
+    -i, --ignore-case         без учёта регистра
+    -w, --word-regexp         совпадение - только целые слова
+    -x, --line-regexp         совпадение - только строка целиком
+    -n, --line-number         выводить номер строки (с 1) перед строкой
+    -v, --invert-match        выбирать строки без совпадений
+    -H, --with-filename       выводить имя файла перед строкой
+    -h, --no-filename         не выводить имя файла
+        --color[=КОГДА]       подсвечивать совпадения: never (по умолчанию), always
+                              или auto (при выводе в терминал, без КОГДА)
+        --json                записи JSON Lines: файл, номер и смещение строки, текст,
+                              границы совпадений в байтах
+    -r, --recursive           искать в файлах каталогов, кроме символьных ссылок
+    -R, --dereference-recursive
+                              то же с переходом по символьным ссылкам
+        --include=ШАБЛОН      искать только в файлах с подходящим именем (*.rs)
+        --exclude=ШАБЛОН      пропускать файлы с подходящим именем
+        --exclude-dir=ШАБЛОН  не заходить в каталоги с подходящим именем
+        --no-ignore           не учитывать .gitignore и .ignore при обходе
+        --help                эта справка
+код выхода: 0 - есть выбранные строки, 1 - нет, 2 - ошибка";
+
+// параметры; у --color значение необязательно и пишется только через '='
+const OPTIONS: [Opt; 27] = [
+    Opt::both('A', "after-context").value(),
+    Opt::both('B', "before-context").value(),
+    Opt::both('C', "context").value(),
+    Opt::both('c', "count"),
+    Opt::both('l', "files-with-matches"),
+    Opt::both('L', "files-without-match"),
+    Opt::both('m', "max-count").value(),
+    Opt::both('o', "only-matching"),
+    Opt::both('e', "regexp").value(),
+    Opt::both('f', "file").value(),
+    Opt::both('F', "fixed-strings"),
+    Opt::both('i', "ignore-case"),
+    Opt::both('w', "word-regexp"),
+    Opt::both('x', "line-regexp"),
+    Opt::both('n', "line-number"),
+    Opt::both('v', "invert-match"),
+    Opt::both('H', "with-filename"),
+    Opt::both('h', "no-filename"),
+    Opt::long("color").optional_value(),
+    Opt::long("json"),
+    Opt::both('r', "recursive"),
+    Opt::both('R', "dereference-recursive"),
+    Opt::long("include").value(),
+    Opt::long("exclude").value(),
+    Opt::long("exclude-dir").value(),
+    Opt::long("no-ignore"),
+    Opt::long("help"),
+];
+
+// когда подсвечивать вывод
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Color {
+    #[default]
+    Never,
+    Always,
+    // при выводе в терминал
+    Auto,
 }
 
-// находит совпадающие с regex строки и возвращает их индекс в HashMap
-fn get_regex_match_ids(
-    input_strings: &Vec<String>,
-    regex_pattern: String,
-    register_ignore: bool,
-) -> HashSet<usize> {
-    // массив для совпадающих айди
-    let mut matching_ids: HashSet<usize> = HashSet::new();
-
-    // если нужно игнорировать регистр
-    let regex_opinionated = match register_ignore {
-        true => regex_pattern.to_lowercase(),
-        false => regex_pattern,
-    };
+// разобранные аргументы
+#[derive(Debug, Default)]
+struct Options {
+    // -e или первый операнд
+    patterns: Vec<String>,
+    // -f
+    pattern_files: Vec<String>,
+    matching: MatchOptions,
+    // файлы и каталоги, "-" - stdin
+    files: Vec<String>,
+    walk: WalkOptions,
+    // -H / -h, по умолчанию - имя выводится, если входов может быть больше одного
+    with_filename: Option<bool>,
+    search: SearchOptions,
+    count: bool,
+    // -l - Some(true), -L - Some(false): выводить имена файлов с выбранными строками или без
+    list: Option<bool>,
+    only_matching: bool,
+    line_number: bool,
+    color: Color,
+    json: bool,
+}
 
-    // создание объекта regex
-    let re = Regex::new(&regex_opinionated).unwrap();
+// параметры по мере разбора: -C задаёт контекст, не заданный явно через -A и -B
+#[derive(Debug, Default)]
+struct Parsed {
+    options: Options,
+    before: Option<usize>,
+    after: Option<usize>,
+    context: Option<usize>,
+}
 
-    // запись id совпадений
-    for (i, line) in input_strings.iter().enumerate() {
-        // если нужно игнорировать регистр
-        let line_opinionated = match register_ignore {
-            true => line.to_lowercase(),
-            false => line.to_owned(),
+impl Parsed {
+    // применение короткого параметра
+    fn apply(&mut self, flag: char, value: Option<String>) -> Result<(), String> {
+        let lines = |value: Option<String>| {
+            let value = value.unwrap_or_default();
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("неверное число строк контекста: {:?}", value))
         };
 
-        // есть ли совпадение
-        if re.is_match(&line_opinionated) {
-            matching_ids.insert(i);
+        match flag {
+            'A' => self.after = Some(lines(value)?),
+            'B' => self.before = Some(lines(value)?),
+            'C' => self.context = Some(lines(value)?),
+            'c' => self.options.count = true,
+            'l' => self.options.list = Some(true),
+            'L' => self.options.list = Some(false),
+            'm' => {
+                let value = value.unwrap_or_default();
+                let max = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("неверное число строк: {:?}", value))?;
+                self.options.search.max_count = Some(max);
+            }
+            'o' => self.options.only_matching = true,
+            'e' => self.options.patterns.extend(value),
+            'f' => self.options.pattern_files.extend(value),
+            'F' => self.options.matching.fixed = true,
+            'i' => self.options.matching.ignore_case = true,
+            'w' => self.options.matching.word = true,
+            'x' => self.options.matching.line = true,
+            'n' => self.options.line_number = true,
+            'v' => self.options.search.invert = true,
+            'H' => self.options.with_filename = Some(true),
+            'h' => self.options.with_filename = Some(false),
+            'r' => self.options.walk.recursive = true,
+            'R' => self.options.walk.follow_links = true,
+            // неизвестные параметры отсеивает Parser
+            _ => {}
         }
+        Ok(())
     }
 
-    matching_ids
-}
-
-// находит совпадающие с фиксированной строкой строки из записывает их индекс
-fn get_fixed_match_ids(
-    input_strings: &Vec<String>,
-    regex_pattern: String,
-    register_ignore: bool,
-) -> HashSet<usize> {
-    // массив для совпадающих айди
-    let mut matching_ids: HashSet<usize> = HashSet::new();
-
-    // если нужно игнорировать регистр
-    let regex_opinionated = match register_ignore {
-        true => regex_pattern.to_lowercase(),
-        false => regex_pattern.to_owned(),
-    };
-
-    // запись id совпадений
-    for (i, line) in input_strings.iter().enumerate() {
-        // если нужно игнорировать регистр
-        let line_opinionated = match register_ignore {
-            true => line.to_lowercase(),
-            false => line.to_owned(),
-        };
-
-        // есть ли совпадение
-        if line_opinionated == regex_opinionated {
-            matching_ids.insert(i);
+    // применение параметра без короткой формы
+    fn apply_long(&mut self, name: &str, value: Option<String>) -> Result<(), String> {
+        match name {
+            "include" => self.options.walk.include.extend(value),
+            "exclude" => self.options.walk.exclude.extend(value),
+            "exclude-dir" => self.options.walk.exclude_dir.extend(value),
+            "no-ignore" => self.options.walk.no_ignore = true,
+            "color" => {
+                self.options.color = match value.as_deref() {
+                    None | Some("auto") => Color::Auto,
+                    Some("always") => Color::Always,
+                    Some("never") => Color::Never,
+                    Some(value) => return Err(format!("неверное значение --color: {:?}", value)),
+                }
+            }
+            "json" => self.options.json = true,
+            "help" => {
+                println!("{}", USAGE);
+                exit(0);
+            }
+            _ => {}
         }
+        Ok(())
     }
-
-    matching_ids
 }
 
-// возвращяет HashMap с id с N строками до и изначальными id
-fn get_required_ids_before(
-    ids_raw: HashSet<usize>,
-    adjacent_lines_num: usize,
-) -> HashSet<usize> {
-    let mut ids_processed: HashSet<usize> = HashSet::new();
-
-    for id in ids_raw {
-        let low = match id < adjacent_lines_num {
-            false => id - adjacent_lines_num,
-            true => 0
-        };
+// разбор аргументов командной строки (без имени программы)
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut parsed = Parsed::default();
+    let mut operands = Vec::new();
 
-        for id_new in low..=id {
-            ids_processed.insert(id_new);
+    for arg in Parser::new(&OPTIONS, args) {
+        match arg? {
+            Arg::Short(flag, value) => parsed.apply(flag, value)?,
+            Arg::Long(name, value) => parsed.apply_long(name, value)?,
+            Arg::Operand(operand) => operands.push(operand),
         }
     }
 
-    ids_processed
-}
-
-// возвращяет HashMap с id с N строками после и изначальными id
-fn get_required_ids_after(
-    ids_raw: HashSet<usize>,
-    max_id: usize,
-    adjacent_lines_num: usize,
-) -> HashSet<usize> {
-    let mut ids_processed: HashSet<usize> = HashSet::new();
+    let mut operands = operands.into_iter();
+    let mut options = parsed.options;
+    // без -e и -f шаблон - первый операнд
+    if options.patterns.is_empty() && options.pattern_files.is_empty() {
+        options
+            .patterns
+            .push(operands.next().ok_or("не задан шаблон")?);
+    }
+    options.files = operands.collect();
 
-    for id in ids_raw {
-        let high = (id + adjacent_lines_num).min(max_id);
+    if options.json && (options.count || options.list.is_some() || options.only_matching) {
+        return Err("--json несовместим с -c, -l, -L и -o".to_string());
+    }
+    // для -l и -L хватает первой выбранной строки
+    if options.list.is_some() {
+        options.search.max_count = Some(options.search.max_count.map_or(1, |max| max.min(1)));
+    }
+    // с -c, -l и -L контекст не выводится, в --json записи не разделяются
+    if !options.count && options.list.is_none() {
+        options.search.before = parsed.before.or(parsed.context).unwrap_or(0);
+        options.search.after = parsed.after.or(parsed.context).unwrap_or(0);
+        options.search.separators = !options.json
+            && (parsed.before.is_some() || parsed.after.is_some() || parsed.context.is_some());
+    }
+    Ok(options)
+}
 
-        for id_new in id..=high {
-            ids_processed.insert(id_new);
+// шаблоны -e и из файлов -f: строка файла - шаблон, пустой файл - ни одного шаблона
+fn read_patterns(options: &Options) -> Result<Vec<String>, String> {
+    let mut patterns = options.patterns.clone();
+    for name in &options.pattern_files {
+        let text = if name == "-" {
+            io::read_to_string(io::stdin())
+        } else {
+            fs::read_to_string(name)
         }
+        .map_err(|err| format!("{}: {}", name, args::io_error(&err)))?;
+        patterns.extend(text.lines().map(String::from));
     }
-
-    ids_processed
+    Ok(patterns)
 }
 
-// возвращяет HashMap с id с N строками вокруг и изначальными id
-fn get_required_ids_around(
-    ids_raw: HashSet<usize>,
-    max_id: usize,
-    adjacent_lines_num: usize,
-) -> HashSet<usize> {
-    let mut ids_processed: HashSet<usize> = HashSet::new();
-
-    for id in ids_raw {
-        let low = match id < adjacent_lines_num {
-            false => id - adjacent_lines_num,
-            true => 0
+// поиск в одном входе с выводом в out, двоичные файлы - без совпадений
+fn search_input(
+    searcher: &Searcher,
+    options: &Options,
+    input: &Input,
+    with_filename: bool,
+    color: bool,
+    out: &mut dyn Write,
+) -> io::Result<u64> {
+    let mut reader: Box<dyn BufRead> = match input {
+        Input::Stdin => Box::new(BufReader::with_capacity(BINARY_CHECK_SIZE, io::stdin())),
+        Input::File(path) => Box::new(BufReader::with_capacity(
+            BINARY_CHECK_SIZE,
+            File::open(path)?,
+        )),
+    };
+
+    let binary = files::is_binary(&mut reader)?;
+    let name = input.name();
+
+    if options.json {
+        let mut printer = JsonPrinter {
+            out,
+            matcher: searcher.matcher(),
+            name: &name,
+        };
+        return if binary {
+            Ok(0)
+        } else {
+            searcher.search(reader, &mut printer)
         };
-        let high = (id + adjacent_lines_num).min(max_id);
+    }
 
-        for id_new in low..=high {
-            ids_processed.insert(id_new);
+    let mut printer = Printer {
+        out,
+        matcher: searcher.matcher(),
+        line_number: options.line_number,
+        only_matching: options.only_matching,
+        color,
+        name: with_filename.then_some(name.as_str()),
+    };
+    // двоичный вход виден только в -c (0) и -L, строки из него не печатаются
+    let selected = if options.count || options.list.is_some() {
+        if binary {
+            0
+        } else {
+            searcher.search(reader, &mut Discard)?
         }
+    } else if binary {
+        return Ok(0);
+    } else {
+        return searcher.search(reader, &mut printer);
+    };
+
+    match options.list {
+        Some(with_matches) if (selected > 0) == with_matches => printer.file_name(&name)?,
+        Some(_) => {}
+        None => printer.count(selected)?,
     }
+    Ok(selected)
+}
 
-    ids_processed
+// итог поиска по всем входам
+#[derive(Debug, Default)]
+struct Outcome {
+    // есть выбранные строки
+    selected: bool,
+    // были ошибки с отдельными входами
+    failed: bool,
 }
 
-fn get_matches(
-    input_strings: Vec<String>,
-    regex_pattern: String,
-    mode: String,
-    invert: bool,
-    fixed: bool,
-    register_ignore: bool,
-    adj_mode: String,
-    adjacent_lines_num: usize,
-) {
-    // количество входных строк
-    let input_len = input_strings.len();
-
-    // HashSet с id совпавших строк
-    let matched_ids: HashSet<usize> = match fixed {
-        true => get_fixed_match_ids(&input_strings, regex_pattern, register_ignore),
-        false => get_regex_match_ids(&input_strings, regex_pattern, register_ignore),
+// ошибка входа: io::Error нужна, чтобы отличить закрытый вывод, текст - для stderr
+type InputError = (io::Error, String);
+
+// поиск по всем входам: единственный вход выводится сразу, несколько - параллельно
+// пачками, вывод пачки печатается в порядке входов
+fn run(options: &Options) -> Result<Outcome, String> {
+    let matcher = Matcher::new(&read_patterns(options)?, options.matching)?;
+    let searcher = Searcher::new(matcher, options.search);
+    let walker = Walker::new(options.walk.clone())?;
+
+    let inputs = walker.inputs(&options.files);
+    // как в GNU grep: с -r имя выводится, если обходится каталог
+    let with_filename = options.with_filename.unwrap_or(
+        options.files.len() > 1
+            || (walker.recursive()
+                && (options.files.is_empty() || options.files.iter().any(|f| files::is_dir(f)))),
+    );
+
+    let color = match options.color {
+        Color::Never => false,
+        Color::Always => true,
+        Color::Auto => io::stdout().is_terminal(),
     };
 
-    // HashSet с id совпавших и соседних строк - в зависимости от аргумента
-    let mut processed_ids = match adj_mode.as_str() {
-        "-A" => get_required_ids_after(matched_ids, input_len-1, adjacent_lines_num),
-        "-B" => get_required_ids_before(matched_ids, adjacent_lines_num),
-        "-C" => get_required_ids_around(matched_ids, input_len-1, adjacent_lines_num),
-        _ => matched_ids,
+    let search = |input: &Result<Input, String>, out: &mut dyn Write| match input {
+        Ok(input) => {
+            search_input(&searcher, options, input, with_filename, color, out).map_err(|err| {
+                let message = format!("{}: {}", input.name(), args::io_error(&err));
+                (err, message)
+            })
+        }
+        Err(err) => Err((io::Error::other(err.clone()), err.clone())),
     };
 
-    // HashSet исключая id совпавших и соседних строк - в зависимости от аргумента
-    if invert {
-        let all_ids: HashSet<usize> = (0..input_len).collect();
-        processed_ids = &all_ids - &processed_ids;
-    }
+    let mut outcome = Outcome::default();
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut printed = false;
+
+    for batch in inputs.chunks(PARALLEL_BATCH) {
+        let results: Vec<(Vec<u8>, Result<u64, InputError>)> = if inputs.len() == 1 {
+            // единственный вход - без буфера в памяти
+            vec![(Vec::new(), search(&batch[0], &mut out))]
+        } else {
+            batch
+                .par_iter()
+                .map(|input| {
+                    let mut output = Vec::new();
+                    let result = search(input, &mut output);
+                    (output, result)
+                })
+                .collect()
+        };
 
-    // вывод нужой информации в зависимости от входного аргумента
-    match mode.as_str() {
-        // печать количества строк
-        "-c" => {
-            println!("{}", processed_ids.len());
-        },
-        // печать id
-        "-n" => {
-            for i in 0..input_len {
-                if processed_ids.contains(&i) {
-                    println!("{}", i);
-                }
+        for (output, result) in results {
+            // группы контекста разных файлов тоже разделяются
+            let written = if options.search.separators && printed && !output.is_empty() {
+                output::paint(&mut out, color, SEPARATOR_COLOR, b"--")
+                    .and_then(|_| out.write_all(b"\n"))
+            } else {
+                Ok(())
             }
-        },
-        // печать строк
-        _ => {
-            for (i, line) in input_strings.iter().enumerate() {
-                if processed_ids.contains(&i) {
-                    println!("{}", line);
+            .and_then(|_| out.write_all(&output));
+            printed |= !output.is_empty();
+
+            match written.map_err(|err| (err, String::new())).and(result) {
+                Ok(selected) => outcome.selected |= selected > 0,
+                // вывод закрыт читателем (t5 ... | head)
+                Err((err, _)) if err.kind() == ErrorKind::BrokenPipe => {
+                    outcome.selected = true;
+                    return Ok(outcome);
+                }
+                Err((_, message)) => {
+                    let _ = out.flush();
+                    eprintln!("t5: {}", message);
+                    outcome.failed = true;
                 }
             }
         }
     }
-}
-
-// записывает в вектор и проверяет входные агрументы
-fn parse_args() -> (String, bool, bool, bool, String, String, String, usize) {
-    // возможные аргументы
-    let possible_arguments: HashSet<&str> =
-        HashSet::from(["-A", "-B", "-C", "-c", "-i", "-v", "-F", "-n"]);
 
-    // возможные типы -grep
-    let possible_types: HashSet<&str> = HashSet::from(["-c", "-n"]);
-
-    // возможные виды вывода соседних строк
-    let possible_adj_types: HashSet<&str> = HashSet::from(["-A", "-B", "-C"]);
-
-    // аргументы командной строки
-    let args: Vec<String> = env::args().collect();
-
-    // обработка аргументов
-    if args.len() < 3 {
-        panic!("недостаточное количество аргументов")
+    match out.flush() {
+        Err(err) if err.kind() != ErrorKind::BrokenPipe => Err(args::io_error(&err)),
+        _ => Ok(outcome),
     }
+}
 
-    // имя входного файла (предпоследний аргумент)
-    let input_file_name = args.last().unwrap().to_owned();
-    // regex - паттерн
-    let regex_string = args[args.len() - 2].to_owned();
-
-    // массив для обработанных аргументов
-    let mut command_arguments = HashSet::new();
-    // как выводить соседние линии
-    let mut adj_mode: String = String::new();
-    // по каком типу сортировать
-    let mut mode: String = String::new();
-    // инвертирована ли команда
-    let mut invert: bool = false;
-    // точное ли совпадение
-    let mut fixed: bool = false;
-    // игнорировать ли регистр
-    let mut register_ignore: bool = false;
-    // количество линий для вывода
-    let mut adjacent_lines_num: usize = 0;
-
-    let mut i: usize = 1;
-    // проверка и парсинг аргументов
-    while i < args.len() - 2 {
-        let argument = &args[i];
-
-        // если аргумент не присутсвует в сете возможных
-        if !possible_arguments.contains(argument.as_str()) {
-            panic!("неверный аргумент {:?}", argument);
-        }
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
 
-        // если аргумент повторный
-        if command_arguments.contains(argument.as_str()) {
-            panic!("повторный аргумент {:?}", argument);
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("t5: {}\nподробнее: t5 --help", err);
+            exit(2);
         }
+    };
 
-        // как выводить соседние линии
-        if possible_adj_types.contains(argument.as_str()) {
-            if adj_mode.is_empty() {
-                adjacent_lines_num = args[i + 1]
-                    .parse::<usize>()
-                    .expect("неверное число соседних строк для вывода");
-
-                adj_mode = argument.to_owned();
-                command_arguments.insert(argument.as_str());
-                i += 2;
-                continue;
-            } else {
-                panic!("несколько способов вывода соседних линий")
-            }
-        }
-        // возможные варианты выведения строк
-        else if possible_types.contains(argument.as_str()) {
-            if mode.is_empty() {
-                mode = argument.to_owned();
-            } else {
-                panic!("несколько способов сортировки задано в аргументах")
-            }
-        }
-        // инвертирована ли команда
-        else if argument == "-v" {
-            invert = true;
-        }
-        // проверять отсортированы ли строчки
-        else if argument == "-F" {
-            fixed = true;
-        }
-        // игнорировать ли регистр
-        else if argument == "-i" {
-            register_ignore = true;
+    match run(&options) {
+        Ok(Outcome { failed: true, .. }) => exit(2),
+        Ok(Outcome { selected: true, .. }) => {}
+        Ok(_) => exit(1),
+        Err(err) => {
+            eprintln!("t5: {}", err);
+            exit(2);
         }
+    }
+}
 
-        command_arguments.insert(argument.as_str());
+#[cfg(test)]
+mod tests {
+    use super::{parse_args, read_patterns, search_input};
+    use crate::files::Input;
+    use crate::matcher::Matcher;
+    use crate::search::Searcher;
+    use std::fs;
+
+    // разбор строки аргументов через пробел
+    fn parse(args: &str) -> Result<super::Options, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        parse_args(&args)
+    }
 
-        i += 1
+    #[test]
+    // -C задаёт контекст, явные -A и -B важнее; с -c контекста нет
+    fn test_parse_args() {
+        let options = parse("-C 2 -A1 -vi x file.txt").unwrap();
+        assert_eq!((options.search.before, options.search.after), (2, 1));
+        assert!(options.search.invert && options.matching.ignore_case && options.search.separators);
+        assert_eq!(options.patterns, ["x"]);
+        assert_eq!(options.files, ["file.txt"]);
+
+        let options = parse("-B1 --context=3 -c -- -x").unwrap();
+        assert_eq!((options.search.before, options.search.after), (0, 0));
+        assert!(!options.search.separators);
+        assert_eq!(options.patterns, ["-x"]);
+        assert!(options.files.is_empty());
+
+        let options = parse("-rH --include=*.rs --exclude *.md --no-ig p a b").unwrap();
+        assert!(options.walk.recursive && options.walk.no_ignore);
+        assert_eq!(options.walk.include, ["*.rs"]);
+        assert_eq!(options.walk.exclude, ["*.md"]);
+        assert_eq!(options.with_filename, Some(true));
+        assert_eq!(options.files, ["a", "b"]);
+
+        // с -e и -f все операнды - файлы
+        let options = parse("-wx -e a --regexp=b -f p.txt c").unwrap();
+        assert_eq!(options.patterns, ["a", "b"]);
+        assert_eq!(options.pattern_files, ["p.txt"]);
+        assert_eq!(options.files, ["c"]);
+        assert!(options.matching.word && options.matching.line);
+
+        // -l берёт первую выбранную строку и без контекста; --color без значения - auto
+        let options = parse("-m5 -C1 -L --color p").unwrap();
+        assert_eq!(options.list, Some(false));
+        assert_eq!(options.search.max_count, Some(1));
+        assert_eq!(options.search.after, 0);
+        assert_eq!(options.color, super::Color::Auto);
+        assert!(options.files.is_empty());
+        let options = parse("--max-count 0 --colo=always -o p").unwrap();
+        assert_eq!(options.search.max_count, Some(0));
+        assert_eq!(options.color, super::Color::Always);
+        assert!(options.only_matching);
+
+        for invalid in [
+            "",
+            "-A",
+            "-A x p",
+            "-q p",
+            "--co=1 p",
+            "--count=1 p",
+            "--include",
+            "-m -1 p",
+            "--color=red p",
+            "--json -c p",
+        ] {
+            assert!(parse(invalid).is_err(), "{:?}", invalid);
+        }
     }
 
-    (
-        mode,
-        invert,
-        fixed,
-        register_ignore,
-        input_file_name,
-        regex_string,
-        adj_mode,
-        adjacent_lines_num,
-    )
-}
+    // вывод t5 с аргументами args для одного файла, как при запуске из командной строки
+    fn grep(args: &str, path: &str) -> String {
+        let options = parse(&format!("{} {}", args, path)).unwrap();
+        let matcher = Matcher::new(&read_patterns(&options).unwrap(), options.matching).unwrap();
+        let searcher = Searcher::new(matcher, options.search);
+        let mut out = Vec::new();
+        search_input(
+            &searcher,
+            &options,
+            &Input::File(path.into()),
+            options.with_filename.unwrap_or(false),
+            false,
+            &mut out,
+        )
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    }
 
-fn main() {
-    // запарсить аргументы
-    let (
-        mode,
-        invert,
-        fixed,
-        register_ignore,
-        input_file_name,
-        regex_string,
-        adj_mode,
-        adjacent_lines_num,
-    ) = parse_args();
-    // чтение колонок для матчинга
-    let lines_to_match = read_lines_from_file(&input_file_name);
-
-    // получение совпадений и вывод в стд
-    get_matches(
-        lines_to_match,
-        regex_string,
-        mode,
-        invert,
-        fixed,
-        register_ignore,
-        adj_mode,
-        adjacent_lines_num,
-    );
+    #[test]
+    // двоичный файл: без -c и -L ничего не выводится, с -c - 0
+    fn test_binary_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bin");
+        fs::write(&path, b"foo\0bar\n").unwrap();
+        let path = path.to_str().unwrap();
+
+        assert_eq!(grep("foo", path), "");
+        assert_eq!(grep("-H foo", path), "");
+        assert_eq!(grep("-c foo", path), "0\n");
+        assert_eq!(grep("-l foo", path), "");
+        assert_eq!(grep("-L foo", path), format!("{}\n", path));
+    }
 }
@@ -0,0 +1,250 @@
+//! проверка строки на совпадение с шаблонами
+//!
+//! регулярные выражения объединяются в одно через `|`, -i - флаг регулярного выражения,
+//! -w и -x - обрамление выражения. Строки -F ищутся как подстроки автоматом Ахо - Корасик,
+//! а с -w, -x или -i не в ASCII - экранированным регулярным выражением.
+//! Совпадения ищутся как диапазоны байт строки: по ним подсвечиваются и выводятся части строк
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, Input, MatchKind};
+use regex::bytes::{Regex, RegexBuilder};
+use std::ops::Range;
+
+// способ сравнения
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchOptions {
+    // -F: шаблоны - строки, а не регулярные выражения
+    pub fixed: bool,
+    // -i: без учёта регистра
+    pub ignore_case: bool,
+    // -w: совпадение - целые слова (буквы, цифры и _)
+    pub word: bool,
+    // -x: совпадение - строка целиком
+    pub line: bool,
+}
+
+// как ищутся шаблоны
+#[derive(Debug, Clone)]
+enum Engine {
+    Regex(Regex),
+    Fixed(AhoCorasick),
+    // ни одного шаблона (-f с пустым файлом) - ни одна строка не совпадает
+    Nothing,
+}
+
+// скомпилированные шаблоны
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    engine: Engine,
+}
+
+impl Matcher {
+    // шаблон с переводами строк - несколько шаблонов, как в grep
+    pub fn new(patterns: &[String], options: MatchOptions) -> Result<Self, String> {
+        let patterns: Vec<&str> = patterns
+            .iter()
+            .flat_map(|pattern| pattern.split('\n'))
+            .collect();
+        if patterns.is_empty() {
+            return Ok(Matcher {
+                engine: Engine::Nothing,
+            });
+        }
+
+        let ascii_only = patterns.iter().all(|pattern| pattern.is_ascii());
+        if options.fixed && !options.word && !options.line && (!options.ignore_case || ascii_only) {
+            let automaton = AhoCorasickBuilder::new()
+                .ascii_case_insensitive(options.ignore_case)
+                .match_kind(MatchKind::LeftmostLongest)
+                .build(&patterns)
+                .map_err(|err| format!("неверный шаблон: {}", err))?;
+            return Ok(Matcher {
+                engine: Engine::Fixed(automaton),
+            });
+        }
+
+        let alternatives: Vec<String> = patterns
+            .iter()
+            .map(|&pattern| {
+                if options.fixed {
+                    regex::escape(pattern)
+                } else {
+                    pattern.to_string()
+                }
+            })
+            .map(|pattern| format!("(?:{})", pattern))
+            .collect();
+        let pattern = alternatives.join("|");
+        let pattern = if options.line {
+            format!("^(?:{})$", pattern)
+        } else if options.word {
+            // перед совпадением и после него - граница строки или не буква
+            format!(r"\b{{start-half}}(?:{})\b{{end-half}}", pattern)
+        } else {
+            pattern
+        };
+
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(options.ignore_case)
+            .build()
+            .map_err(|err| format!("неверный шаблон: {}", err))?;
+        Ok(Matcher {
+            engine: Engine::Regex(regex),
+        })
+    }
+
+    // есть ли в строке совпадение
+    pub fn is_match(&self, line: &[u8]) -> bool {
+        match &self.engine {
+            Engine::Regex(regex) => regex.is_match(line),
+            Engine::Fixed(automaton) => automaton.is_match(line),
+            Engine::Nothing => false,
+        }
+    }
+
+    // первое совпадение, начинающееся не раньше start; ^ и границы слов учитывают
+    // строку целиком
+    pub fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        match &self.engine {
+            Engine::Regex(regex) => regex.find_at(line, start).map(|found| found.range()),
+            Engine::Fixed(automaton) => automaton
+                .find(Input::new(line).range(start..))
+                .map(|found| found.range()),
+            Engine::Nothing => None,
+        }
+    }
+
+    // непересекающиеся непустые совпадения слева направо
+    pub fn find_iter<'a>(&'a self, line: &'a [u8]) -> impl Iterator<Item = Range<usize>> + 'a {
+        let mut start = 0;
+        std::iter::from_fn(move || {
+            while start <= line.len() {
+                let found = self.find_at(line, start)?;
+                if found.is_empty() {
+                    start = found.end + 1;
+                } else {
+                    start = found.end;
+                    return Some(found);
+                }
+            }
+            None
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MatchOptions, Matcher};
+
+    // совпадающие строки
+    fn matching<'a>(patterns: &[&str], options: MatchOptions, lines: &[&'a str]) -> Vec<&'a str> {
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        let matcher = Matcher::new(&patterns, options).unwrap();
+        lines
+            .iter()
+            .copied()
+            .filter(|line| matcher.is_match(line.as_bytes()))
+            .collect()
+    }
+
+    #[test]
+    // -i через флаг выражения не ломает экранирование вроде \D
+    fn test_ignore_case() {
+        let options = MatchOptions {
+            ignore_case: true,
+            ..MatchOptions::default()
+        };
+        assert_eq!(
+            matching(&[r"hello\D"], options, &["HELLO!", "hello1", "Hello world"]),
+            ["HELLO!", "Hello world"]
+        );
+        assert_eq!(
+            matching(&["ПРИВЕТ"], options, &["привет", "пока"]),
+            ["привет"]
+        );
+        assert!(Matcher::new(&["(".to_string()], options).is_err());
+    }
+
+    #[test]
+    // -F - подстроки, несколько шаблонов сразу, символы выражений - обычные символы
+    fn test_fixed() {
+        let fixed = MatchOptions {
+            fixed: true,
+            ..MatchOptions::default()
+        };
+        let lines = ["a.b", "axb", "x(y", "ничего", "HELLO"];
+        assert_eq!(matching(&["a.b"], fixed, &lines), ["a.b"]);
+        assert_eq!(
+            matching(&["(", "b\nич"], fixed, &lines),
+            ["a.b", "axb", "x(y", "ничего"]
+        );
+
+        let ignore_case = MatchOptions {
+            ignore_case: true,
+            ..fixed
+        };
+        assert_eq!(matching(&["hell"], ignore_case, &lines), ["HELLO"]);
+        assert_eq!(matching(&["НИЧ"], ignore_case, &lines), ["ничего"]);
+        assert!(matching(&[], fixed, &lines).is_empty());
+        assert_eq!(matching(&[""], fixed, &lines).len(), lines.len());
+    }
+
+    #[test]
+    // -w - целые слова, -x - строки целиком
+    fn test_word_line() {
+        let word = MatchOptions {
+            word: true,
+            ..MatchOptions::default()
+        };
+        let lines = ["foo", "foobar", "a foo.", "_foo", "foo_bar foo", "фоо foo"];
+        assert_eq!(
+            matching(&["foo"], word, &lines),
+            ["foo", "a foo.", "foo_bar foo", "фоо foo"]
+        );
+        assert_eq!(matching(&["a", "o\\."], word, &lines), ["a foo."]);
+
+        let line = MatchOptions {
+            line: true,
+            fixed: true,
+            ..MatchOptions::default()
+        };
+        assert_eq!(
+            matching(&["foo", "a foo."], line, &lines),
+            ["foo", "a foo."]
+        );
+        let line = MatchOptions {
+            fixed: false,
+            ..line
+        };
+        assert_eq!(matching(&["fo+|_.*"], line, &lines), ["foo", "_foo"]);
+    }
+
+    #[test]
+    // совпадения - диапазоны байт, пустые пропускаются
+    fn test_find_iter() {
+        let spans = |patterns: &[&str], options: MatchOptions, line: &str| {
+            let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+            let matcher = Matcher::new(&patterns, options).unwrap();
+            matcher.find_iter(line.as_bytes()).collect::<Vec<_>>()
+        };
+        let fixed = MatchOptions {
+            fixed: true,
+            ..MatchOptions::default()
+        };
+        let word = MatchOptions {
+            word: true,
+            ..MatchOptions::default()
+        };
+
+        assert_eq!(
+            spans(&["o*"], MatchOptions::default(), "foo bo"),
+            [1..3, 5..6]
+        );
+        assert_eq!(spans(&["ab", "abc", "c"], fixed, "abcabc"), [0..3, 3..6]);
+        assert_eq!(spans(&["щ"], fixed, "ещё щ"), [2..4, 7..9]);
+        assert_eq!(
+            spans(&["foo"], word, "foo foofoo _foo foo."),
+            [0..3, 16..19]
+        );
+        assert!(spans(&[""], fixed, "abc").is_empty());
+        assert!(spans(&[], fixed, "abc").is_empty());
+    }
+}
@@ -0,0 +1,280 @@
+//! вывод найденного: строки как у GNU grep (с подсветкой совпадений, -o, -c, -l/-L)
+//! или записи JSON Lines с диапазонами совпадений; всё строится на Matcher::find_iter
+use crate::matcher::Matcher;
+use crate::search::{Line, Sink};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+// цвета GNU grep по умолчанию: имя файла, номер строки, разделители и совпадение
+pub const FILE_COLOR: &str = "35";
+pub const LINE_NUMBER_COLOR: &str = "32";
+pub const SEPARATOR_COLOR: &str = "36";
+pub const MATCH_COLOR: &str = "01;31";
+
+// текст, с подсветкой - в escape-последовательностях цвета, как у GNU grep
+pub fn paint<W: Write>(out: &mut W, color: bool, code: &str, text: &[u8]) -> io::Result<()> {
+    if color {
+        write!(out, "\x1b[{}m\x1b[K", code)?;
+        out.write_all(text)?;
+        out.write_all(b"\x1b[m\x1b[K")
+    } else {
+        out.write_all(text)
+    }
+}
+
+// вывод строк как у grep
+pub struct Printer<'a, W: Write> {
+    pub out: W,
+    pub matcher: &'a Matcher,
+    // -n: номер строки перед строкой
+    pub line_number: bool,
+    // -o: только совпадения, каждое с новой строки
+    pub only_matching: bool,
+    // --color: подсвечивать имя, номер, разделители и совпадения
+    pub color: bool,
+    // имя файла перед строкой
+    pub name: Option<&'a str>,
+}
+
+impl<W: Write> Printer<'_, W> {
+    fn paint(&mut self, code: &str, text: &[u8]) -> io::Result<()> {
+        paint(&mut self.out, self.color, code, text)
+    }
+
+    // имя файла и номер строки, после них ':' у выбранной строки и '-' у контекста
+    fn prefix(&mut self, line_number: u64, separator: &str) -> io::Result<()> {
+        if let Some(name) = self.name {
+            self.paint(FILE_COLOR, name.as_bytes())?;
+            self.paint(SEPARATOR_COLOR, separator.as_bytes())?;
+        }
+        if self.line_number {
+            let number = line_number.to_string();
+            self.paint(LINE_NUMBER_COLOR, number.as_bytes())?;
+            self.paint(SEPARATOR_COLOR, separator.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    // строка с подсвеченными совпадениями, с -o - только совпадения
+    fn line(&mut self, line: Line, separator: &str) -> io::Result<()> {
+        let matcher = self.matcher;
+        if self.only_matching {
+            for span in matcher.find_iter(line.text) {
+                self.prefix(line.number, separator)?;
+                self.paint(MATCH_COLOR, &line.text[span])?;
+                self.out.write_all(b"\n")?;
+            }
+            return Ok(());
+        }
+
+        self.prefix(line.number, separator)?;
+        if self.color {
+            let mut printed = 0;
+            for span in matcher.find_iter(line.text) {
+                self.out.write_all(&line.text[printed..span.start])?;
+                printed = span.end;
+                self.paint(MATCH_COLOR, &line.text[span])?;
+            }
+            self.out.write_all(&line.text[printed..])?;
+        } else {
+            self.out.write_all(line.text)?;
+        }
+        self.out.write_all(b"\n")
+    }
+
+    // -c: число выбранных строк после имени файла
+    pub fn count(&mut self, selected: u64) -> io::Result<()> {
+        if let Some(name) = self.name {
+            self.paint(FILE_COLOR, name.as_bytes())?;
+            self.paint(SEPARATOR_COLOR, b":")?;
+        }
+        writeln!(self.out, "{}", selected)
+    }
+
+    // -l и -L: имя файла, выводится всегда
+    pub fn file_name(&mut self, name: &str) -> io::Result<()> {
+        self.paint(FILE_COLOR, name.as_bytes())?;
+        self.out.write_all(b"\n")
+    }
+}
+
+impl<W: Write> Sink for Printer<'_, W> {
+    fn matched(&mut self, line: Line) -> io::Result<()> {
+        self.line(line, ":")
+    }
+
+    // с -o контекст не выводится, как у GNU grep, разделители групп остаются
+    fn context(&mut self, line: Line) -> io::Result<()> {
+        if self.only_matching {
+            return Ok(());
+        }
+        self.line(line, "-")
+    }
+
+    fn separator(&mut self) -> io::Result<()> {
+        self.paint(SEPARATOR_COLOR, b"--")?;
+        self.out.write_all(b"\n")
+    }
+}
+
+// с -c, -l и -L строки не выводятся, только считаются
+pub struct Discard;
+
+impl Sink for Discard {
+    fn matched(&mut self, _: Line) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn context(&mut self, _: Line) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn separator(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// совпадение в строке: границы в байтах строки
+#[derive(Debug, Serialize)]
+struct Submatch<'a> {
+    start: usize,
+    end: usize,
+    text: Cow<'a, str>,
+}
+
+// запись --json о строке; не UTF-8 в тексте заменяется на U+FFFD, границы - по исходным байтам
+#[derive(Debug, Serialize)]
+struct Record<'a> {
+    // "match" у выбранной строки, "context" у контекста
+    #[serde(rename = "type")]
+    kind: &'a str,
+    file: &'a str,
+    line_number: u64,
+    // смещение начала строки в файле в байтах
+    offset: u64,
+    line: Cow<'a, str>,
+    submatches: Vec<Submatch<'a>>,
+}
+
+// --json: запись на строку, разделителей групп нет
+pub struct JsonPrinter<'a, W: Write> {
+    pub out: W,
+    pub matcher: &'a Matcher,
+    pub name: &'a str,
+}
+
+impl<W: Write> JsonPrinter<'_, W> {
+    fn record(&mut self, kind: &str, line: Line) -> io::Result<()> {
+        let submatches = self
+            .matcher
+            .find_iter(line.text)
+            .map(|span| Submatch {
+                start: span.start,
+                end: span.end,
+                text: String::from_utf8_lossy(&line.text[span]),
+            })
+            .collect();
+        let record = Record {
+            kind,
+            file: self.name,
+            line_number: line.number,
+            offset: line.offset,
+            line: String::from_utf8_lossy(line.text),
+            submatches,
+        };
+        serde_json::to_writer(&mut self.out, &record)?;
+        self.out.write_all(b"\n")
+    }
+}
+
+impl<W: Write> Sink for JsonPrinter<'_, W> {
+    fn matched(&mut self, line: Line) -> io::Result<()> {
+        self.record("match", line)
+    }
+
+    fn context(&mut self, line: Line) -> io::Result<()> {
+        self.record("context", line)
+    }
+
+    fn separator(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JsonPrinter, Printer};
+    use crate::matcher::{MatchOptions, Matcher};
+    use crate::search::{Line, Sink};
+
+    fn matcher(pattern: &str) -> Matcher {
+        Matcher::new(&[pattern.to_string()], MatchOptions::default()).unwrap()
+    }
+
+    fn line(text: &str) -> Line<'_> {
+        Line {
+            number: 3,
+            offset: 10,
+            text: text.as_bytes(),
+        }
+    }
+
+    #[test]
+    // подсветка и -o в escape-последовательностях GNU grep
+    fn test_printer() {
+        let matcher = matcher("o+");
+        let mut printer = Printer {
+            out: Vec::new(),
+            matcher: &matcher,
+            line_number: true,
+            only_matching: false,
+            color: true,
+            name: Some("a"),
+        };
+        printer.matched(line("foo boo")).unwrap();
+        assert_eq!(
+            String::from_utf8(printer.out).unwrap(),
+            "\x1b[35m\x1b[Ka\x1b[m\x1b[K\x1b[36m\x1b[K:\x1b[m\x1b[K\
+             \x1b[32m\x1b[K3\x1b[m\x1b[K\x1b[36m\x1b[K:\x1b[m\x1b[K\
+             f\x1b[01;31m\x1b[Koo\x1b[m\x1b[K b\x1b[01;31m\x1b[Koo\x1b[m\x1b[K\n"
+        );
+
+        let mut printer = Printer {
+            out: Vec::new(),
+            matcher: &matcher,
+            line_number: false,
+            only_matching: true,
+            color: false,
+            name: Some("a"),
+        };
+        printer.matched(line("foo boo")).unwrap();
+        printer.context(line("oo")).unwrap();
+        printer.matched(line("x")).unwrap();
+        assert_eq!(String::from_utf8(printer.out).unwrap(), "a:oo\na:oo\n");
+    }
+
+    #[test]
+    // запись JSON с байтовыми границами совпадений
+    fn test_json() {
+        let matcher = matcher("щ");
+        let mut printer = JsonPrinter {
+            out: Vec::new(),
+            matcher: &matcher,
+            name: "a.txt",
+        };
+        printer.matched(line("ещё щ")).unwrap();
+        printer.context(line("x")).unwrap();
+        assert_eq!(
+            String::from_utf8(printer.out).unwrap(),
+            concat!(
+                r#"{"type":"match","file":"a.txt","line_number":3,"offset":10,"line":"ещё щ","#,
+                r#""submatches":[{"start":2,"end":4,"text":"щ"},{"start":7,"end":9,"text":"щ"}]}"#,
+                "\n",
+                r#"{"type":"context","file":"a.txt","line_number":3,"offset":10,"line":"x","#,
+                r#""submatches":[]}"#,
+                "\n"
+            )
+        );
+    }
+}
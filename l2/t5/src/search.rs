@@ -0,0 +1,304 @@
+//! поиск за один потоковый проход: строки перед совпадением (-B) ждут в кольцевом буфере,
+//! после совпадения (-A) выводится ещё столько строк, сколько осталось; в памяти - не больше
+//! B строк, сколько бы ни было строк во входе. После -m NUM выбранных строк поиск
+//! останавливается, дочитав только строки контекста -A
+use crate::matcher::Matcher;
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+
+// строка входа без перевода строки
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Line<'a> {
+    // номер с 1
+    pub number: u64,
+    // смещение начала строки во входе в байтах
+    pub offset: u64,
+    pub text: &'a [u8],
+}
+
+// получатель найденного
+pub trait Sink {
+    // выбранная строка: совпадающая, а с -v - несовпадающая
+    fn matched(&mut self, line: Line) -> io::Result<()>;
+    // строка контекста
+    fn context(&mut self, line: Line) -> io::Result<()>;
+    // разрыв между группами строк с контекстом ("--")
+    fn separator(&mut self) -> io::Result<()>;
+}
+
+// настройки поиска
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    // -B: строк контекста перед выбранной
+    pub before: usize,
+    // -A: строк контекста после выбранной
+    pub after: usize,
+    // -v: выбирать несовпадающие строки
+    pub invert: bool,
+    // -m: остановиться после стольких выбранных строк
+    pub max_count: Option<u64>,
+    // выводить "--" между группами: как в GNU grep, при любом заданном -A, -B или -C,
+    // даже нулевом
+    pub separators: bool,
+}
+
+// поиск по шаблону
+pub struct Searcher {
+    matcher: Matcher,
+    options: SearchOptions,
+}
+
+impl Searcher {
+    pub fn new(matcher: Matcher, options: SearchOptions) -> Self {
+        Searcher { matcher, options }
+    }
+
+    pub fn matcher(&self) -> &Matcher {
+        &self.matcher
+    }
+
+    // проход по входу, результат - число выбранных строк
+    pub fn search<R: BufRead, S: Sink>(&self, mut reader: R, sink: &mut S) -> io::Result<u64> {
+        let SearchOptions {
+            before,
+            after,
+            invert,
+            max_count,
+            separators,
+        } = self.options;
+        let limit_reached = |selected: u64| max_count.is_some_and(|max| selected >= max);
+
+        // последние строки перед текущей, ещё не выведенные: номер, смещение и текст
+        let mut ring: VecDeque<(u64, u64, Vec<u8>)> = VecDeque::with_capacity(before);
+        // сколько строк после выбранной ещё вывести
+        let mut after_left = 0;
+        let mut last_printed: Option<u64> = None;
+        let mut selected = 0;
+        let mut line = Vec::new();
+        let mut line_number = 0;
+        let mut next_offset = 0;
+
+        // после последней выбранной строки - только её контекст, даже совпадающие строки
+        while !(limit_reached(selected) && after_left == 0) {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                break;
+            }
+            let offset = next_offset;
+            next_offset += read as u64;
+            line_number += 1;
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            let current = Line {
+                number: line_number,
+                offset,
+                text: &line,
+            };
+
+            if !limit_reached(selected) && self.matcher.is_match(&line) != invert {
+                selected += 1;
+
+                let first = ring.front().map_or(line_number, |&(number, _, _)| number);
+                if separators && last_printed.is_some_and(|last| first > last + 1) {
+                    sink.separator()?;
+                }
+                for (number, offset, text) in ring.drain(..) {
+                    sink.context(Line {
+                        number,
+                        offset,
+                        text: &text,
+                    })?;
+                }
+                sink.matched(current)?;
+
+                last_printed = Some(line_number);
+                after_left = after;
+            } else if after_left > 0 {
+                sink.context(current)?;
+                last_printed = Some(line_number);
+                after_left -= 1;
+            } else if before > 0 {
+                // самая старая строка вытесняется, её буфер используется снова
+                let mut text = if ring.len() == before {
+                    ring.pop_front()
+                        .map(|(_, _, text)| text)
+                        .unwrap_or_default()
+                } else {
+                    Vec::new()
+                };
+                text.clear();
+                text.extend_from_slice(&line);
+                ring.push_back((line_number, offset, text));
+            }
+        }
+
+        Ok(selected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Line, SearchOptions, Searcher, Sink};
+    use crate::matcher::{MatchOptions, Matcher};
+    use std::io;
+
+    // найденное в виде строк как в выводе grep -n: "N:строка", "N-контекст", "--"
+    #[derive(Default)]
+    struct Lines(Vec<String>);
+
+    impl Sink for Lines {
+        fn matched(&mut self, line: Line) -> io::Result<()> {
+            self.0.push(format!(
+                "{}:{}",
+                line.number,
+                String::from_utf8_lossy(line.text)
+            ));
+            Ok(())
+        }
+
+        fn context(&mut self, line: Line) -> io::Result<()> {
+            self.0.push(format!(
+                "{}-{}",
+                line.number,
+                String::from_utf8_lossy(line.text)
+            ));
+            Ok(())
+        }
+
+        fn separator(&mut self) -> io::Result<()> {
+            self.0.push("--".to_string());
+            Ok(())
+        }
+    }
+
+    // поиск шаблона в тексте
+    fn search(pattern: &str, input: &str, options: SearchOptions) -> (u64, Vec<String>) {
+        let matcher = Matcher::new(&[pattern.to_string()], MatchOptions::default()).unwrap();
+        let searcher = Searcher::new(matcher, options);
+        let mut lines = Lines::default();
+        let selected = searcher.search(input.as_bytes(), &mut lines).unwrap();
+        (selected, lines.0)
+    }
+
+    #[test]
+    // группы контекста разделяются "--", пересекающиеся группы сливаются
+    fn test_context() {
+        let input = "a\nx\nb\nc\nd\nx\ne\nf\ng\nh\nx\nx\ni\n";
+        let options = SearchOptions {
+            before: 1,
+            after: 1,
+            separators: true,
+            ..SearchOptions::default()
+        };
+        assert_eq!(
+            search("x", input, options),
+            (
+                4,
+                [
+                    "1-a", "2:x", "3-b", "--", "5-d", "6:x", "7-e", "--", "10-h", "11:x", "12:x",
+                    "13-i"
+                ]
+                .map(String::from)
+                .to_vec()
+            )
+        );
+
+        let options = SearchOptions {
+            before: 2,
+            separators: true,
+            ..SearchOptions::default()
+        };
+        assert_eq!(
+            search("x", input, options).1,
+            ["1-a", "2:x", "--", "4-c", "5-d", "6:x", "--", "9-g", "10-h", "11:x", "12:x"]
+        );
+
+        // соседние группы без пропуска строк не разделяются
+        let options = SearchOptions {
+            after: 3,
+            separators: true,
+            ..SearchOptions::default()
+        };
+        assert_eq!(
+            search("x", "x\na\nb\nc\nx\nd", options).1,
+            ["1:x", "2-a", "3-b", "4-c", "5:x", "6-d"]
+        );
+    }
+
+    #[test]
+    // -v выбирает несовпадающие строки, контекст - вокруг них
+    fn test_invert() {
+        let options = SearchOptions {
+            after: 1,
+            invert: true,
+            separators: true,
+            ..SearchOptions::default()
+        };
+        assert_eq!(
+            search("x", "x\na\nx\nx\nx\nb", options),
+            (2, ["2:a", "3-x", "--", "6:b"].map(String::from).to_vec())
+        );
+    }
+
+    #[test]
+    // -m: после NUM выбранных строк - только их контекст -A, совпадения в нём - контекст
+    fn test_max_count() {
+        let input = "x\na\nx\nb\nx\nc\n";
+        let options = SearchOptions {
+            max_count: Some(2),
+            after: 2,
+            ..SearchOptions::default()
+        };
+        assert_eq!(
+            search("x", input, options),
+            (
+                2,
+                ["1:x", "2-a", "3:x", "4-b", "5-x"]
+                    .map(String::from)
+                    .to_vec()
+            )
+        );
+
+        let options = SearchOptions {
+            max_count: Some(0),
+            ..SearchOptions::default()
+        };
+        assert_eq!(search("x", input, options), (0, Vec::new()));
+    }
+
+    #[test]
+    // смещения строк - в байтах от начала входа, включая переводы строк
+    fn test_offsets() {
+        #[derive(Default)]
+        struct Offsets(Vec<u64>);
+
+        impl Sink for Offsets {
+            fn matched(&mut self, line: Line) -> io::Result<()> {
+                self.0.push(line.offset);
+                Ok(())
+            }
+
+            fn context(&mut self, line: Line) -> io::Result<()> {
+                self.0.push(line.offset);
+                Ok(())
+            }
+
+            fn separator(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let matcher = Matcher::new(&["x".to_string()], MatchOptions::default()).unwrap();
+        let options = SearchOptions {
+            before: 1,
+            ..SearchOptions::default()
+        };
+        let mut offsets = Offsets::default();
+        Searcher::new(matcher, options)
+            .search("ab\nщx\n\nx".as_bytes(), &mut offsets)
+            .unwrap();
+        assert_eq!(offsets.0, [0, 3, 7, 8]);
+    }
+}
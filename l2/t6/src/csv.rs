@@ -0,0 +1,304 @@
+//! --csv: записи по RFC 4180 - поля в кавычках могут содержать разделитель, переводы строк
+//! и кавычки (удвоенные). Столбцы выбираются по номерам или по именам из заголовка,
+//! вывод - снова CSV: поле берётся в кавычки, только если без них его не прочитать.
+//! Записи выводятся через "\n", "\r\n" во входе тоже принимается
+use crate::list::List;
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+
+// чтение записей из потока
+pub struct Reader<R> {
+    reader: R,
+    delimiter: u8,
+    // номер строки входа, на которой начинается следующая запись (для ошибок)
+    line_number: u64,
+    line: Vec<u8>,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(reader: R, delimiter: u8) -> Self {
+        Reader {
+            reader,
+            delimiter,
+            line_number: 1,
+            line: Vec::new(),
+        }
+    }
+
+    // следующая запись в record; false - вход закончился
+    pub fn read_record(&mut self, record: &mut Vec<Vec<u8>>) -> io::Result<bool> {
+        record.clear();
+        let start = self.line_number;
+        let mut field = Vec::new();
+        // внутри кавычек, и было ли поле в кавычках
+        let mut quoted = false;
+        let mut in_quotes = false;
+
+        loop {
+            self.line.clear();
+            if self.reader.read_until(b'\n', &mut self.line)? == 0 {
+                if in_quotes {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("незакрытая кавычка в записи со строки {}", start),
+                    ));
+                }
+                if record.is_empty() && field.is_empty() && !quoted {
+                    return Ok(false);
+                }
+                record.push(field);
+                return Ok(true);
+            }
+            self.line_number += 1;
+
+            let mut bytes = self.line.iter().copied().peekable();
+            while let Some(byte) = bytes.next() {
+                if in_quotes {
+                    if byte != b'"' {
+                        field.push(byte);
+                    } else if bytes.peek() == Some(&b'"') {
+                        bytes.next();
+                        field.push(b'"');
+                    } else {
+                        in_quotes = false;
+                    }
+                } else if byte == b'"' && field.is_empty() && !quoted {
+                    in_quotes = true;
+                    quoted = true;
+                } else if byte == self.delimiter {
+                    record.push(std::mem::take(&mut field));
+                    quoted = false;
+                } else if byte == b'\n' || (byte == b'\r' && bytes.peek() == Some(&b'\n')) {
+                    record.push(field);
+                    return Ok(true);
+                } else {
+                    // кавычка в середине поля без кавычек - обычный символ
+                    field.push(byte);
+                }
+            }
+        }
+    }
+}
+
+// запись CSV с разделителем delimiter
+pub fn write_record<W: Write + ?Sized>(
+    out: &mut W,
+    fields: &[&[u8]],
+    delimiter: &[u8],
+) -> io::Result<()> {
+    for (index, field) in fields.iter().enumerate() {
+        if index > 0 {
+            out.write_all(delimiter)?;
+        }
+        let needs_quotes = field.iter().any(|&b| b == b'"' || b == b'\n' || b == b'\r')
+            || field
+                .windows(delimiter.len())
+                .any(|window| window == delimiter)
+            // единственное пустое поле без кавычек - пустая строка, а не запись
+            || (fields.len() == 1 && field.is_empty());
+        if needs_quotes {
+            out.write_all(b"\"")?;
+            for (index, part) in field.split(|&b| b == b'"').enumerate() {
+                if index > 0 {
+                    out.write_all(b"\"\"")?;
+                }
+                out.write_all(part)?;
+            }
+            out.write_all(b"\"")?;
+        } else {
+            out.write_all(field)?;
+        }
+    }
+    out.write_all(b"\n")
+}
+
+// выбор столбцов
+#[derive(Debug, Clone)]
+pub enum Columns {
+    // -f: номера в порядке записи
+    Numbers(List),
+    // -F: имена из заголовка в заданном порядке
+    Names(Vec<String>),
+}
+
+// вырезание столбцов CSV
+#[derive(Debug, Clone)]
+pub struct CsvCutter {
+    columns: Columns,
+    complement: bool,
+    // -s: не выводить записи из одного поля
+    only_delimited: bool,
+    delimiter: u8,
+    output_delimiter: Vec<u8>,
+}
+
+impl CsvCutter {
+    pub fn new(
+        columns: Columns,
+        complement: bool,
+        only_delimited: bool,
+        delimiter: u8,
+        output_delimiter: Option<Vec<u8>>,
+    ) -> Self {
+        let columns = match columns {
+            Columns::Numbers(list) if complement => Columns::Numbers(list.complement()),
+            columns => columns,
+        };
+        CsvCutter {
+            columns,
+            complement,
+            only_delimited,
+            delimiter,
+            output_delimiter: output_delimiter.unwrap_or(vec![delimiter]),
+        }
+    }
+
+    // номера столбцов (с 0) по именам из заголовка
+    fn resolve(&self, names: &[String], header: &[Vec<u8>]) -> io::Result<Vec<usize>> {
+        let mut indices = Vec::with_capacity(names.len());
+        for name in names {
+            let index = header
+                .iter()
+                .position(|column| column == name.as_bytes())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("в заголовке нет столбца {:?}", name),
+                    )
+                })?;
+            indices.push(index);
+        }
+        if self.complement {
+            let selected: HashSet<usize> = indices.into_iter().collect();
+            indices = (0..header.len())
+                .filter(|index| !selected.contains(index))
+                .collect();
+        }
+        Ok(indices)
+    }
+
+    // все записи входа; по именам столбцы ищутся в первой записи, она тоже выводится
+    pub fn cut<R: BufRead, W: Write + ?Sized>(&self, reader: R, out: &mut W) -> io::Result<()> {
+        let mut reader = Reader::new(reader, self.delimiter);
+        let mut record = Vec::new();
+        let mut indices: Option<Vec<usize>> = None;
+
+        while reader.read_record(&mut record)? {
+            // запись без разделителя выводится целиком, с -s - пропускается
+            if record.len() == 1 && matches!(self.columns, Columns::Numbers(_)) {
+                if !self.only_delimited {
+                    write_record(out, &[&record[0]], &self.output_delimiter)?;
+                }
+                continue;
+            }
+
+            let fields: Vec<&[u8]> = match &self.columns {
+                Columns::Numbers(list) => record
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| list.contains(index + 1))
+                    .map(|(_, field)| field.as_slice())
+                    .collect(),
+                Columns::Names(names) => {
+                    if indices.is_none() {
+                        indices = Some(self.resolve(names, &record)?);
+                    }
+                    indices
+                        .iter()
+                        .flatten()
+                        .filter_map(|&index| record.get(index))
+                        .map(Vec::as_slice)
+                        .collect()
+                }
+            };
+            write_record(out, &fields, &self.output_delimiter)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Columns, CsvCutter, Reader};
+
+    // записи входа
+    fn records(input: &str) -> Vec<Vec<String>> {
+        let mut reader = Reader::new(input.as_bytes(), b',');
+        let mut record = Vec::new();
+        let mut records = Vec::new();
+        while reader.read_record(&mut record).unwrap() {
+            records.push(
+                record
+                    .iter()
+                    .map(|field| String::from_utf8(field.clone()).unwrap())
+                    .collect(),
+            );
+        }
+        records
+    }
+
+    fn cut(cutter: CsvCutter, input: &str) -> Result<String, String> {
+        let mut out = Vec::new();
+        cutter
+            .cut(input.as_bytes(), &mut out)
+            .map_err(|err| err.to_string())?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    // кавычки, удвоенные кавычки, переводы строк внутри поля, CRLF, пустые поля
+    fn test_reader() {
+        assert_eq!(
+            records("a,\"b,c\",\"say \"\"hi\"\"\"\r\n\"multi\nline\",,x\"y\nlast"),
+            [
+                vec!["a", "b,c", "say \"hi\""],
+                vec!["multi\nline", "", "x\"y"],
+                vec!["last"],
+            ]
+        );
+        assert_eq!(records("\n\"\"\n"), [vec![""], vec![""]]);
+        assert!(records("").is_empty());
+
+        let mut reader = Reader::new("a\n\"b,\nc".as_bytes(), b',');
+        let mut record = Vec::new();
+        assert!(reader.read_record(&mut record).unwrap());
+        assert!(reader.read_record(&mut record).is_err());
+    }
+
+    #[test]
+    // по номерам и именам, с дополнением; вывод - правильный CSV
+    fn test_cut() {
+        let input = "name,\"price, $\",note\napple,\"1,5\",\"\"\"fresh\"\"\"\npear,2\n";
+        let numbers = CsvCutter::new(
+            Columns::Numbers("2-".parse().unwrap()),
+            false,
+            false,
+            b',',
+            None,
+        );
+        assert_eq!(
+            cut(numbers, input).unwrap(),
+            "\"price, $\",note\n\"1,5\",\"\"\"fresh\"\"\"\n2\n"
+        );
+
+        let names = |names: &[&str], complement| {
+            let names = names.iter().map(|name| name.to_string()).collect();
+            CsvCutter::new(
+                Columns::Names(names),
+                complement,
+                false,
+                b',',
+                Some(b";".to_vec()),
+            )
+        };
+        assert_eq!(
+            cut(names(&["note", "name"], false), input).unwrap(),
+            "note;name\n\"\"\"fresh\"\"\";apple\npear\n"
+        );
+        assert_eq!(
+            cut(names(&["price, $"], true), input).unwrap(),
+            "name;note\napple;\"\"\"fresh\"\"\"\npear\n"
+        );
+        assert!(cut(names(&["cost"], false), input).is_err());
+    }
+}
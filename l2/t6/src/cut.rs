@@ -0,0 +1,226 @@
+//! вырезание частей строки: полей между разделителями (-f), байт (-b) или символов (-c).
+//! Номера за концом строки просто пропускаются; части выводятся в порядке строки
+use crate::list::List;
+
+// что вырезается
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mode {
+    // -b
+    Bytes,
+    // -c: символы UTF-8, каждый неверный байт - отдельный символ
+    Chars,
+    // -f: поля между разделителями (строка любой длины)
+    Fields {
+        delimiter: Vec<u8>,
+        // -s: не выводить строки без разделителя
+        only_delimited: bool,
+    },
+}
+
+// вырезание по списку
+#[derive(Debug, Clone)]
+pub struct Cutter {
+    list: List,
+    mode: Mode,
+    // --output-delimiter: для полей - вместо входного разделителя, для байт и символов -
+    // между несмежными диапазонами
+    output_delimiter: Option<Vec<u8>>,
+}
+
+// поля строки между вхождениями разделителя
+fn split<'a>(line: &'a [u8], delimiter: &'a [u8]) -> impl Iterator<Item = &'a [u8]> + 'a {
+    let mut rest = Some(line);
+    std::iter::from_fn(move || {
+        let current = rest?;
+        match current
+            .windows(delimiter.len())
+            .position(|window| window == delimiter)
+        {
+            Some(position) => {
+                rest = Some(&current[position + delimiter.len()..]);
+                Some(&current[..position])
+            }
+            None => {
+                rest = None;
+                Some(current)
+            }
+        }
+    })
+}
+
+// начала символов строки и её длина в конце
+fn char_bounds(line: &[u8]) -> Vec<usize> {
+    let mut bounds = Vec::with_capacity(line.len() + 1);
+    let mut offset = 0;
+    for chunk in line.utf8_chunks() {
+        bounds.extend(
+            chunk
+                .valid()
+                .char_indices()
+                .map(|(index, _)| offset + index),
+        );
+        offset += chunk.valid().len();
+        bounds.extend(offset..offset + chunk.invalid().len());
+        offset += chunk.invalid().len();
+    }
+    bounds.push(offset);
+    bounds
+}
+
+impl Cutter {
+    // complement - выбирать всё, кроме списка
+    pub fn new(
+        list: List,
+        mode: Mode,
+        complement: bool,
+        output_delimiter: Option<Vec<u8>>,
+    ) -> Self {
+        let list = if complement { list.complement() } else { list };
+        Cutter {
+            list,
+            mode,
+            output_delimiter,
+        }
+    }
+
+    // вырезанные части строки (без перевода строки) дописываются в out;
+    // false - строка не выводится
+    pub fn cut(&self, line: &[u8], out: &mut Vec<u8>) -> bool {
+        match &self.mode {
+            Mode::Bytes => self.cut_units(line, line.len(), |index| index, out),
+            Mode::Chars => {
+                let bounds = char_bounds(line);
+                self.cut_units(line, bounds.len() - 1, |index| bounds[index], out);
+            }
+            Mode::Fields {
+                delimiter,
+                only_delimited,
+            } => {
+                let mut fields = split(line, delimiter).peekable();
+                let first = fields.next().unwrap_or_default();
+                // строка без разделителя выводится целиком, с -s - пропускается
+                if fields.peek().is_none() {
+                    if *only_delimited {
+                        return false;
+                    }
+                    out.extend_from_slice(first);
+                    return true;
+                }
+
+                let output_delimiter = self.output_delimiter.as_deref().unwrap_or(delimiter);
+                let mut printed = false;
+                for (index, field) in std::iter::once(first).chain(fields).enumerate() {
+                    if self.list.contains(index + 1) {
+                        if printed {
+                            out.extend_from_slice(output_delimiter);
+                        }
+                        out.extend_from_slice(field);
+                        printed = true;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    // байты или символы: units - их число, offset - смещение начала единицы с номером
+    // от 0 (для units - длина строки)
+    fn cut_units(
+        &self,
+        line: &[u8],
+        units: usize,
+        offset: impl Fn(usize) -> usize,
+        out: &mut Vec<u8>,
+    ) {
+        let mut printed = false;
+        for &(start, end) in self.list.ranges() {
+            if start > units {
+                break;
+            }
+            if printed {
+                if let Some(delimiter) = &self.output_delimiter {
+                    out.extend_from_slice(delimiter);
+                }
+            }
+            out.extend_from_slice(&line[offset(start - 1)..offset(end.min(units))]);
+            printed = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cutter, Mode};
+
+    fn cut(list: &str, mode: Mode, complement: bool, output: Option<&str>, line: &str) -> String {
+        let cutter = Cutter::new(
+            list.parse().unwrap(),
+            mode,
+            complement,
+            output.map(|d| d.as_bytes().to_vec()),
+        );
+        let mut out = Vec::new();
+        if cutter.cut(line.as_bytes(), &mut out) {
+            String::from_utf8(out).unwrap()
+        } else {
+            "<нет>".to_string()
+        }
+    }
+
+    fn fields(delimiter: &str, only_delimited: bool) -> Mode {
+        Mode::Fields {
+            delimiter: delimiter.as_bytes().to_vec(),
+            only_delimited,
+        }
+    }
+
+    #[test]
+    // короткие строки, строки без разделителя, многосимвольный разделитель
+    fn test_fields() {
+        assert_eq!(
+            cut("1,3", fields(":", false), false, None, "a:b:c:d"),
+            "a:c"
+        );
+        assert_eq!(cut("2,5", fields(":", false), false, None, "a:b"), "b");
+        assert_eq!(cut("5", fields(":", false), false, None, "a:b"), "");
+        assert_eq!(cut("2", fields(":", false), false, None, "abc"), "abc");
+        assert_eq!(cut("2", fields(":", true), false, None, "abc"), "<нет>");
+        assert_eq!(
+            cut(
+                "10-",
+                fields(",", false),
+                false,
+                None,
+                "1,2,3,4,5,6,7,8,9,10,11"
+            ),
+            "10,11"
+        );
+        assert_eq!(
+            cut("2-", fields("::", false), false, Some(" | "), "a::b:c::::d"),
+            "b:c |  | d"
+        );
+        assert_eq!(cut("2", fields(":", false), true, None, "a:b:c:d"), "a:c:d");
+    }
+
+    #[test]
+    // байты и символы, --output-delimiter между несмежными диапазонами
+    fn test_units() {
+        assert_eq!(
+            cut("1-2,4-", Mode::Bytes, false, Some(":"), "abcdef"),
+            "ab:def"
+        );
+        assert_eq!(
+            cut("1-2,3,5", Mode::Bytes, false, Some(":"), "abcdef"),
+            "ab:c:e"
+        );
+        assert_eq!(
+            cut("1-3,2-4", Mode::Bytes, false, Some(":"), "abcdef"),
+            "abcd"
+        );
+        assert_eq!(cut("2", Mode::Bytes, true, Some(":"), "abcdef"), "a:cdef");
+        assert_eq!(cut("3-10", Mode::Bytes, false, None, "abcd"), "cd");
+        assert_eq!(cut("7", Mode::Bytes, false, None, "abcd"), "");
+        assert_eq!(cut("2,4", Mode::Chars, false, None, "ёжик"), "жк");
+        assert_eq!(cut("-2", Mode::Bytes, false, None, "ёжик"), "ё");
+    }
+}
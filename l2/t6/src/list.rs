@@ -0,0 +1,135 @@
+//! список номеров полей, байт или символов по POSIX: N, N-, -M, N-M через запятую
+//! или пробел, номера с 1. Диапазоны хранятся упорядоченными, пересекающиеся - слитыми,
+//! так что каждый номер выбирается один раз и в порядке строки
+use std::str::FromStr;
+
+// номера с 1, оба конца включительно; открытый конец - usize::MAX
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct List {
+    ranges: Vec<(usize, usize)>,
+}
+
+// номер из списка
+fn number(text: &str) -> Result<usize, String> {
+    if text.is_empty() || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(format!("неверный номер в списке: {:?}", text));
+    }
+    match text.parse::<usize>() {
+        Ok(0) => Err("номера начинаются с 1".to_string()),
+        Ok(number) => Ok(number),
+        Err(_) => Err(format!("слишком большой номер: {}", text)),
+    }
+}
+
+impl FromStr for List {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let mut ranges = Vec::new();
+        for item in text.split(|c: char| c == ',' || c.is_ascii_whitespace()) {
+            let range = match item.split_once('-') {
+                None => {
+                    let number = number(item)?;
+                    (number, number)
+                }
+                Some(("", "")) => return Err("диапазон без границ: -".to_string()),
+                Some(("", end)) => (1, number(end)?),
+                Some((start, "")) => (number(start)?, usize::MAX),
+                Some((start, end)) => (number(start)?, number(end)?),
+            };
+            if range.0 > range.1 {
+                return Err(format!("убывающий диапазон: {}", item));
+            }
+            ranges.push(range);
+        }
+        Ok(List::from_ranges(ranges))
+    }
+}
+
+impl List {
+    // упорядочивание и слияние пересекающихся диапазонов; соседние не сливаются,
+    // чтобы --output-delimiter разделял их, как в GNU cut
+    fn from_ranges(mut ranges: Vec<(usize, usize)>) -> Self {
+        ranges.sort_unstable();
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        List { ranges: merged }
+    }
+
+    // номера, не попавшие в список (--complement)
+    pub fn complement(&self) -> Self {
+        let mut ranges = Vec::new();
+        let mut next = 1;
+        for &(start, end) in &self.ranges {
+            if start > next {
+                ranges.push((next, start - 1));
+            }
+            if end == usize::MAX {
+                return List { ranges };
+            }
+            next = end + 1;
+        }
+        ranges.push((next, usize::MAX));
+        List { ranges }
+    }
+
+    // диапазоны по возрастанию
+    pub fn ranges(&self) -> &[(usize, usize)] {
+        &self.ranges
+    }
+
+    // входит ли номер в список
+    pub fn contains(&self, number: usize) -> bool {
+        let index = self.ranges.partition_point(|&(_, end)| end < number);
+        self.ranges
+            .get(index)
+            .is_some_and(|&(start, _)| start <= number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::List;
+
+    fn ranges(text: &str) -> Vec<(usize, usize)> {
+        text.parse::<List>().unwrap().ranges().to_vec()
+    }
+
+    #[test]
+    // любые ширины номеров, открытые диапазоны, слияние пересечений
+    fn test_parse() {
+        const END: usize = usize::MAX;
+        assert_eq!(ranges("10"), [(10, 10)]);
+        assert_eq!(ranges("3-"), [(3, END)]);
+        assert_eq!(ranges("-2,12-15"), [(1, 2), (12, 15)]);
+        assert_eq!(ranges("5,1-3 2-4"), [(1, 4), (5, 5)]);
+        assert_eq!(ranges("7-,3-9,1"), [(1, 1), (3, END)]);
+
+        for invalid in ["", "0", "1,,2", "-", "3-1", "a", "1-2-3", "+1", "1.5"] {
+            assert!(invalid.parse::<List>().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    // дополнение и проверка номеров
+    fn test_complement() {
+        let list: List = "2,4-5,8-".parse().unwrap();
+        assert_eq!(list.complement().ranges(), [(1, 1), (3, 3), (6, 7)]);
+        assert_eq!(
+            "1-".parse::<List>().unwrap().complement().ranges(),
+            Vec::new()
+        );
+        assert_eq!(
+            "-3".parse::<List>().unwrap().complement().ranges(),
+            [(4, usize::MAX)]
+        );
+
+        assert!([2, 4, 5, 8, 100].iter().all(|&n| list.contains(n)));
+        assert!(![1, 3, 6, 7].iter().any(|&n| list.contains(n)));
+    }
+}
@@ -1,152 +1,393 @@
-use std::collections::HashSet;
+//! вырезание полей, байт или символов из строк файлов или stdin, как cut из POSIX,
+//! и столбцов CSV по номерам или именам из заголовка
+//!
+//! Пример: t6 -s -f 1-2,4- -d : input.txt
+//!         t6 -d '::' -f 3- --output-delimiter=' | '
+//!         tail -f access.log | t6 -c -10 --complement
+//!         t6 -F name,price goods.csv
+//!
+//! номера в списке начинаются с 1; строки читаются и выводятся по одной, так что вход
+//! может быть бесконечным. Строки - байты: в режиме -b неверный UTF-8 проходит как есть
+mod csv;
+mod cut;
+mod list;
+
+use args::{Arg, Opt, Parser};
+use csv::{Columns, CsvCutter};
+use cut::{Cutter, Mode};
+use list::List;
 use std::env;
-use std::io::{self};
-
-///
-/// пример: t6 -s -f 1-2,3 -d :
-///
-/// айди колонок в аргументе -f начинается с 1 (1-индексировано)
-///
-
-// преобразование строчки с колонками в вектор id колонок
-fn parse_columns(columns_raw: String) -> Vec<usize> {
-    // айдишники колонок без повторений
-    let mut resulting_ids_hashed: HashSet<usize> = HashSet::new();
-    // разделённые по запятой колонки с айди
-    let separated_id_args: Vec<&str> = columns_raw.split(',').collect();
-
-    // если строка из одного символа - запись одного айди, если 3 - проверка интревала и запись всех id из него
-    for arg in separated_id_args {
-        if arg.len() == 1 {
-            resulting_ids_hashed
-                .insert(arg.parse::<usize>().expect("неверный формат id колонки") - 1);
-        } else if arg.len() == 3 {
-            let split_range: Vec<&str> = arg.split('-').collect();
-            let (start, finish): (usize, usize) = (
-                split_range[0]
-                    .parse::<usize>()
-                    .expect("неверный формат id колонки"),
-                split_range[1]
-                    .parse::<usize>()
-                    .expect("неверный формат id колонки"),
-            );
-
-            if start > finish {
-                panic!("неверный аргумент с колонками")
-            }
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, IsTerminal, LineWriter, Write};
+use std::process::exit;
+
+const USAGE: &str = "использование: t6 -b СПИСОК | -c СПИСОК | -f СПИСОК [ПАРАМЕТРЫ] [ФАЙЛ]...
+выводит части строк файлов (без файлов или для \"-\" - stdin);
+СПИСОК - номера с 1 через запятую: N, N-, -M, N-M
+    -b, --bytes=СПИСОК        выбрать байты
+    -c, --characters=СПИСОК   выбрать символы UTF-8
+    -f, --fields=СПИСОК       выбрать поля, строки без разделителя выводятся целиком
+    -d, --delimiter=РАЗД      разделитель полей вместо табуляции, любой длины
+    -s, --only-delimited      не выводить строки без разделителя
+    -F, --names=ИМЕНА         выбрать столбцы CSV по именам из заголовка (через запятую,
+                              в порядке перечисления), включает --csv
+        --csv                 поля - столбцы CSV по RFC 4180 (кавычки, переводы строк
+                              в полях), разделитель по умолчанию - запятая, вывод - CSV
+        --tsv                 то же с табуляцией
+        --complement          выбрать всё, кроме списка
+        --output-delimiter=РАЗД
+                              разделитель в выводе: между полями вместо входного,
+                              между несмежными диапазонами байт и символов
+    -n                        не используется (для совместимости с POSIX)
+        --help                эта справка
+код выхода: 0 - успех, 1 - ошибка (вход, который не прочитать, пропускается)";
+
+// параметры: -n принимается для совместимости и ничего не меняет
+const OPTIONS: [Opt; 12] = [
+    Opt::both('b', "bytes").value(),
+    Opt::both('c', "characters").value(),
+    Opt::both('f', "fields").value(),
+    Opt::both('d', "delimiter").value(),
+    Opt::both('s', "only-delimited"),
+    Opt::both('F', "names").value(),
+    Opt::short('n'),
+    Opt::long("csv"),
+    Opt::long("tsv"),
+    Opt::long("complement"),
+    Opt::long("output-delimiter").value(),
+    Opt::long("help"),
+];
+
+// вырезание строк или записей CSV
+#[derive(Debug)]
+enum Cut {
+    Lines(Cutter),
+    Csv(CsvCutter),
+}
 
-            for id in start..=finish {
-                resulting_ids_hashed.insert(id - 1);
+// разобранные аргументы
+#[derive(Debug)]
+struct Options {
+    cut: Cut,
+    // файлы, "-" - stdin
+    files: Vec<String>,
+}
+
+// параметры по мере разбора
+#[derive(Debug, Default)]
+struct Parsed {
+    // -b, -c или -f и список
+    list: Option<(char, List)>,
+    // -F
+    names: Option<Vec<String>>,
+    // --csv, --tsv - разделитель по умолчанию
+    csv: Option<u8>,
+    delimiter: Option<String>,
+    only_delimited: bool,
+    complement: bool,
+    output_delimiter: Option<String>,
+}
+
+impl Parsed {
+    // применение короткого параметра
+    fn apply(&mut self, flag: char, value: Option<String>) -> Result<(), String> {
+        match flag {
+            'b' | 'c' | 'f' | 'F' if self.list.is_some() || self.names.is_some() => {
+                return Err("можно задать только один список".to_string());
             }
-        } else {
-            panic!("неверный аргумент с колонками")
+            'b' | 'c' | 'f' => {
+                let list = value.unwrap_or_default().parse()?;
+                self.list = Some((flag, list));
+            }
+            'F' => {
+                // имена - сами запись CSV: '"a,b",c' - два имени
+                let value = value.unwrap_or_default();
+                let mut names = Vec::new();
+                csv::Reader::new(value.as_bytes(), b',')
+                    .read_record(&mut names)
+                    .map_err(|err| format!("неверный список имён: {}", err))?;
+                let names = names
+                    .into_iter()
+                    .map(|name| String::from_utf8_lossy(&name).into_owned())
+                    .collect();
+                self.names = Some(names);
+                self.csv.get_or_insert(b',');
+            }
+            'd' => {
+                let delimiter = value.unwrap_or_default();
+                if delimiter.is_empty() {
+                    return Err("разделитель не может быть пустым".to_string());
+                }
+                self.delimiter = Some(delimiter);
+            }
+            's' => self.only_delimited = true,
+            // -n ничего не меняет, неизвестные параметры отсеивает Parser
+            _ => {}
         }
+        Ok(())
     }
 
-    // преобразование множества в вектор, а затем сортировка
-    let mut resulting_ids: Vec<usize> = resulting_ids_hashed.into_iter().collect();
-    resulting_ids.sort();
-
-    resulting_ids
+    // применение параметра без короткой формы
+    fn apply_long(&mut self, name: &str, value: Option<String>) {
+        match name {
+            "csv" => self.csv = Some(b','),
+            "tsv" => self.csv = Some(b'\t'),
+            "complement" => self.complement = true,
+            "output-delimiter" => self.output_delimiter = value,
+            "help" => {
+                println!("{}", USAGE);
+                exit(0);
+            }
+            _ => {}
+        }
+    }
 }
 
-// запись в вектор и проверка входных агрументов
-fn parse_args() -> (Vec<usize>, bool, char) {
-    // возможные аргументы
-    let possible_arguments: HashSet<&str> = HashSet::from(["-f", "-d", "-s"]);
+// разбор аргументов командной строки (без имени программы)
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut parsed = Parsed::default();
+    let mut files = Vec::new();
 
-    // текущие аргументы
-    let mut command_arguments: HashSet<&str> = HashSet::new();
+    for arg in Parser::new(&OPTIONS, args) {
+        match arg? {
+            Arg::Short(flag, value) => parsed.apply(flag, value)?,
+            Arg::Long(name, value) => parsed.apply_long(name, value),
+            Arg::Operand(file) => files.push(file),
+        }
+    }
 
-    // аргументы командной строки
-    let args: Vec<String> = env::args().collect();
+    Ok(Options {
+        cut: cut_from(parsed)?,
+        files,
+    })
+}
 
-    // обработка аргументов
-    if args.len() < 3 {
-        panic!("недостаточное количество аргументов")
+// вырезание строк или CSV по разобранным параметрам
+fn cut_from(parsed: Parsed) -> Result<Cut, String> {
+    let output_delimiter = parsed.output_delimiter.map(String::into_bytes);
+    if let Some(default_delimiter) = parsed.csv {
+        let delimiter = match parsed.delimiter.as_deref().map(str::as_bytes) {
+            None => default_delimiter,
+            Some(&[delimiter]) => delimiter,
+            Some(_) => return Err("разделитель CSV должен быть одним байтом".to_string()),
+        };
+        let columns = match (parsed.list, parsed.names) {
+            (Some(('f', list)), _) => Columns::Numbers(list),
+            (_, Some(names)) => Columns::Names(names),
+            (Some(_), _) => return Err("в CSV выбираются только поля".to_string()),
+            (None, None) => return Err("нужно задать номера или имена столбцов".to_string()),
+        };
+        let cutter = CsvCutter::new(
+            columns,
+            parsed.complement,
+            parsed.only_delimited,
+            delimiter,
+            output_delimiter,
+        );
+        return Ok(Cut::Csv(cutter));
     }
 
-    // требуемые колонки
-    let mut columns_to_take: Vec<usize> = Vec::new();
-    // брать ли колонки без раделителя
-    let mut take_non_delim: bool = true;
-    // разделитель
-    let mut delimiter: char = '\t';
-
-    // проверка и парсинг аргументов
-    let mut i: usize = 1;
-    while i < args.len() {
-        let argument = &args[i];
-
-        // если аргумент не присутсвует в сете возможных
-        if !possible_arguments.contains(argument.as_str()) {
-            panic!("неверный аргумент {:?}", argument);
+    let (flag, list) = parsed
+        .list
+        .ok_or("нужно задать список байт, символов или полей")?;
+    if flag != 'f' {
+        if parsed.delimiter.is_some() {
+            return Err("разделитель задаётся только для полей".to_string());
         }
-
-        // если аргумент повторный
-        if command_arguments.contains(argument.as_str()) {
-            panic!("повторный аргумент {:?}", argument);
+        if parsed.only_delimited {
+            return Err("-s имеет смысл только для полей".to_string());
         }
+    }
+    let mode = match flag {
+        'b' => Mode::Bytes,
+        'c' => Mode::Chars,
+        _ => Mode::Fields {
+            delimiter: parsed.delimiter.unwrap_or("\t".to_string()).into_bytes(),
+            only_delimited: parsed.only_delimited,
+        },
+    };
 
-        // включение колонок без нужного разделителя
-        if argument == "-s" {
-            take_non_delim = false;
-            command_arguments.insert(argument.as_str());
-            i += 1;
-            continue;
+    Ok(Cut::Lines(Cutter::new(
+        list,
+        mode,
+        parsed.complement,
+        output_delimiter,
+    )))
+}
+
+// вырезание из одного входа; у последней строки без перевода строки он добавляется
+fn cut_input<R: BufRead>(cut: &Cut, mut reader: R, out: &mut dyn Write) -> io::Result<()> {
+    let cutter = match cut {
+        Cut::Csv(cutter) => return cutter.cut(reader, out),
+        Cut::Lines(cutter) => cutter,
+    };
+
+    let mut line = Vec::new();
+    let mut cut = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
         }
 
-        if argument == "-f" {
-            // парсинг номера колонок в нужный формат
-            let columns_str = args[i + 1].to_owned();
-            columns_to_take = parse_columns(columns_str);
-        } else if argument == "-d" {
-            // запись разделителя в нужную форму
-            delimiter = args[i + 1]
-                .parse::<char>()
-                .expect("разделитель должен быть одним символом");
+        // строки без разделителя с -s пропускаются
+        cut.clear();
+        if cutter.cut(&line, &mut cut) {
+            cut.push(b'\n');
+            out.write_all(&cut)?;
         }
+    }
+}
+
+// вырезание из всех входов по порядку; ошибки входов - в stderr, результат - были ли они.
+// В терминал вывод идёт построчно, в файлы и каналы - буферизованно
+fn run(options: &Options) -> io::Result<bool> {
+    let stdout = io::stdout().lock();
+    let mut out: Box<dyn Write> = if stdout.is_terminal() {
+        Box::new(LineWriter::new(stdout))
+    } else {
+        Box::new(BufWriter::new(stdout))
+    };
 
-        i += 2;
-        command_arguments.insert(argument.as_str());
+    let stdin = ["-".to_string()];
+    let files = if options.files.is_empty() {
+        &stdin[..]
+    } else {
+        &options.files[..]
+    };
+
+    let mut ok = true;
+    for name in files {
+        let result = if name == "-" {
+            cut_input(&options.cut, io::stdin().lock(), &mut out)
+        } else {
+            File::open(name)
+                .and_then(|file| cut_input(&options.cut, BufReader::new(file), &mut out))
+        };
+
+        match result {
+            Ok(()) => {}
+            // вывод закрыт читателем (t6 ... | head)
+            Err(err) if err.kind() == ErrorKind::BrokenPipe => return Ok(ok),
+            Err(err) => {
+                out.flush()?;
+                eprintln!("t6: {}: {}", name, args::io_error(&err));
+                ok = false;
+            }
+        }
     }
 
-    (columns_to_take, take_non_delim, delimiter)
+    match out.flush() {
+        Err(err) if err.kind() != ErrorKind::BrokenPipe => Err(err),
+        _ => Ok(ok),
+    }
 }
 
 fn main() {
-    // парсинг аргументов
-    let (columns_to_take, take_non_delim, delimiter) = parse_args();
-
-    // запись строки из std в вектор
-    let mut lines: Vec<String> = io::stdin()
-        .lines()
-        .map(|line_res| line_res.unwrap())
-        .collect();
-
-    // удаление колонки без разделителя если нужно
-    if !take_non_delim {
-        lines.retain(|line| line.contains(delimiter));
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("t6: {}\nподробнее: t6 --help", err);
+            exit(1);
+        }
+    };
+
+    match run(&options) {
+        Ok(true) => {}
+        Ok(false) => exit(1),
+        Err(err) => {
+            eprintln!("t6: {}", args::io_error(&err));
+            exit(1);
+        }
     }
+}
 
-    // взятие нужных колонок из всех строчек и запись результата в массив строчек
-    let mut vec_of_columns: Vec<Vec<String>> = Vec::new();
-    for line in lines {
-        // разделение колонок по разделителю
-        let columns: Vec<&str> = line.split(delimiter).collect();
+#[cfg(test)]
+mod tests {
+    use super::{cut_input, parse_args, Cut};
 
-        // запись нужных колонок в массив
-        let mut columns_temp: Vec<String> = Vec::new();
-        for id in &columns_to_take {
-            columns_temp.push(columns[*id].to_owned())
+    // вырезание из строки (или записей CSV) с аргументами через пробел
+    fn cut(args: &str, line: &str) -> Result<String, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        let options = parse_args(&args)?;
+        let mut out = Vec::new();
+        match options.cut {
+            Cut::Lines(cutter) => {
+                cutter.cut(line.as_bytes(), &mut out);
+            }
+            Cut::Csv(cutter) => cutter
+                .cut(line.as_bytes(), &mut out)
+                .map_err(|err| err.to_string())?,
         }
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    // режимы, длинные параметры, разделители; неверные сочетания - ошибки
+    fn test_parse_args() {
+        assert_eq!(cut("-s -f 1-2,4- -d :", "a:b:c:d:e").unwrap(), "a:b:d:e");
+        assert_eq!(cut("-d:: -f3-", "a::b::c::d").unwrap(), "c::d");
+        assert_eq!(cut("--fields=2 --compl", "a\tb\tc").unwrap(), "a\tc");
+        assert_eq!(cut("-nb 2-3", "abcd").unwrap(), "bc");
+        assert_eq!(
+            cut("--characters -1,3 --output-delimiter=|", "ёжик").unwrap(),
+            "ё|и"
+        );
+
+        // CSV: по именам, по номерам, TSV
+        let csv = "id,name,\"price, $\"\n1,\"a \"\"b\"\"\",2\n";
+        assert_eq!(
+            cut("-F \"price,_$\",id", &csv.replace(' ', "_")).unwrap(),
+            "\"price,_$\",id\n2,1\n"
+        );
+        assert_eq!(
+            cut("--csv -f 2 -s", csv).unwrap(),
+            "name\n\"a \"\"b\"\"\"\n"
+        );
+        assert_eq!(cut("--tsv -f2", "a\t\"b\tc\"\n").unwrap(), "\"b\tc\"\n");
 
-        // запись нужных колонок в вектор со всеми строчками
-        vec_of_columns.push(columns_temp)
+        let args: Vec<String> = ["-f1", "a", "-", "--", "-b"].map(String::from).to_vec();
+        assert_eq!(parse_args(&args).unwrap().files, ["a", "-", "-b"]);
+
+        for invalid in [
+            "",
+            "-f",
+            "-f 0",
+            "-f 1 -b 1",
+            "-b 1 -d :",
+            "-c 1 -s",
+            "-f 1 -d",
+            "--c 1",
+            "--csv -b 1",
+            "--csv",
+            "-F a -f 1",
+            "-F a -d ::",
+            "-f 1 --bogus",
+            "-x",
+        ] {
+            assert!(cut(invalid, "").is_err(), "{:?}", invalid);
+        }
     }
 
-    // вывод нужых колонок с нужным разделителем в stdout
-    for new_line in vec_of_columns {
-        println!("{:?}", new_line.join(&delimiter.to_string()))
+    #[test]
+    // поток строк: последняя строка без перевода строки, неверный UTF-8 проходит как есть
+    fn test_cut_input() {
+        let input: &[u8] = b"a:\xff\xfe:c\nxyz\n\xd0:b";
+        let output = |args: &str| {
+            let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+            let options = parse_args(&args).unwrap();
+            let mut out = Vec::new();
+            cut_input(&options.cut, input, &mut out).unwrap();
+            out
+        };
+        assert_eq!(output("-d : -f 2"), b"\xff\xfe\nxyz\nb\n");
+        assert_eq!(output("-d : -f 1 -s"), b"a\n\xd0\n");
+        assert_eq!(output("-b 2-3"), b":\xff\nyz\n:b\n");
+        assert_eq!(output("-c 3"), b"\xff\nz\nb\n");
     }
 }
@@ -0,0 +1,142 @@
+//! потоковое чтение текста блоками: блок кончается на границе символа, а для слов
+//! и n-грамм - перед пробельным символом; остаток переносится в следующий блок,
+//! так что в памяти - не больше пары блоков, сколько бы ни весил файл
+use crate::count::Mode;
+use std::io::{self, Read};
+
+// блоки текста из потока
+pub struct TextBlocks<R> {
+    reader: R,
+    mode: Mode,
+    block_size: usize,
+    // начало следующего блока, прочитанное с предыдущим
+    carry: Vec<u8>,
+    // смещение carry от начала потока (для ошибок)
+    offset: u64,
+    eof: bool,
+}
+
+impl<R: Read> TextBlocks<R> {
+    pub fn new(reader: R, mode: Mode, block_size: usize) -> Self {
+        TextBlocks {
+            reader,
+            mode,
+            block_size: block_size.max(1),
+            carry: Vec::new(),
+            offset: 0,
+            eof: false,
+        }
+    }
+
+    // следующий блок; None - поток закончился
+    pub fn next_block(&mut self) -> io::Result<Option<String>> {
+        let mut buf = std::mem::take(&mut self.carry);
+        let mut target = self.block_size;
+
+        loop {
+            if !self.eof && buf.len() < target {
+                let wanted = (target - buf.len()) as u64;
+                let read = self.reader.by_ref().take(wanted).read_to_end(&mut buf)?;
+                self.eof = read == 0;
+                if !self.eof {
+                    continue;
+                }
+            }
+            if buf.is_empty() {
+                return Ok(None);
+            }
+
+            // целые символы: незаконченный в конце блока символ дочитывается потом
+            let valid = match std::str::from_utf8(&buf) {
+                Ok(text) => text.len(),
+                Err(err) if err.error_len().is_none() && !self.eof => err.valid_up_to(),
+                Err(err) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "неверный UTF-8 на байте {}",
+                            self.offset + err.valid_up_to() as u64
+                        ),
+                    ))
+                }
+            };
+            let text = std::str::from_utf8(&buf[..valid]).unwrap_or_default();
+            let end = if self.eof && valid == buf.len() {
+                valid
+            } else if self.mode.by_words() {
+                text.rfind(char::is_whitespace).unwrap_or(0)
+            } else {
+                valid
+            };
+
+            // ни одной границы в блоке (длинное слово) - блок растёт
+            if end == 0 {
+                target = buf.len() + self.block_size;
+                continue;
+            }
+
+            self.carry = buf.split_off(end);
+            self.offset += end as u64;
+            return String::from_utf8(buf)
+                .map(Some)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TextBlocks;
+    use crate::count::Mode;
+    use std::io::{self, Read};
+
+    // поток, отдающий по одному байту за чтение
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match (self.0.split_first(), buf.is_empty()) {
+                (Some((&byte, rest)), false) => {
+                    buf[0] = byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    fn blocks(text: &[u8], mode: Mode, block_size: usize) -> io::Result<Vec<String>> {
+        let mut blocks = TextBlocks::new(Trickle(text), mode, block_size);
+        let mut result = Vec::new();
+        while let Some(block) = blocks.next_block()? {
+            result.push(block);
+        }
+        Ok(result)
+    }
+
+    #[test]
+    // символы и слова не разрезаются, весь текст сохраняется
+    fn test_blocks() {
+        let text = "ёж ёлка\nдом   кот";
+        for size in 1..=text.len() + 1 {
+            let chars = blocks(text.as_bytes(), Mode::Chars, size).unwrap();
+            assert_eq!(chars.concat(), text);
+            assert!(chars.iter().all(|block| !block.is_empty()));
+
+            let words = blocks(text.as_bytes(), Mode::Words, size).unwrap();
+            assert_eq!(words.concat(), text);
+            for block in &words[1..] {
+                assert!(block.starts_with(char::is_whitespace), "{:?}", words);
+            }
+        }
+        assert!(blocks(b"", Mode::Letters, 4).unwrap().is_empty());
+    }
+
+    #[test]
+    // неверный UTF-8 и незаконченный символ в конце - ошибки
+    fn test_invalid() {
+        assert!(blocks(b"ab\xffcd", Mode::Chars, 2).is_err());
+        assert!(blocks("аб".as_bytes().split_last().unwrap().1, Mode::Chars, 8).is_err());
+    }
+}
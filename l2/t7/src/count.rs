@@ -0,0 +1,293 @@
+//! подсчёт частот: символов, букв любой письменности, слов (по правилам Unicode UAX #29)
+//! или n-грамм - последовательностей из n символов внутри слова.
+//! Текст делится на части только по границам символов, а для слов и n-грамм - по пробелам,
+//! так что ни символ, ни слово не разрезаются между потоками. Каждый поток считает в свой
+//! Counter: единицы из одного символа ASCII - в массиве, остальные - в хеш-таблице
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
+
+// что считается
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    // все символы, включая пробелы и переводы строк
+    Chars,
+    // буквы любой письменности
+    Letters,
+    Words,
+    // n символов подряд внутри слова
+    NGrams(usize),
+}
+
+impl Mode {
+    // считаются ли единицы внутри слов
+    pub fn by_words(self) -> bool {
+        matches!(self, Mode::Words | Mode::NGrams(_))
+    }
+}
+
+// настройки подсчёта
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountOptions {
+    pub mode: Mode,
+    // -i: без учёта регистра (всё в нижнем регистре)
+    pub fold_case: bool,
+}
+
+impl Default for CountOptions {
+    fn default() -> Self {
+        CountOptions {
+            mode: Mode::Letters,
+            fold_case: false,
+        }
+    }
+}
+
+// деление текста примерно на parts частей: граница сдвигается вперёд до границы символа,
+// а для слов и n-грамм - до пробельного символа
+pub fn split_chunks(text: &str, parts: usize, mode: Mode) -> Vec<&str> {
+    let size = text.len().div_ceil(parts.max(1)).max(1);
+    let mut chunks = Vec::with_capacity(parts);
+    let mut start = 0;
+
+    while start < text.len() {
+        let mut end = (start + size).min(text.len());
+        while !text.is_char_boundary(end) {
+            end += 1;
+        }
+        if mode.by_words() {
+            end = text[end..]
+                .find(char::is_whitespace)
+                .map_or(text.len(), |offset| end + offset);
+        }
+        chunks.push(&text[start..end]);
+        start = end;
+    }
+    chunks
+}
+
+// счётчик единиц одного потока
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counter {
+    options: CountOptions,
+    // единицы из одного символа ASCII по его коду
+    ascii: [u64; 128],
+    other: HashMap<String, u64>,
+}
+
+impl Counter {
+    pub fn new(options: CountOptions) -> Self {
+        Counter {
+            options,
+            ascii: [0; 128],
+            other: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, item: &str) {
+        match item.as_bytes() {
+            &[byte] if byte.is_ascii() => self.ascii[byte as usize] += 1,
+            _ => match self.other.get_mut(item) {
+                Some(count) => *count += 1,
+                None => {
+                    self.other.insert(item.to_string(), 1);
+                }
+            },
+        }
+    }
+
+    // подсчёт единиц текста
+    pub fn add_text(&mut self, text: &str) {
+        match self.options.mode {
+            Mode::Chars | Mode::Letters => {
+                let letters = self.options.mode == Mode::Letters;
+                let mut buf = [0; 4];
+                for c in text.chars() {
+                    if letters && !c.is_alphabetic() {
+                        continue;
+                    }
+                    if c.is_ascii() {
+                        let c = if self.options.fold_case {
+                            c.to_ascii_lowercase()
+                        } else {
+                            c
+                        };
+                        self.ascii[c as usize] += 1;
+                    } else if self.options.fold_case && !c.is_lowercase() {
+                        // у некоторых символов нижний регистр - несколько символов (İ)
+                        self.add(&c.to_lowercase().collect::<String>());
+                    } else {
+                        self.add(c.encode_utf8(&mut buf));
+                    }
+                }
+            }
+            Mode::Words | Mode::NGrams(_) => {
+                for word in text.unicode_words() {
+                    let word = if self.options.fold_case {
+                        word.to_lowercase()
+                    } else {
+                        word.to_string()
+                    };
+                    match self.options.mode {
+                        Mode::NGrams(n) => {
+                            let bounds: Vec<usize> = word
+                                .char_indices()
+                                .map(|(index, _)| index)
+                                .chain([word.len()])
+                                .collect();
+                            for window in bounds.windows(n + 1) {
+                                self.add(&word[window[0]..window[n]]);
+                            }
+                        }
+                        _ => self.add(&word),
+                    }
+                }
+            }
+        }
+    }
+
+    // слияние счётчика другого потока
+    pub fn merge(&mut self, other: Counter) {
+        for (total, count) in self.ascii.iter_mut().zip(other.ascii) {
+            *total += count;
+        }
+        for (item, count) in other.other {
+            *self.other.entry(item).or_insert(0) += count;
+        }
+    }
+
+    // число вхождений каждой встреченной единицы
+    pub fn into_counts(self) -> HashMap<String, u64> {
+        let mut counts = self.other;
+        for (code, &count) in self.ascii.iter().enumerate() {
+            if count > 0 {
+                counts.insert(char::from(code as u8).to_string(), count);
+            }
+        }
+        counts
+    }
+}
+
+// частота единицы
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Frequency {
+    pub item: String,
+    pub count: u64,
+    // доля от всех единиц
+    pub frequency: f64,
+}
+
+// частоты по убыванию числа, при равенстве - по единице
+pub fn frequencies(counts: HashMap<String, u64>) -> (u64, Vec<Frequency>) {
+    let total: u64 = counts.values().sum();
+    let mut frequencies: Vec<Frequency> = counts
+        .into_iter()
+        .map(|(item, count)| Frequency {
+            item,
+            count,
+            frequency: count as f64 / total as f64,
+        })
+        .collect();
+    frequencies.sort_by(|a, b| (Reverse(a.count), &a.item).cmp(&(Reverse(b.count), &b.item)));
+    (total, frequencies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frequencies, split_chunks, CountOptions, Counter, Mode};
+    use std::collections::HashMap;
+
+    // число вхождений каждой единицы текста в одном счётчике
+    fn count(text: &str, options: CountOptions) -> HashMap<String, u64> {
+        let mut counter = Counter::new(options);
+        counter.add_text(text);
+        counter.into_counts()
+    }
+
+    fn counted(text: &str, mode: Mode, fold_case: bool) -> Vec<(String, u64)> {
+        let counts = count(text, CountOptions { mode, fold_case });
+        frequencies(counts)
+            .1
+            .into_iter()
+            .map(|f| (f.item, f.count))
+            .collect()
+    }
+
+    fn pairs(expected: &[(&str, u64)]) -> Vec<(String, u64)> {
+        expected.iter().map(|&(s, n)| (s.to_string(), n)).collect()
+    }
+
+    #[test]
+    // части не разрезают символы и слова, вместе - весь текст
+    fn test_split_chunks() {
+        let text = "ёж ёлка\nдом   кот";
+        for parts in 1..=text.len() + 1 {
+            let chunks = split_chunks(text, parts, Mode::Chars);
+            assert_eq!(chunks.concat(), text);
+            assert!(chunks.len() <= parts);
+
+            let options = CountOptions {
+                mode: Mode::Words,
+                fold_case: false,
+            };
+            let chunks = split_chunks(text, parts, Mode::Words);
+            assert_eq!(chunks.concat(), text);
+            let mut words = Counter::new(options);
+            for chunk in &chunks {
+                let mut counter = Counter::new(options);
+                counter.add_text(chunk);
+                words.merge(counter);
+            }
+            assert_eq!(words.into_counts(), count(text, options), "{:?}", chunks);
+        }
+        assert!(split_chunks("", 4, Mode::Letters).is_empty());
+    }
+
+    #[test]
+    // буквы любой письменности, все символы, регистр
+    fn test_chars() {
+        assert_eq!(
+            counted("Ab, аБ; αβ A", Mode::Letters, false),
+            pairs(&[("A", 2), ("b", 1), ("α", 1), ("β", 1), ("Б", 1), ("а", 1)])
+        );
+        assert_eq!(
+            counted("Ab аБ A", Mode::Letters, true),
+            pairs(&[("a", 2), ("b", 1), ("а", 1), ("б", 1)])
+        );
+        assert_eq!(
+            counted("a a\n", Mode::Chars, false),
+            pairs(&[("a", 2), ("\n", 1), (" ", 1)])
+        );
+        assert_eq!(counted("İ", Mode::Chars, true), pairs(&[("i\u{307}", 1)]));
+        // однобуквенные слова ASCII - тоже в массиве счётчика
+        assert_eq!(
+            counted("a A ab", Mode::Words, true),
+            pairs(&[("a", 2), ("ab", 1)])
+        );
+    }
+
+    #[test]
+    // слова по UAX #29 и n-граммы внутри слов
+    fn test_words() {
+        assert_eq!(
+            counted("Earth's sea, the SEA; 3.5 sea", Mode::Words, true),
+            pairs(&[("sea", 3), ("3.5", 1), ("earth's", 1), ("the", 1)])
+        );
+        assert_eq!(
+            counted("мама, ма", Mode::NGrams(2), false),
+            pairs(&[("ма", 3), ("ам", 1)])
+        );
+        assert!(counted("ab", Mode::NGrams(3), false).is_empty());
+    }
+
+    #[test]
+    // доли от общего числа
+    fn test_frequencies() {
+        let (total, frequencies) = frequencies(count("aab", CountOptions::default()));
+        assert_eq!(total, 3);
+        assert_eq!(frequencies[0].item, "a");
+        assert!((frequencies[0].frequency - 2.0 / 3.0).abs() < 1e-12);
+        assert!((frequencies.iter().map(|f| f.frequency).sum::<f64>() - 1.0).abs() < 1e-12);
+    }
+}
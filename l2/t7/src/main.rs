@@ -1,158 +1,384 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs::read_to_string;
-use std::str;
-use std::sync::mpsc;
-use std::time::Instant;
-use std::{env, thread};
-
-///
-/// пример использования: t7 -f 5 input.txt
-///
+//! частотный анализ текста файла в пуле потоков: символы, буквы, слова или n-граммы,
+//! результат - числа и доли по убыванию в JSON или таблицей.
+//! Файл читается блоками, пока пул считает предыдущий блок; --bench сравнивает
+//! время подсчёта на разном числе потоков
+//!
+//! пример использования: t7 -f 5 input.txt
+//!                       t7 -f 4 -w -i --table input.txt
+//!                       t7 -n 2 -i input.txt
+//!                       t7 --bench -f 8 input.txt
+mod blocks;
+mod count;
 
-// структура для печати в формате json
-#[derive(Serialize, Deserialize, Debug)]
-struct Result {
-    elapsed: String,
-    result: HashMap<char, u32>,
+use args::{Arg, Opt, Parser};
+use blocks::TextBlocks;
+use count::{CountOptions, Counter, Frequency, Mode};
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use serde::Serialize;
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process::exit;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "использование: t7 [ПАРАМЕТРЫ] ФАЙЛ
+считает частоты в тексте файла (по умолчанию - буквы любой письменности);
+ФАЙЛ \"-\" - стандартный ввод
+    -f, --threads=N      число потоков (по числу процессоров)
+    -c, --chars          считать все символы, включая пробелы и переводы строк
+    -l, --letters        считать буквы
+    -w, --words          считать слова
+    -n, --ngrams=N       считать последовательности из N символов внутри слов
+    -i, --ignore-case    без учёта регистра
+    -t, --table          вывести таблицей, а не JSON
+        --bench          сравнить время на 1, 2, 4... потоках (до -f или числа процессоров)
+        --help           эта справка";
+
+// размер блока чтения
+const BLOCK_SIZE: usize = 4 << 20;
+
+// частей блока на поток: части неравны по времени, мелкие выравнивают нагрузку
+const CHUNKS_PER_THREAD: usize = 4;
+
+// запусков на каждое число потоков в --bench (берётся лучшее время)
+const BENCH_RUNS: usize = 3;
+
+// параметры
+const OPTIONS: [Opt; 9] = [
+    Opt::both('f', "threads").value(),
+    Opt::both('c', "chars"),
+    Opt::both('l', "letters"),
+    Opt::both('w', "words"),
+    Opt::both('n', "ngrams").value(),
+    Opt::both('i', "ignore-case"),
+    Opt::both('t', "table"),
+    Opt::long("bench"),
+    Opt::long("help"),
+];
+
+// разобранные аргументы
+#[derive(Debug, Default)]
+struct Options {
+    // 0 - по числу процессоров
+    threads: usize,
+    count: CountOptions,
+    table: bool,
+    bench: bool,
+    filename: String,
 }
 
-// чтение из файла
-fn read_lines_from_file(filename: &str) -> Vec<String> {
-    let mut file_lines = Vec::new();
-    for line in read_to_string(filename).unwrap().lines() {
-        file_lines.push(line.to_string())
+impl Options {
+    // применение короткого параметра
+    fn apply(&mut self, flag: char, value: Option<String>) -> Result<(), String> {
+        let positive = |value: Option<String>, what: &str| {
+            let value = value.unwrap_or_default();
+            match value.parse::<usize>() {
+                Ok(number) if number > 0 => Ok(number),
+                _ => Err(format!("неверное число {}: {:?}", what, value)),
+            }
+        };
+
+        match flag {
+            'f' => self.threads = positive(value, "потоков")?,
+            'c' => self.count.mode = Mode::Chars,
+            'l' => self.count.mode = Mode::Letters,
+            'w' => self.count.mode = Mode::Words,
+            'n' => self.count.mode = Mode::NGrams(positive(value, "символов в n-грамме")?),
+            'i' => self.count.fold_case = true,
+            't' => self.table = true,
+            // неизвестные параметры отсеивает Parser
+            _ => {}
+        }
+        Ok(())
     }
-    file_lines
 }
 
-// считает символы из строки и записывает в HashMap
-fn string_to_count(string: String) -> HashMap<char, u32> {
-    let mut char_hash: HashMap<char, u32> = HashMap::new();
-    for c in string.chars() {
-        let char_ascii = c as u32;
-        if (65..=90).contains(&char_ascii) || (97..=122).contains(&char_ascii) {
-            match char_hash.get_mut(&c) {
-                Some(elem) => {
-                    *elem += 1;
-                }
-                None => {
-                    char_hash.insert(c, 1);
-                }
-            };
+// разбор аргументов командной строки (без имени программы)
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut operands = Vec::new();
+
+    for arg in Parser::new(&OPTIONS, args) {
+        match arg? {
+            Arg::Short(flag, value) => options.apply(flag, value)?,
+            Arg::Long("bench", _) => options.bench = true,
+            Arg::Long("help", _) => {
+                println!("{}", USAGE);
+                exit(0);
+            }
+            Arg::Long(..) => {}
+            Arg::Operand(operand) => operands.push(operand),
         }
     }
 
-    char_hash
+    options.filename = match operands.as_slice() {
+        [filename] => filename.clone(),
+        [] => return Err("не задан файл".to_string()),
+        [_, extra, ..] => return Err(format!("лишний операнд '{}'", extra)),
+    };
+    Ok(options)
 }
 
-// запись и проверка входных агрументов
-fn parse_args() -> (usize, String) {
-    // аргументы командной строки
-    let args: Vec<String> = env::args().collect();
+// структура для печати в формате json
+#[derive(Serialize, Debug)]
+struct Report {
+    // время подсчёта в миллисекундах
+    elapsed_ms: f64,
+    threads: usize,
+    // всего единиц
+    total: u64,
+    // по убыванию числа
+    result: Vec<Frequency>,
+}
 
-    // количество трэдов
-    let mut threads_num: usize = 1;
+// пул из threads потоков (0 - по числу процессоров)
+fn build_pool(threads: usize) -> io::Result<ThreadPool> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(io::Error::other)
+}
 
-    // парсинг в зависимости от числа аргументов
-    let filename: String = match args.len() {
-        2 => args[1].to_owned(),
-        4 => {
-            if args[1] != "-f" {
-                panic!("неверный аргумент {:?}", args[1])
-            };
+// подсчёт блока: части считаются потоками пула в свои счётчики, затем сливаются
+fn count_block(block: &str, threads: usize, options: CountOptions) -> Counter {
+    count::split_chunks(block, threads * CHUNKS_PER_THREAD, options.mode)
+        .par_iter()
+        .fold(
+            || Counter::new(options),
+            |mut counter, chunk| {
+                counter.add_text(chunk);
+                counter
+            },
+        )
+        .reduce(
+            || Counter::new(options),
+            |mut total, counter| {
+                total.merge(counter);
+                total
+            },
+        )
+}
 
-            // парсинг номера трэдов
-            threads_num = args[2]
-                .parse::<usize>()
-                .expect("неверный формат количества трэдов");
+// подсчёт потока: следующий блок читается, пока пул считает текущий
+fn count_reader<R: Read + Send>(
+    pool: &ThreadPool,
+    reader: R,
+    options: CountOptions,
+) -> io::Result<Counter> {
+    let mut blocks = TextBlocks::new(reader, options.mode, BLOCK_SIZE);
+    let mut total = Counter::new(options);
+    let threads = pool.current_num_threads();
 
-            args[3].to_owned()
+    pool.install(|| {
+        let mut block = blocks.next_block()?;
+        while let Some(text) = block {
+            let (next, counter) = rayon::join(
+                || blocks.next_block(),
+                || count_block(&text, threads, options),
+            );
+            total.merge(counter);
+            block = next?;
         }
-        _ => {
-            panic!("неверное количество элементов")
-        }
-    };
+        Ok(total)
+    })
+}
 
-    (threads_num, filename)
+// подсчёт файла ("-" - стандартный ввод)
+fn count_file(pool: &ThreadPool, filename: &str, options: CountOptions) -> io::Result<Counter> {
+    if filename == "-" {
+        count_reader(pool, io::stdin(), options)
+    } else {
+        count_reader(pool, File::open(filename)?, options)
+    }
 }
 
-fn main() {
-    // начальное время
-    let now = Instant::now();
+// таблица: единица (пробельные и управляющие символы - экранированными), число, доля
+fn write_table<W: Write>(out: &mut W, report: &Report) -> io::Result<()> {
+    let items: Vec<String> = report
+        .result
+        .iter()
+        .map(|frequency| frequency.item.escape_debug().to_string())
+        .collect();
+    let item_width = items
+        .iter()
+        .map(|item| item.chars().count())
+        .chain(["элемент".chars().count()])
+        .max()
+        .unwrap_or(0);
+    let count_width = report.total.to_string().len().max("число".chars().count());
 
-    // парсинг аргументов командной строки
-    let (threads_num, filename) = parse_args();
-    // каналы
-    let (tx, rx) = mpsc::channel();
-
-    // читает строки из файла и разбивает по трэдам насколько это возможно - не UTF символы не учитываются
-    let string_of_chars: String = read_lines_from_file(&filename).join("");
-    let elem_per_thread = string_of_chars.len().div_ceil(threads_num);
-    let chunked_data: Vec<&str> = string_of_chars
-        .as_bytes()
-        .chunks(elem_per_thread)
-        .map(str::from_utf8)
-        .filter(|result| !result.is_err())
-        .map(|result| result.unwrap())
+    writeln!(
+        out,
+        "{:<item_width$}  {:>count_width$}  {:>8}",
+        "элемент", "число", "доля"
+    )?;
+    for (item, frequency) in items.iter().zip(&report.result) {
+        writeln!(
+            out,
+            "{:<item_width$}  {:>count_width$}  {:>7.3}%",
+            item,
+            frequency.count,
+            frequency.frequency * 100.0
+        )?;
+    }
+    writeln!(
+        out,
+        "всего: {}, потоков: {}, {:.3} мс",
+        report.total, report.threads, report.elapsed_ms
+    )
+}
+
+// число потоков для --bench: 1, 2, 4... и сам максимум
+fn bench_threads(max: usize) -> Vec<usize> {
+    let mut threads: Vec<usize> = std::iter::successors(Some(1), |n| Some(n * 2))
+        .take_while(|&n| n < max)
         .collect();
+    threads.push(max.max(1));
+    threads
+}
 
-    // массив трэдов для обработки
-    let mut handles = vec![];
+// --bench: лучшее из нескольких время на каждом числе потоков и ускорение относительно
+// одного потока; результаты всех запусков должны совпасть
+fn bench<W: Write>(out: &mut W, options: &Options) -> io::Result<()> {
+    if options.filename == "-" {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "для --bench нужен файл: стандартный ввод не перечитать",
+        ));
+    }
+    let max = match options.threads {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        threads => threads,
+    };
 
-    // цикл для работы с частями данных
-    for chunk in chunked_data {
-        // передача владения частью данных
-        let chunk_owned = chunk.to_owned();
+    let mut expected: Option<Counter> = None;
+    let mut base: Option<Duration> = None;
+    writeln!(
+        out,
+        "{:>7}  {:>10}  {:>9}",
+        "потоков", "время, мс", "ускорение"
+    )?;
+    for threads in bench_threads(max) {
+        let pool = build_pool(threads)?;
+        let mut best = Duration::MAX;
+        for _ in 0..BENCH_RUNS {
+            let now = Instant::now();
+            let counter = count_file(&pool, &options.filename, options.count)?;
+            best = best.min(now.elapsed());
+            match &expected {
+                Some(expected) if *expected != counter => {
+                    return Err(io::Error::other(format!(
+                        "на {} потоках результат отличается от 1 потока",
+                        threads
+                    )))
+                }
+                Some(_) => {}
+                None => expected = Some(counter),
+            }
+        }
+        let base = *base.get_or_insert(best);
+        writeln!(
+            out,
+            "{:>7}  {:>10.3}  {:>8.2}x",
+            threads,
+            best.as_secs_f64() * 1000.0,
+            base.as_secs_f64() / best.as_secs_f64().max(f64::MIN_POSITIVE)
+        )?;
+    }
+    Ok(())
+}
 
-        // клонирование канала отправки
-        let tx_clone = tx.clone();
+// подсчёт и печать результата
+fn run<W: Write>(out: &mut W, options: &Options) -> io::Result<()> {
+    if options.bench {
+        return bench(out, options);
+    }
 
-        // создания отдельного трэда для обработки
-        let handle = thread::spawn(move || {
-            // подсчёт чисел в каждом отдельном трэде
-            let hash_set = string_to_count(chunk_owned);
+    let pool = build_pool(options.threads)?;
+    // начальное время
+    let now = Instant::now();
+    let counter = count_file(&pool, &options.filename, options.count)
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", options.filename, err)))?;
+    let (total, result) = count::frequencies(counter.into_counts());
 
-            // отправка счёта в главный поток
-            tx_clone.send(hash_set).unwrap();
-        });
+    // создание объекта результата для финального вывода
+    let report = Report {
+        elapsed_ms: now.elapsed().as_secs_f64() * 1000.0,
+        threads: pool.current_num_threads(),
+        total,
+        result,
+    };
 
-        // добавления хэндлера в массив
-        handles.push(handle);
+    if options.table {
+        write_table(out, &report)
+    } else {
+        serde_json::to_writer(&mut *out, &report)?;
+        writeln!(out)
     }
+}
 
-    // ожидание окончания работы трэдов
-    for handle in handles {
-        handle.join().unwrap();
+fn main() {
+    // парсинг аргументов командной строки
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("t7: {}\nподробнее: t7 --help", err);
+            exit(1);
+        }
+    };
+
+    // закрытый читателем вывод (t7 ... | head) - не ошибка
+    let mut out = io::stdout().lock();
+    if let Err(err) = run(&mut out, &options) {
+        if err.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("t7: {}", err);
+            exit(1);
+        }
     }
+}
 
-    // удаление оригинального канала отправки
-    drop(tx);
+#[cfg(test)]
+mod tests {
+    use super::parse_args;
+    use crate::count::Mode;
 
-    // обработка финальных данных (соединение всех hashMap из трэдов в единый объект)
-    let mut final_result: HashMap<char, u32> = HashMap::new();
-    rx.iter().for_each(|map| {
-        for (key, value) in map {
-            match final_result.get_mut(&key) {
-                Some(elem) => {
-                    *elem += value;
-                }
-                None => {
-                    final_result.insert(key, value);
-                }
-            };
-        }
-    });
+    // разбор строки аргументов через пробел
+    fn parse(args: &str) -> Result<super::Options, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        parse_args(&args)
+    }
 
-    // конечное время
-    let time_elapsed_string = format!("elapsed: {} ms", now.elapsed().as_millis());
+    #[test]
+    // режимы и числа; последний режим важнее
+    fn test_parse_args() {
+        let options = parse("-f 5 input.txt").unwrap();
+        assert_eq!(options.threads, 5);
+        assert_eq!(options.count.mode, Mode::Letters);
+        assert_eq!(options.filename, "input.txt");
 
-    // создание объекта результата для финального вывода
-    let result = Result {
-        elapsed: time_elapsed_string,
-        result: final_result,
-    };
+        let options = parse("-wit --ngrams=3 -f2 a").unwrap();
+        assert_eq!(options.count.mode, Mode::NGrams(3));
+        assert!(options.count.fold_case && options.table);
+        assert_eq!(options.threads, 2);
 
-    // печать финальных данных
-    println!("{:?}", serde_json::to_string(&result).unwrap());
+        let options = parse("--bench -").unwrap();
+        assert!(options.bench);
+        assert_eq!(options.threads, 0);
+        assert_eq!(options.filename, "-");
+
+        for invalid in [
+            "",
+            "a b",
+            "-f 0 a",
+            "-n x a",
+            "-f",
+            "-x a",
+            "--table=1 a",
+            "--bench=2 a",
+        ] {
+            assert!(parse(invalid).is_err(), "{:?}", invalid);
+        }
+    }
 }